@@ -0,0 +1,34 @@
+// Run with `cargo fuzz run deserialize_state` from the repository root.
+// vaporwm is a binary, so the modules the saved state needs are compiled in here
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serialized_state::SerializedState;
+use serialized_state::SERIALIZED_STATE_VERSION;
+
+#[allow(dead_code)]
+#[path = "../../src/config.rs"]
+mod config;
+
+#[allow(dead_code)]
+#[path = "../../src/theme.rs"]
+mod theme;
+
+#[path = "../../src/serialized_state.rs"]
+mod serialized_state;
+
+fuzz_target!(|data: &[u8]| {
+    // The same as when the WM starts
+    let Some(mut state) = serde_json::from_slice::<SerializedState>(data)
+        .ok()
+        .filter(|state| state.version <= SERIALIZED_STATE_VERSION)
+    else {
+        return;
+    };
+
+    state.sanitize();
+
+    if let Err(error) = state.check_invariants() {
+        panic!("Sanitized state is invalid: {error}");
+    }
+});
@@ -0,0 +1,85 @@
+use crate::api::Api;
+use crate::client::Client;
+use crate::config;
+use crate::config::WindowAnimation;
+use std::time::Duration;
+use std::time::Instant;
+
+// Animations are advanced by the main loop, this is how often it wakes up while any is in progress
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+// How far below its actual position a sliding window starts
+const SLIDE_DISTANCE: f64 = 40.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AnimationKind {
+    // Played when a window gets mapped, see config::WINDOW_ANIMATION
+    Map,
+
+    // The frame flashes when a window gets raised, see config::RAISE_FLASH
+    RaiseFlash,
+}
+
+pub struct Animation {
+    client_id: u32,
+    kind: AnimationKind,
+    started_at: Instant,
+}
+
+impl Animation {
+    pub fn new(api: &Api, client: &Client, kind: AnimationKind) -> Self {
+        let this = Self {
+            client_id: client.id(),
+            kind,
+            started_at: Instant::now(),
+        };
+
+        this.apply(api, client, 0.0);
+
+        this
+    }
+
+    pub fn client_id(&self) -> u32 {
+        self.client_id
+    }
+
+    pub fn kind(&self) -> AnimationKind {
+        self.kind
+    }
+
+    // Returns false once the animation has finished
+    pub fn advance(&self, api: &Api, client: &Client) -> bool {
+        let duration = match self.kind {
+            AnimationKind::Map => config::WINDOW_ANIMATION_DURATION,
+            AnimationKind::RaiseFlash => config::RAISE_FLASH_DURATION,
+        };
+
+        let progress = (self.started_at.elapsed().as_secs_f64() / duration.as_secs_f64()).min(1.0);
+
+        self.apply(api, client, progress);
+
+        progress < 1.0
+    }
+
+    fn apply(&self, api: &Api, client: &Client, progress: f64) {
+        if self.kind == AnimationKind::RaiseFlash {
+            client.set_flashing(progress < 1.0);
+            return;
+        }
+
+        // Ease out, so the window settles down smoothly
+        let eased = 1.0 - (1.0 - progress).powi(3);
+
+        match config::WINDOW_ANIMATION {
+            WindowAnimation::None => {}
+            WindowAnimation::Fade => {
+                api.set_window_opacity(client.container_id(), (progress < 1.0).then_some(eased))
+            }
+            // Position is calculated from the client every frame, so moving it meanwhile is fine
+            WindowAnimation::Slide => api.set_window_y(
+                client.container_id(),
+                client.container_y() + ((1.0 - eased) * SLIDE_DISTANCE).round() as i16,
+            ),
+        }
+    }
+}
@@ -1,20 +1,31 @@
-use crate::keycode::Keycode;
+use crate::hints::SizeHints;
 use nix::poll::poll;
 use nix::poll::PollFd;
 use nix::poll::PollFlags;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::os::fd::AsRawFd;
 use std::os::fd::BorrowedFd;
 use std::time::Duration;
+use std::time::Instant;
 use x11rb::atom_manager;
 use x11rb::connection::Connection;
+use x11rb::cookie::Cookie;
 use x11rb::cookie::VoidCookie;
 use x11rb::properties::WmClassCookie;
+use x11rb::properties::WmHints;
+use x11rb::properties::WmSizeHints;
+use x11rb::protocol::shape::ConnectionExt as _;
+use x11rb::protocol::shape::SK;
+use x11rb::protocol::shape::SO;
 use x11rb::protocol::xproto::Allow;
 use x11rb::protocol::xproto::AtomEnum;
 use x11rb::protocol::xproto::ButtonIndex;
 use x11rb::protocol::xproto::ChangeWindowAttributesAux;
 use x11rb::protocol::xproto::ClientMessageData;
 use x11rb::protocol::xproto::ClientMessageEvent;
+use x11rb::protocol::xproto::ClipOrdering;
 use x11rb::protocol::xproto::ColormapAlloc;
 use x11rb::protocol::xproto::ConfigWindow;
 use x11rb::protocol::xproto::ConfigureRequestEvent;
@@ -23,8 +34,11 @@ use x11rb::protocol::xproto::ConnectionExt;
 use x11rb::protocol::xproto::CreateWindowAux;
 use x11rb::protocol::xproto::EventMask;
 use x11rb::protocol::xproto::GetGeometryReply;
+use x11rb::protocol::xproto::GetPropertyReply;
 use x11rb::protocol::xproto::GetWindowAttributesReply;
+use x11rb::protocol::xproto::Grab;
 use x11rb::protocol::xproto::GrabMode;
+use x11rb::protocol::xproto::GrabStatus;
 use x11rb::protocol::xproto::InputFocus;
 use x11rb::protocol::xproto::ModMask;
 use x11rb::protocol::xproto::PropMode;
@@ -94,6 +108,13 @@ define_cursors! {
         bottom_right_corner,
         left_ptr,
         hand,
+        top_left_corner,
+        top_side,
+        top_right_corner,
+        right_side,
+        bottom_side,
+        bottom_left_corner,
+        left_side,
     }
 }
 
@@ -103,22 +124,62 @@ atom_manager! {
     AtomsCookie {
         WM_PROTOCOLS,
         WM_DELETE_WINDOW,
+        WM_TAKE_FOCUS,
         WM_STATE,
         _NET_WM_NAME,
         _NET_WM_ICON,
+        _NET_WM_MOVERESIZE,
+        _NET_STARTUP_ID,
         UTF8_STRING,
     }
 }
 
+// Atoms which are rarely used go through Api::atom() instead of Atoms, these are interned in advance
+const PRELOADED_ATOMS: &[&str] = &[
+    "_NET_WM_WINDOW_OPACITY",
+    "_NET_SUPPORTED",
+    "_NET_SUPPORTING_WM_CHECK",
+    "VAPORWM_SELECTION",
+    "_NET_STARTUP_INFO_BEGIN",
+    "_NET_STARTUP_INFO",
+    "_XROOTPMAP_ID",
+    "ESETROOT_PMAP_ID",
+];
+
+// Everything needed to manage a window which was there before us
+pub struct WindowSnapshot {
+    pub attributes: GetWindowAttributesReply,
+    pub geometry: GetGeometryReply,
+    pub class: Option<String>,
+    pub title: Option<String>,
+    pub icon: Option<cairo::ImageSurface>,
+    pub size_hints: SizeHints,
+}
+
 pub struct Api {
     connection: XCBConnection,
     screen_index: usize,
     pub cursors: Cursors,
     pub atoms: Atoms,
+
+    // Atoms by their names, filled on demand
+    atom_cache: RefCell<HashMap<String, u32>>,
+
+    // Result of the last query_pointer(), dropped whenever new events are read,
+    // so the pointer is queried at most once per batch of events
+    pointer_position: Cell<Option<(i16, i16)>>,
+
     visual_id: u32,
     colormap_id: u32,
     cairo: Cairo,
     pub default_icon: cairo::ImageSurface,
+
+    // Background of the root window, kept alive as other clients can refer to it by _XROOTPMAP_ID
+    root_pixmap: Cell<Option<u32>>,
+
+    // Gets the focus when no client should have it. With the focus on the root window,
+    // keys would go to whatever window is under the pointer
+    focus_sink: u32,
 }
 
 impl Api {
@@ -138,17 +199,28 @@ impl Api {
 
         let visual_id = visual.visual_id;
         let colormap_id = create_colormap(&connection, screen, visual_id);
-        let cairo = Cairo::new(&connection, visual);
+        let root_visual = screen
+            .allowed_depths
+            .iter()
+            .filter(|depth| depth.depth == screen.root_depth)
+            .flat_map(|depth| &depth.visuals)
+            .find(|visual| visual.visual_id == screen.root_visual)
+            .unwrap();
+
+        let cairo = Cairo::new(&connection, visual, root_visual);
 
         let db = resource_manager::new_from_default(&connection).unwrap();
         let cursors = CursorsCookie::new(&connection, &db, screen_index).reply();
         let atoms = Atoms::new(&connection).unwrap().reply().unwrap();
+        let focus_sink = connection.generate_id().unwrap();
 
-        Self {
+        let this = Self {
             connection,
             screen_index,
             cursors,
             atoms,
+            atom_cache: RefCell::new(HashMap::new()),
+            pointer_position: Cell::new(None),
             visual_id,
             colormap_id,
             cairo,
@@ -156,7 +228,78 @@ impl Api {
                 let mut stream = include_bytes!("../assets/default-icon.png").as_slice();
                 cairo::ImageSurface::create_from_png(&mut stream).unwrap()
             },
+            focus_sink,
+            root_pixmap: Cell::new(None),
+        };
+
+        this.intern_atoms(PRELOADED_ATOMS);
+
+        // Off screen, but it has to be mapped to be focusable
+        this.create_input_only_window(
+            focus_sink,
+            -1,
+            -1,
+            1,
+            1,
+            CreateWindowAux::new().override_redirect(1),
+        );
+
+        this.map_window(focus_sink);
+
+        this
+    }
+
+    pub fn atom(&self, name: &str) -> u32 {
+        if let Some(atom) = self.atom_cache.borrow().get(name) {
+            return *atom;
         }
+
+        let atom = self
+            .connection
+            .intern_atom(false, name.as_bytes())
+            .unwrap()
+            .reply()
+            .unwrap()
+            .atom;
+
+        self.atom_cache.borrow_mut().insert(name.to_owned(), atom);
+
+        atom
+    }
+
+    // Sends all the requests before waiting for any reply, so it takes a single round trip
+    pub fn intern_atoms(&self, names: &[&str]) {
+        let cookies = names
+            .iter()
+            .map(|name| self.connection.intern_atom(false, name.as_bytes()).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut atom_cache = self.atom_cache.borrow_mut();
+
+        for (name, cookie) in names.iter().zip(cookies) {
+            atom_cache.insert((*name).to_owned(), cookie.reply().unwrap().atom);
+        }
+    }
+
+    // For debugging, None if there's no such atom.
+    // Names looked up here are cached too, so the same atom only costs a round trip once
+    pub fn atom_name(&self, atom: u32) -> Option<String> {
+        let cached_name = self
+            .atom_cache
+            .borrow()
+            .iter()
+            .find(|(_, cached_atom)| **cached_atom == atom)
+            .map(|(name, _)| name.clone());
+
+        if cached_name.is_some() {
+            return cached_name;
+        }
+
+        let reply = self.connection.get_atom_name(atom).ok()?.reply().ok()?;
+        let name = String::from_utf8(reply.name).ok()?;
+
+        self.atom_cache.borrow_mut().insert(name.clone(), atom);
+        Some(name)
     }
 
     fn screen(&self) -> &Screen {
@@ -189,6 +332,28 @@ impl Api {
         );
     }
 
+    // Only has an effect when a compositor is running. None makes the window fully opaque
+    pub fn set_window_opacity(&self, window: u32, opacity: Option<f64>) {
+        match opacity {
+            Some(opacity) => check(
+                self.connection
+                    .change_property32(
+                        PropMode::REPLACE,
+                        window,
+                        self.atom("_NET_WM_WINDOW_OPACITY"),
+                        AtomEnum::CARDINAL,
+                        &[(opacity.clamp(0.0, 1.0) * u32::MAX as f64) as u32],
+                    )
+                    .unwrap(),
+            ),
+            None => check(
+                self.connection
+                    .delete_property(window, self.atom("_NET_WM_WINDOW_OPACITY"))
+                    .unwrap(),
+            ),
+        }
+    }
+
     pub fn set_window_x(&self, window: u32, x: i16) {
         check(
             self.connection
@@ -261,9 +426,67 @@ impl Api {
             .map(|reply| String::from_utf8_lossy(reply.class()).into_owned())
     }
 
+    // The window a dialog belongs to
+    pub fn get_window_transient_for(&self, window: u32) -> Option<u32> {
+        self.connection
+            .get_property(
+                false,
+                window,
+                AtomEnum::WM_TRANSIENT_FOR,
+                AtomEnum::WINDOW,
+                0,
+                1,
+            )
+            .unwrap()
+            .reply()
+            .ok()
+            .and_then(|reply| reply.value32()?.next())
+            .filter(|parent| *parent != 0 && *parent != window)
+    }
+
+    pub fn get_window_size_hints(&self, window: u32) -> SizeHints {
+        WmSizeHints::get_normal_hints(&self.connection, window)
+            .unwrap()
+            .reply()
+            .map(SizeHints::from)
+            .unwrap_or_default()
+    }
+
+    // Atoms listed in WM_PROTOCOLS, e.g. WM_DELETE_WINDOW
+    pub fn get_window_protocols(&self, window: u32) -> Vec<u32> {
+        self.connection
+            .get_property(
+                false,
+                window,
+                self.atoms.WM_PROTOCOLS,
+                AtomEnum::ATOM,
+                0,
+                u32::MAX,
+            )
+            .unwrap()
+            .reply()
+            .ok()
+            .and_then(|reply| reply.value32().map(|atoms| atoms.collect()))
+            .unwrap_or_default()
+    }
+
+    // Input field of WM_HINTS, whether the window wants us to set the focus on it.
+    // ICCCM says to assume it does if the field is missing
+    pub fn get_window_input_hint(&self, window: u32) -> bool {
+        WmHints::get(&self.connection, window)
+            .unwrap()
+            .reply()
+            .ok()
+            .and_then(|hints| hints.input)
+            .unwrap_or(true)
+    }
+
     pub fn get_window_title(&self, window: u32) -> Option<String> {
-        let reply = self
-            .connection
+        self.parse_window_title(self.request_window_title(window).reply().unwrap())
+    }
+
+    fn request_window_title(&self, window: u32) -> Cookie<'_, XCBConnection, GetPropertyReply> {
+        self.connection
             .get_property(
                 false,
                 window,
@@ -273,9 +496,9 @@ impl Api {
                 u32::MAX,
             )
             .unwrap()
-            .reply()
-            .unwrap();
+    }
 
+    fn parse_window_title(&self, reply: GetPropertyReply) -> Option<String> {
         (reply.type_ == self.atoms.UTF8_STRING)
             .then(|| String::from_utf8_lossy(&reply.value).into_owned())
     }
@@ -316,14 +539,71 @@ impl Api {
         );
     }
 
-    pub fn grab_key(&self, window: u32, modmask: ModMask, keycode: Keycode) {
+    // Covers the whole screen, transparent and override redirect. Its input shape is empty,
+    // so the pointer goes through it to whatever is below
+    pub fn create_overlay_window(&self, window: u32) {
+        self.create_window(
+            window,
+            0,
+            0,
+            self.screen_width(),
+            self.screen_height(),
+            CreateWindowAux::new()
+                .override_redirect(1)
+                .background_pixel(0),
+        );
+
+        check(
+            self.connection
+                .shape_rectangles(
+                    SO::SET,
+                    SK::INPUT,
+                    ClipOrdering::UNSORTED,
+                    window,
+                    0,
+                    0,
+                    &[],
+                )
+                .unwrap(),
+        );
+    }
+
+    pub fn create_input_only_window(
+        &self,
+        window: u32,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        values: CreateWindowAux,
+    ) {
+        check(
+            self.connection
+                .create_window(
+                    0,
+                    window,
+                    self.root(),
+                    x,
+                    y,
+                    width,
+                    height,
+                    0,
+                    WindowClass::INPUT_ONLY,
+                    x11rb::COPY_FROM_PARENT,
+                    &values,
+                )
+                .unwrap(),
+        );
+    }
+
+    pub fn grab_key(&self, window: u32, modmask: ModMask, keycode: u8) {
         check(
             self.connection
                 .grab_key(
                     false,
                     window,
                     modmask,
-                    keycode as u8,
+                    keycode,
                     GrabMode::ASYNC,
                     GrabMode::ASYNC,
                 )
@@ -331,21 +611,138 @@ impl Api {
         );
     }
 
+    pub fn ungrab_all_keys(&self, window: u32) {
+        check(
+            self.connection
+                .ungrab_key(Grab::ANY, window, ModMask::ANY)
+                .unwrap(),
+        );
+    }
+
+    // If a keysym is produced by several keycodes, the lowest one is used
+    pub fn get_keysym_table(&self) -> HashMap<u32, u8> {
+        let setup = self.connection.setup();
+
+        let reply = self
+            .connection
+            .get_keyboard_mapping(setup.min_keycode, setup.max_keycode - setup.min_keycode + 1)
+            .unwrap()
+            .reply()
+            .unwrap();
+
+        let mut table = HashMap::new();
+
+        for (index, keysyms) in reply
+            .keysyms
+            .chunks(reply.keysyms_per_keycode as _)
+            .enumerate()
+        {
+            for keysym in keysyms {
+                table
+                    .entry(*keysym)
+                    .or_insert(setup.min_keycode + index as u8);
+            }
+        }
+
+        table
+    }
+
     pub fn flush(&self) {
         self.connection.flush().unwrap();
     }
 
-    pub fn wait_for_events(&self, duration: Duration) -> impl Iterator<Item = Event> + '_ {
+    // Also wakes up when any of 'other_fds' becomes readable, their owners have to check them
+    pub fn wait_for_events(
+        &self,
+        duration: Duration,
+        other_fds: &[BorrowedFd],
+    ) -> impl Iterator<Item = Event> + '_ {
         // SAFETY: connection definitely lives long enough
         let fd = unsafe { BorrowedFd::borrow_raw(self.connection.as_raw_fd()) };
-        let fds = &mut [PollFd::new(&fd, PollFlags::POLLIN)];
-        poll(fds, duration.as_millis() as _).unwrap();
 
-        std::iter::from_coroutine(|| {
-            while let Some(event) = self.connection.poll_for_event().unwrap() {
-                yield event;
+        let mut fds = std::iter::once(&fd)
+            .chain(other_fds)
+            .map(|fd| PollFd::new(fd, PollFlags::POLLIN))
+            .collect::<Vec<_>>();
+
+        poll(&mut fds, duration.as_millis() as _).unwrap();
+        self.pointer_position.set(None);
+
+        std::iter::from_coroutine(
+            #[coroutine]
+            || {
+                while let Some(event) = self.connection.poll_for_event().unwrap() {
+                    yield event;
+                }
+            },
+        )
+    }
+
+    // Uses a separate connection, so that waiting for SelectionNotify doesn't swallow our events.
+    // Gives up after the timeout, the selection owner might be hung
+    pub fn get_selection(&self, selection: u32, timeout: Duration) -> Option<String> {
+        let (connection, _) = XCBConnection::connect(None).ok()?;
+        let window = connection.generate_id().ok()?;
+
+        connection
+            .create_window(
+                0,
+                window,
+                self.root(),
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_ONLY,
+                0,
+                &CreateWindowAux::new(),
+            )
+            .ok()?;
+
+        connection
+            .convert_selection(
+                window,
+                selection,
+                self.atoms.UTF8_STRING,
+                self.atom("VAPORWM_SELECTION"),
+                x11rb::CURRENT_TIME,
+            )
+            .ok()?;
+
+        connection.flush().ok()?;
+
+        let deadline = Instant::now() + timeout;
+
+        let event = loop {
+            match connection.poll_for_event().ok()? {
+                Some(Event::SelectionNotify(event)) => break event,
+                Some(_) => continue,
+                None => {}
             }
-        })
+
+            let timeout = deadline.checked_duration_since(Instant::now())?;
+
+            // SAFETY: connection definitely lives long enough
+            let fd = unsafe { BorrowedFd::borrow_raw(connection.as_raw_fd()) };
+            let fds = &mut [PollFd::new(&fd, PollFlags::POLLIN)];
+            poll(fds, timeout.as_millis() as _).ok()?;
+        };
+
+        // The owner couldn't convert the selection
+        if event.property == x11rb::NONE {
+            return None;
+        }
+
+        let reply = connection
+            .get_property(true, window, event.property, AtomEnum::ANY, 0, u32::MAX)
+            .ok()?
+            .reply()
+            .ok()?;
+
+        // Large selections are transferred incrementally (INCR), we don't support that
+        (reply.type_ == self.atoms.UTF8_STRING || reply.type_ == u32::from(AtomEnum::STRING))
+            .then(|| String::from_utf8_lossy(&reply.value).into_owned())
     }
 
     pub fn generate_id(&self) -> u32 {
@@ -376,12 +773,80 @@ impl Api {
         .unwrap()
     }
 
-    pub fn reparent_window(&self, window: u32, parent: u32, offset_x: i16, offset_y: i16) {
+    // Replaces the background of the root window with whatever paint() draws over the whole
+    // screen. It's also advertised to compositors and pseudo-transparent terminals
+    pub fn set_root_background(&self, paint: impl FnOnce(&cairo::Context)) {
+        let root = self.root();
+        let pixmap = self.connection.generate_id().unwrap();
+
+        check(
+            self.connection
+                .create_pixmap(
+                    self.screen().root_depth,
+                    pixmap,
+                    root,
+                    self.screen_width(),
+                    self.screen_height(),
+                )
+                .unwrap(),
+        );
+
+        let surface = cairo::XCBSurface::create(
+            &self.cairo.connection,
+            &cairo::XCBDrawable(pixmap),
+            &self.cairo.root_visual,
+            self.screen_width() as _,
+            self.screen_height() as _,
+        )
+        .unwrap();
+
+        paint(&cairo::Context::new(&surface).unwrap());
+        surface.flush();
+        surface.finish();
+
         check(
             self.connection
-                .reparent_window(window, parent, offset_x, offset_y)
+                .change_window_attributes(
+                    root,
+                    &ChangeWindowAttributesAux::new().background_pixmap(pixmap),
+                )
                 .unwrap(),
         );
+
+        for name in ["_XROOTPMAP_ID", "ESETROOT_PMAP_ID"] {
+            check(
+                self.connection
+                    .change_property32(
+                        PropMode::REPLACE,
+                        root,
+                        self.atom(name),
+                        AtomEnum::PIXMAP,
+                        &[pixmap],
+                    )
+                    .unwrap(),
+            );
+        }
+
+        check(self.connection.clear_area(false, root, 0, 0, 0, 0).unwrap());
+
+        if let Some(old_pixmap) = self.root_pixmap.replace(Some(pixmap)) {
+            check(self.connection.free_pixmap(old_pixmap).unwrap());
+        }
+    }
+
+    // Waits for the result, false if the window doesn't exist anymore
+    pub fn try_reparent_window(
+        &self,
+        window: u32,
+        parent: u32,
+        offset_x: i16,
+        offset_y: i16,
+    ) -> bool {
+        self.connection
+            .reparent_window(window, parent, offset_x, offset_y)
+            .unwrap()
+            .check()
+            .is_ok()
     }
 
     pub fn add_to_save_set(&self, window: u32) {
@@ -441,7 +906,80 @@ impl Api {
         check(self.connection.destroy_window(window).unwrap());
     }
 
+    pub fn get_startup_id(&self, window: u32) -> Option<String> {
+        let reply = self
+            .connection
+            .get_property(
+                false,
+                window,
+                self.atoms._NET_STARTUP_ID,
+                self.atoms.UTF8_STRING,
+                0,
+                u32::MAX,
+            )
+            .ok()?
+            .reply()
+            .ok()?;
+
+        (reply.type_ == self.atoms.UTF8_STRING && !reply.value.is_empty())
+            .then(|| String::from_utf8_lossy(&reply.value).into_owned())
+    }
+
+    // Tells launchers that the application has started, so they stop showing the busy feedback.
+    // The message is split into 20 byte client messages, the first one has a different type
+    pub fn complete_startup(&self, startup_id: &str) {
+        let escaped_id = startup_id.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut message = format!("remove: ID=\"{escaped_id}\"").into_bytes();
+        message.push(0);
+
+        // Receivers put the chunks together by the window they come from, so it has to be ours
+        let window = self.generate_id();
+        self.create_input_only_window(window, -1, -1, 1, 1, CreateWindowAux::new());
+
+        for (index, chunk) in message.chunks(20).enumerate() {
+            let mut data = [0; 20];
+            data[..chunk.len()].copy_from_slice(chunk);
+
+            let type_ = if index == 0 {
+                self.atom("_NET_STARTUP_INFO_BEGIN")
+            }
+            else {
+                self.atom("_NET_STARTUP_INFO")
+            };
+
+            check(
+                self.connection
+                    .send_event(
+                        false,
+                        self.root(),
+                        EventMask::PROPERTY_CHANGE,
+                        ClientMessageEvent {
+                            response_type: 33,
+                            format: 8,
+                            sequence: 0,
+                            window,
+                            type_,
+                            data: ClientMessageData::from(data),
+                        },
+                    )
+                    .unwrap(),
+            );
+        }
+
+        self.destroy_window(window);
+    }
+
     pub fn ask_window_to_close(&self, window: u32) {
+        self.send_protocol_message(window, self.atoms.WM_DELETE_WINDOW, x11rb::CURRENT_TIME);
+    }
+
+    // For clients which set the focus themselves, ICCCM wants the time of the event
+    // which has caused the focus change
+    pub fn ask_window_to_take_focus(&self, window: u32, time: u32) {
+        self.send_protocol_message(window, self.atoms.WM_TAKE_FOCUS, time);
+    }
+
+    fn send_protocol_message(&self, window: u32, protocol: u32, time: u32) {
         check(
             self.connection
                 .send_event(
@@ -454,13 +992,7 @@ impl Api {
                         sequence: 0,
                         window,
                         type_: self.atoms.WM_PROTOCOLS,
-                        data: ClientMessageData::from([
-                            self.atoms.WM_DELETE_WINDOW,
-                            x11rb::CURRENT_TIME,
-                            0,
-                            0,
-                            0,
-                        ]),
+                        data: ClientMessageData::from([protocol, time, 0, 0, 0]),
                     },
                 )
                 .unwrap(),
@@ -481,6 +1013,155 @@ impl Api {
                 .warp_pointer(x11rb::NONE, self.root(), 0, 0, 0, 0, x as _, y as _)
                 .unwrap(),
         );
+
+        self.pointer_position.set(Some((x as _, y as _)));
+    }
+
+    // Motion events during the grab are reported relative to 'window'
+    pub fn grab_pointer(&self, window: u32, cursor: u32) {
+        self.grab_pointer_confined_to(window, cursor, x11rb::NONE);
+    }
+
+    // Same as grab_pointer()
+    pub fn confine_pointer_to_screen(&self, window: u32, cursor: u32) {
+        self.grab_pointer_confined_to(window, cursor, self.root());
+    }
+
+    fn grab_pointer_confined_to(&self, window: u32, cursor: u32, confine_to: u32) {
+        // The grab may fail if someone else has grabbed the pointer, there's nothing to do about it
+        let _ = self
+            .connection
+            .grab_pointer(
+                false,
+                window,
+                EventMask::BUTTON_MOTION | EventMask::BUTTON_RELEASE,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                confine_to,
+                cursor,
+                x11rb::CURRENT_TIME,
+            )
+            .unwrap();
+    }
+
+    // Unlike grab_pointer(), all button presses and motion anywhere on the screen are reported
+    // relative to 'window', so it can tell clicks outside of itself
+    pub fn grab_pointer_for_popup(&self, window: u32) {
+        // Same as for grab_pointer_confined_to()
+        let _ = self
+            .connection
+            .grab_pointer(
+                false,
+                window,
+                EventMask::BUTTON_PRESS | EventMask::POINTER_MOTION,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+                self.cursors.left_ptr,
+                x11rb::CURRENT_TIME,
+            )
+            .unwrap();
+    }
+
+    pub fn ungrab_pointer(&self) {
+        check(self.connection.ungrab_pointer(x11rb::CURRENT_TIME).unwrap());
+    }
+
+    // Fails if someone else has grabbed the keyboard, e.g. a fullscreen game. Whoever needs
+    // the keys then has to give up, otherwise it'd be stuck waiting for them
+    pub fn grab_keyboard(&self, window: u32) -> bool {
+        self.connection
+            .grab_keyboard(
+                false,
+                window,
+                x11rb::CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )
+            .unwrap()
+            .reply()
+            .is_ok_and(|reply| reply.status == GrabStatus::SUCCESS)
+    }
+
+    pub fn ungrab_keyboard(&self) {
+        check(
+            self.connection
+                .ungrab_keyboard(x11rb::CURRENT_TIME)
+                .unwrap(),
+        );
+    }
+
+    // Root-relative position
+    pub fn query_pointer(&self) -> (i16, i16) {
+        if let Some(position) = self.pointer_position.get() {
+            return position;
+        }
+
+        let reply = self
+            .connection
+            .query_pointer(self.root())
+            .unwrap()
+            .reply()
+            .unwrap();
+
+        let position = (reply.root_x, reply.root_y);
+        self.pointer_position.set(Some(position));
+
+        position
+    }
+
+    // Only for children of the root, their geometry is root-relative
+    pub fn is_pointer_in_window(&self, window: u32) -> bool {
+        let (x, y) = self.query_pointer();
+        let geometry = self.get_window_geometry(window);
+
+        (geometry.x..geometry.x + geometry.width as i16).contains(&x)
+            && (geometry.y..geometry.y + geometry.height as i16).contains(&y)
+    }
+
+    // Clients look for _NET_SUPPORTING_WM_CHECK to know whether hints from _NET_SUPPORTED can be used
+    pub fn advertise_supported_hints(&self, name: &str, hints: &[u32]) {
+        let window = self.generate_id();
+
+        self.create_input_only_window(window, -1, -1, 1, 1, CreateWindowAux::new());
+
+        for target in [self.root(), window] {
+            check(
+                self.connection
+                    .change_property32(
+                        PropMode::REPLACE,
+                        target,
+                        self.atom("_NET_SUPPORTING_WM_CHECK"),
+                        AtomEnum::WINDOW,
+                        &[window],
+                    )
+                    .unwrap(),
+            );
+        }
+
+        check(
+            self.connection
+                .change_property8(
+                    PropMode::REPLACE,
+                    window,
+                    self.atoms._NET_WM_NAME,
+                    self.atoms.UTF8_STRING,
+                    name.as_bytes(),
+                )
+                .unwrap(),
+        );
+
+        check(
+            self.connection
+                .change_property32(
+                    PropMode::REPLACE,
+                    self.root(),
+                    self.atom("_NET_SUPPORTED"),
+                    AtomEnum::ATOM,
+                    hints,
+                )
+                .unwrap(),
+        );
     }
 
     pub fn set_focus(&self, window: impl Into<Option<u32>>) {
@@ -488,7 +1169,7 @@ impl Api {
             self.connection
                 .set_input_focus(
                     InputFocus::NONE,
-                    window.into().unwrap_or(self.root()),
+                    window.into().unwrap_or(self.focus_sink),
                     x11rb::CURRENT_TIME,
                 )
                 .unwrap(),
@@ -506,26 +1187,40 @@ impl Api {
         );
     }
 
-    pub fn get_window_children(&self, window: u32) -> Vec<u32> {
+    pub fn lower_window(&self, window: u32) {
+        check(
+            self.connection
+                .configure_window(
+                    window,
+                    &ConfigureWindowAux::new().stack_mode(StackMode::BELOW),
+                )
+                .unwrap(),
+        );
+    }
+
+    pub fn get_window_attributes(&self, window: u32) -> Option<GetWindowAttributesReply> {
         self.connection
-            .query_tree(window)
+            .get_window_attributes(window)
             .unwrap()
             .reply()
-            .unwrap()
-            .children
+            .ok()
     }
 
-    pub fn get_window_attributes(&self, window: u32) -> GetWindowAttributesReply {
+    pub fn get_window_children(&self, window: u32) -> Vec<u32> {
         self.connection
-            .get_window_attributes(window)
+            .query_tree(window)
             .unwrap()
             .reply()
             .unwrap()
+            .children
     }
 
     pub fn get_window_icon(&self, window: u32) -> Option<cairo::ImageSurface> {
-        let reply = self
-            .connection
+        parse_window_icon(self.request_window_icon(window).reply().unwrap())
+    }
+
+    fn request_window_icon(&self, window: u32) -> Cookie<'_, XCBConnection, GetPropertyReply> {
+        self.connection
             .get_property(
                 false,
                 window,
@@ -535,60 +1230,49 @@ impl Api {
                 u32::MAX,
             )
             .unwrap()
-            .reply()
-            .unwrap();
-
-        if reply.value.is_empty() {
-            return None;
-        }
-
-        let mut buffer = reply.value.as_slice();
-        let mut icons = Vec::new();
-
-        loop {
-            let width = u32::from_ne_bytes(buffer.get(..4)?.try_into().unwrap());
-            let height = u32::from_ne_bytes(buffer.get(4..8)?.try_into().unwrap());
-            let length = width as usize * height as usize * 4;
-            let data = buffer.get(8..(8 + length))?;
-
-            icons.push(Icon {
-                width,
-                height,
-                data,
-            });
-
-            buffer = match buffer.get(8 + length..) {
-                Some(buffer) => buffer,
-                None => break,
-            };
-
-            if buffer.is_empty() {
-                break;
-            }
-        }
-
-        let icon = find_most_appropriate_icon(&icons)?;
-        let image = icon.to_image()?;
-
-        if !(icon.width == ICON_SIZE as u32 && icon.height == ICON_SIZE as u32) {
-            let size = icon.width.max(icon.height);
-            let ratio = size as f64 / ICON_SIZE as f64;
-            image.set_device_scale(ratio, ratio);
-
-            let new_image =
-                cairo::ImageSurface::create(cairo::Format::ARgb32, ICON_SIZE as _, ICON_SIZE as _)
-                    .unwrap();
-
-            let context = cairo::Context::new(&new_image).unwrap();
-
-            context.set_source_surface(&image, 0.0, 0.0).unwrap();
-            context.source().set_filter(cairo::Filter::Nearest);
-            context.paint().unwrap();
+    }
 
-            return Some(new_image);
-        }
+    // Sends the requests for all the windows before waiting for any reply, so the number of
+    // round trips doesn't grow with the number of windows. Windows which are gone are left out
+    pub fn get_window_snapshots(&self, windows: &[u32]) -> HashMap<u32, WindowSnapshot> {
+        let cookies = windows
+            .iter()
+            .map(|window| {
+                (
+                    *window,
+                    self.connection.get_window_attributes(*window).unwrap(),
+                    self.connection.get_geometry(*window).unwrap(),
+                    WmClassCookie::new(&self.connection, *window).unwrap(),
+                    self.request_window_title(*window),
+                    self.request_window_icon(*window),
+                    WmSizeHints::get_normal_hints(&self.connection, *window).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
 
-        Some(image)
+        cookies
+            .into_iter()
+            .filter_map(
+                |(window, attributes, geometry, class, title, icon, size_hints)| {
+                    let snapshot = WindowSnapshot {
+                        attributes: attributes.reply().ok()?,
+                        geometry: geometry.reply().ok()?,
+                        class: class
+                            .reply()
+                            .ok()
+                            .map(|reply| String::from_utf8_lossy(reply.class()).into_owned()),
+                        title: title
+                            .reply()
+                            .ok()
+                            .and_then(|reply| self.parse_window_title(reply)),
+                        icon: icon.reply().ok().and_then(parse_window_icon),
+                        size_hints: size_hints.reply().map(SizeHints::from).unwrap_or_default(),
+                    };
+
+                    Some((window, snapshot))
+                },
+            )
+            .collect()
     }
 
     pub fn allow_configure_request(&self, event: &ConfigureRequestEvent) {
@@ -646,20 +1330,16 @@ struct Cairo {
     connection: cairo::XCBConnection,
     visual: cairo::XCBVisualType,
     _visual: Box<XCBVisualType>,
+
+    // The root window's own visual, for drawing its background
+    root_visual: cairo::XCBVisualType,
+    _root_visual: Box<XCBVisualType>,
 }
 
 impl Cairo {
-    fn new(connection: &XCBConnection, visual: &Visualtype) -> Self {
-        let mut xcb_visual_type = Box::new(XCBVisualType {
-            visual_id: visual.visual_id,
-            class: visual.class.into(),
-            bits_per_rgb_value: visual.bits_per_rgb_value,
-            colormap_entries: visual.colormap_entries,
-            red_mask: visual.red_mask,
-            green_mask: visual.green_mask,
-            blue_mask: visual.blue_mask,
-            pad0: [0; 4],
-        });
+    fn new(connection: &XCBConnection, visual: &Visualtype, root_visual: &Visualtype) -> Self {
+        let mut xcb_visual_type = Box::new(XCBVisualType::new(visual));
+        let mut xcb_root_visual_type = Box::new(XCBVisualType::new(root_visual));
 
         unsafe {
             // SAFETY: connection and cairo_connection will have the same lifetime
@@ -670,10 +1350,16 @@ impl Cairo {
             let cairo_visual =
                 cairo::XCBVisualType::from_raw_none(xcb_visual_type.as_mut() as *mut _ as _);
 
+            // SAFETY: same as above
+            let cairo_root_visual =
+                cairo::XCBVisualType::from_raw_none(xcb_root_visual_type.as_mut() as *mut _ as _);
+
             Self {
                 connection: cairo_connection,
                 visual: cairo_visual,
                 _visual: xcb_visual_type,
+                root_visual: cairo_root_visual,
+                _root_visual: xcb_root_visual_type,
             }
         }
     }
@@ -691,6 +1377,21 @@ struct XCBVisualType {
     pad0: [u8; 4],
 }
 
+impl XCBVisualType {
+    fn new(visual: &Visualtype) -> Self {
+        Self {
+            visual_id: visual.visual_id,
+            class: visual.class.into(),
+            bits_per_rgb_value: visual.bits_per_rgb_value,
+            colormap_entries: visual.colormap_entries,
+            red_mask: visual.red_mask,
+            green_mask: visual.green_mask,
+            blue_mask: visual.blue_mask,
+            pad0: [0; 4],
+        }
+    }
+}
+
 #[cfg(debug_assertions)]
 fn check(request: VoidCookie<'_, XCBConnection>) {
     request.check().unwrap();
@@ -699,6 +1400,60 @@ fn check(request: VoidCookie<'_, XCBConnection>) {
 #[cfg(not(debug_assertions))]
 fn check(_request: VoidCookie<'_, XCBConnection>) {}
 
+fn parse_window_icon(reply: GetPropertyReply) -> Option<cairo::ImageSurface> {
+    if reply.value.is_empty() {
+        return None;
+    }
+
+    let mut buffer = reply.value.as_slice();
+    let mut icons = Vec::new();
+
+    loop {
+        let width = u32::from_ne_bytes(buffer.get(..4)?.try_into().unwrap());
+        let height = u32::from_ne_bytes(buffer.get(4..8)?.try_into().unwrap());
+        let length = width as usize * height as usize * 4;
+        let data = buffer.get(8..(8 + length))?;
+
+        icons.push(Icon {
+            width,
+            height,
+            data,
+        });
+
+        buffer = match buffer.get(8 + length..) {
+            Some(buffer) => buffer,
+            None => break,
+        };
+
+        if buffer.is_empty() {
+            break;
+        }
+    }
+
+    let icon = find_most_appropriate_icon(&icons)?;
+    let image = icon.to_image()?;
+
+    if !(icon.width == ICON_SIZE as u32 && icon.height == ICON_SIZE as u32) {
+        let size = icon.width.max(icon.height);
+        let ratio = size as f64 / ICON_SIZE as f64;
+        image.set_device_scale(ratio, ratio);
+
+        let new_image =
+            cairo::ImageSurface::create(cairo::Format::ARgb32, ICON_SIZE as _, ICON_SIZE as _)
+                .unwrap();
+
+        let context = cairo::Context::new(&new_image).unwrap();
+
+        context.set_source_surface(&image, 0.0, 0.0).unwrap();
+        context.source().set_filter(cairo::Filter::Nearest);
+        context.paint().unwrap();
+
+        return Some(new_image);
+    }
+
+    Some(image)
+}
+
 struct Icon<'a> {
     width: u32,
     height: u32,
@@ -732,14 +1487,7 @@ impl<'a> Icon<'a> {
 
         let buffer = chunks
             .iter()
-            .flat_map(|[b, g, r, a]| {
-                [
-                    (((*b as u16) * (*a as u16)) / 255) as u8,
-                    (((*g as u16) * (*a as u16)) / 255) as u8,
-                    (((*r as u16) * (*a as u16)) / 255) as u8,
-                    *a,
-                ]
-            })
+            .flat_map(|pixel| premultiply(*pixel))
             .collect::<Vec<_>>();
 
         cairo::ImageSurface::create_for_data(
@@ -753,6 +1501,12 @@ impl<'a> Icon<'a> {
     }
 }
 
+// Cairo expects color channels to be already multiplied by alpha, while _NET_WM_ICON stores them as is
+fn premultiply([b, g, r, a]: [u8; 4]) -> [u8; 4] {
+    let multiply = |channel: u8| ((channel as u16 * a as u16) / 255) as u8;
+    [multiply(b), multiply(g), multiply(r), a]
+}
+
 fn find_most_appropriate_icon<'a, 'b>(icons: &'a [Icon<'b>]) -> Option<&'a Icon<'b>> {
     let mut result = icons.first()?;
 
@@ -772,3 +1526,65 @@ fn find_most_appropriate_icon<'a, 'b>(icons: &'a [Icon<'b>]) -> Option<&'a Icon<
 
     Some(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icon(width: u32, height: u32) -> Icon<'static> {
+        Icon {
+            width,
+            height,
+            data: &[],
+        }
+    }
+
+    #[test]
+    fn premultiply_by_alpha() {
+        // Red in BGRA order as in _NET_WM_ICON
+        assert_eq!(premultiply([0, 0, 255, 255]), [0, 0, 255, 255]);
+        assert_eq!(premultiply([0, 0, 255, 128]), [0, 0, 128, 128]);
+        assert_eq!(premultiply([0, 0, 255, 0]), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bigger_icon_is_better() {
+        assert!(icon(16, 16).is_better_than(&icon(8, 8)));
+        assert!(icon(32, 32).is_better_than(&icon(16, 16)));
+        assert!(!icon(8, 8).is_better_than(&icon(16, 16)));
+        assert!(!icon(16, 16).is_better_than(&icon(16, 16)));
+    }
+
+    #[test]
+    fn wider_icon_is_better_only_if_square() {
+        assert!(icon(24, 24).is_better_than(&icon(16, 24)));
+        assert!(!icon(32, 16).is_better_than(&icon(16, 16)));
+        assert!(!icon(48, 8).is_better_than(&icon(16, 16)));
+    }
+
+    #[test]
+    fn most_appropriate_icon() {
+        let size = |icon: Option<&Icon>| icon.map(|icon| (icon.width, icon.height));
+
+        assert_eq!(size(find_most_appropriate_icon(&[])), None);
+        assert_eq!(
+            size(find_most_appropriate_icon(&[icon(48, 48)])),
+            Some((48, 48))
+        );
+
+        // Exact size wins over anything bigger, wherever it is in the list
+        let icons = [icon(8, 8), icon(64, 64), icon(16, 16), icon(32, 32)];
+        assert_eq!(size(find_most_appropriate_icon(&icons)), Some((16, 16)));
+
+        let icons = [icon(16, 16), icon(64, 64)];
+        assert_eq!(size(find_most_appropriate_icon(&icons)), Some((16, 16)));
+
+        // Otherwise a bigger one, which is scaled down. One that isn't square has to be bigger
+        // in both dimensions
+        let icons = [icon(8, 8), icon(32, 32), icon(64, 32), icon(24, 24)];
+        assert_eq!(size(find_most_appropriate_icon(&icons)), Some((32, 32)));
+
+        let icons = [icon(8, 8), icon(64, 32), icon(32, 32)];
+        assert_eq!(size(find_most_appropriate_icon(&icons)), Some((64, 32)));
+    }
+}
@@ -1,33 +1,60 @@
 use crate::api::Api;
 use crate::bottom_panel::BottomPanel;
+use crate::client::ClientChange;
+use crate::dialog::Dialog;
+use crate::hot_corner::HotCorners;
+use crate::ipc::Ipc;
+use crate::keycode::Keymap;
+use crate::menu::Menu;
+use crate::overview::Overview;
 use crate::spawner::Spawner;
 use crate::top_panel::TopPanel;
 use crate::wm::Wm;
 use std::cell::OnceCell;
+use std::cell::RefCell;
 use std::rc::Rc;
 
 pub struct App {
     api: Api,
+    keymap: OnceCell<Keymap>,
     wm: OnceCell<Wm>,
     top_panel: OnceCell<TopPanel>,
     bottom_panel: OnceCell<BottomPanel>,
+    overview: OnceCell<Overview>,
+    hot_corners: OnceCell<HotCorners>,
+    dialog: OnceCell<Dialog>,
+    menu: OnceCell<Menu>,
     spawner: OnceCell<Spawner>,
+    ipc: OnceCell<Ipc>,
+    client_changes: RefCell<Vec<ClientChange>>,
 }
 
 impl App {
     pub fn new() -> Rc<Self> {
         let this = Rc::new(Self {
             api: Api::new(),
+            keymap: OnceCell::new(),
             wm: OnceCell::new(),
             top_panel: OnceCell::new(),
             bottom_panel: OnceCell::new(),
+            overview: OnceCell::new(),
+            hot_corners: OnceCell::new(),
+            dialog: OnceCell::new(),
+            menu: OnceCell::new(),
             spawner: OnceCell::new(),
+            ipc: OnceCell::new(),
+            client_changes: RefCell::new(Vec::new()),
         });
 
+        let _ = this.keymap.set(Keymap::new(this.clone()));
         let _ = this.wm.set(Wm::new(this.clone()));
         let _ = this.top_panel.set(TopPanel::new(this.clone()));
         let _ = this.bottom_panel.set(BottomPanel::new(this.clone()));
-        let _ = this.spawner.set(Spawner::new());
+        let _ = this.overview.set(Overview::new(this.clone()));
+        let _ = this.hot_corners.set(HotCorners::new(this.clone()));
+        let _ = this.dialog.set(Dialog::new(this.clone()));
+        let _ = this.menu.set(Menu::new(this.clone()));
+        let _ = this.spawner.set(Spawner::new(this.clone()));
 
         this
     }
@@ -36,6 +63,10 @@ impl App {
         &self.api
     }
 
+    pub fn keymap(&self) -> &Keymap {
+        self.keymap.get().unwrap()
+    }
+
     pub fn wm(&self) -> &Wm {
         self.wm.get().unwrap()
     }
@@ -48,7 +79,50 @@ impl App {
         self.bottom_panel.get().unwrap()
     }
 
+    pub fn overview(&self) -> &Overview {
+        self.overview.get().unwrap()
+    }
+
+    pub fn hot_corners(&self) -> &HotCorners {
+        self.hot_corners.get().unwrap()
+    }
+
+    pub fn dialog(&self) -> &Dialog {
+        self.dialog.get().unwrap()
+    }
+
+    pub fn menu(&self) -> &Menu {
+        self.menu.get().unwrap()
+    }
+
     pub fn spawner(&self) -> &Spawner {
         self.spawner.get().unwrap()
     }
+
+    // Only once we know there's no other window manager running, it'd lose its socket otherwise
+    pub fn start_ipc(self: &Rc<Self>) {
+        if let Some(ipc) = Ipc::new(self.clone()) {
+            let _ = self.ipc.set(ipc);
+        }
+    }
+
+    // None if the socket couldn't be set up
+    pub fn ipc(&self) -> Option<&Ipc> {
+        self.ipc.get()
+    }
+
+    pub fn queue_client_change(&self, change: ClientChange) {
+        self.client_changes.borrow_mut().push(change);
+    }
+
+    // Hands the changes queued since the last call to everyone who displays clients
+    pub fn dispatch_client_changes(&self) {
+        let changes = self.client_changes.take();
+
+        for change in &changes {
+            self.wm().handle_client_change(change);
+            self.top_panel().handle_client_change(change);
+            self.bottom_panel().handle_client_change(change);
+        }
+    }
 }
@@ -1,5 +1,11 @@
 use crate::api::ICON_SIZE;
 use crate::app::App;
+use crate::client::Client;
+use crate::client::ClientChange;
+use crate::client::ClientChangeKind;
+use crate::config;
+use crate::util::cycle_next;
+use crate::util::cycle_previous;
 use std::cell::Cell;
 use std::cell::RefCell;
 use std::ops::RangeInclusive;
@@ -8,21 +14,62 @@ use x11rb::protocol::xproto::ButtonIndex;
 use x11rb::protocol::xproto::ButtonPressEvent;
 use x11rb::protocol::xproto::CreateWindowAux;
 use x11rb::protocol::xproto::EventMask;
+use x11rb::protocol::xproto::NotifyMode;
 use x11rb::protocol::Event;
 
 pub const PANEL_HEIGHT: u16 = 30;
+
+pub const RESERVED_HEIGHT: u16 = if config::BOTTOM_PANEL_AUTO_HIDE {
+    0
+}
+else {
+    PANEL_HEIGHT
+};
+
 const ICON_MARGIN_LEFT: u16 = 7;
 const ICON_MARGIN_RIGHT: u16 = 10;
 
+// What an entry in the panel stands for
+enum PanelEntry {
+    Single(Rc<Client>),
+
+    // Windows of a class collapsed into a single entry, in tasklist order
+    Group(Vec<Rc<Client>>),
+}
+
+impl PanelEntry {
+    fn clients(&self) -> &[Rc<Client>] {
+        match self {
+            PanelEntry::Single(client) => std::slice::from_ref(client),
+            PanelEntry::Group(clients) => clients,
+        }
+    }
+}
+
 pub struct BottomPanel {
     app: Rc<App>,
     id: u32,
+
+    // Same as for TopPanel
+    trigger_id: Option<u32>,
+
     surface: cairo::XCBSurface,
+    context: cairo::Context,
     need_redraw: Cell<bool>,
 
     // Same as for TopPanel
     layout: RefCell<Vec<RangeInclusive<u16>>>,
     last_mouse_x: Cell<Option<u16>>,
+
+    // Index into entries() of the entry under the pointer, the panel is redrawn only when it changes
+    hovered_entry: Cell<Option<usize>>,
+
+    // Index into entries() of the leftmost entry shown, when they don't all fit.
+    // 'layout' only has the entries which are shown, so its indices are offset by it
+    scroll_offset: Cell<usize>,
+
+    // Starts as config::TASKLIST_GROUPING, can be toggled over IPC
+    grouping: Cell<bool>,
 }
 
 impl BottomPanel {
@@ -35,10 +82,31 @@ impl BottomPanel {
             (app.api().screen_height() - PANEL_HEIGHT) as _,
             app.api().screen_width(),
             PANEL_HEIGHT,
-            CreateWindowAux::new().event_mask(EventMask::BUTTON_PRESS | EventMask::POINTER_MOTION),
+            CreateWindowAux::new().event_mask(
+                EventMask::BUTTON_PRESS | EventMask::POINTER_MOTION | EventMask::LEAVE_WINDOW,
+            ),
         );
 
-        app.api().map_window(id);
+        let trigger_id = config::BOTTOM_PANEL_AUTO_HIDE.then(|| {
+            let trigger_id = app.api().generate_id();
+
+            app.api().create_input_only_window(
+                trigger_id,
+                0,
+                (app.api().screen_height() - 1) as _,
+                app.api().screen_width(),
+                1,
+                CreateWindowAux::new().event_mask(EventMask::ENTER_WINDOW),
+            );
+
+            app.api().map_window(trigger_id);
+
+            trigger_id
+        });
+
+        if trigger_id.is_none() {
+            app.api().map_window(id);
+        }
 
         let surface =
             app.api()
@@ -47,15 +115,78 @@ impl BottomPanel {
         Self {
             app,
             id,
+            trigger_id,
+            context: cairo::Context::new(&surface).unwrap(),
             surface,
             need_redraw: Cell::new(true),
             layout: RefCell::new(Vec::new()),
             last_mouse_x: Cell::new(None),
+            hovered_entry: Cell::new(None),
+            scroll_offset: Cell::new(0),
+            grouping: Cell::new(config::TASKLIST_GROUPING),
+        }
+    }
+
+    pub fn toggle_grouping(&self) {
+        self.grouping.set(!self.grouping.get());
+        self.hovered_entry.set(None);
+        self.need_redraw.set(true);
+    }
+
+    // The entries in the order they're shown. Only the panel groups them, keyboard cycling
+    // and Mod4+F1..F9 keep going through the tasklist itself
+    fn entries(&self) -> Vec<PanelEntry> {
+        let mut clients = self.app.wm().active_workspace().tasklist().clone();
+
+        if !self.grouping.get() {
+            return clients.into_iter().map(PanelEntry::Single).collect();
+        }
+
+        // Stable, so windows of a class stay in tasklist order
+        clients.sort_by(|a, b| a.class().as_deref().cmp(&b.class().as_deref()));
+
+        let mut entries = Vec::new();
+
+        for class_clients in clients.chunk_by(|a, b| *a.class() == *b.class()) {
+            // Windows without a class don't have anything in common
+            if class_clients.len() > config::TASKLIST_GROUP_THRESHOLD
+                && class_clients[0].class().is_some()
+            {
+                entries.push(PanelEntry::Group(class_clients.to_vec()));
+            }
+            else {
+                entries.extend(class_clients.iter().cloned().map(PanelEntry::Single));
+            }
+        }
+
+        entries
+    }
+
+    // Index into entries()
+    fn entry_at(&self, x: u16) -> Option<usize> {
+        self.layout
+            .borrow()
+            .iter()
+            .position(|range| range.contains(&x))
+            .map(|index| index + self.scroll_offset.get())
+    }
+
+    pub fn raise(&self) {
+        if let Some(trigger_id) = self.trigger_id {
+            self.app.api().raise_window(trigger_id);
         }
+
+        self.app.api().raise_window(self.id);
     }
 
-    pub fn id(&self) -> u32 {
-        self.id
+    fn reveal(&self) {
+        self.app.api().map_window(self.id);
+        self.app.api().raise_window(self.id);
+        self.need_redraw.set(true);
+    }
+
+    fn hide(&self) {
+        self.app.api().unmap_window(self.id);
     }
 
     pub fn request_redraw(&self) {
@@ -74,20 +205,20 @@ impl BottomPanel {
         let mut layout = self.layout.borrow_mut();
         layout.clear();
 
-        let context = cairo::Context::new(&self.surface).unwrap();
+        let context = &self.context;
+
+        // The context is reused, so we have to reset the state left from the previous redraw
+        context.reset_clip();
+        context.identity_matrix();
+        context.new_path();
 
         context.set_line_width(1.0);
         context.set_antialias(cairo::Antialias::None);
 
-        context.set_source_rgb(0.0, 0.0, 0.0);
+        context.set_operator(cairo::Operator::Source);
+        config::THEME.bottom_panel_background.set_source(context);
         context.paint().unwrap();
-
-        let workspace = self.app.wm().active_workspace();
-        let clients = workspace.tasklist();
-
-        if clients.is_empty() {
-            return;
-        }
+        context.set_operator(cairo::Operator::Over);
 
         context.set_font_size(16.0);
 
@@ -97,25 +228,84 @@ impl BottomPanel {
             cairo::FontWeight::Bold,
         );
 
-        let entry_width = self.app.api().screen_width() / clients.len() as u16;
+        let workspace = self.app.wm().active_workspace();
+        let entries = self.entries();
+
+        // So an empty workspace doesn't look like the panel has failed to draw
+        if entries.is_empty() {
+            let text = format!(
+                "workspace {} is empty",
+                self.app.wm().active_workspace_index() + 1
+            );
+
+            let extents = context.text_extents(&text).unwrap();
+
+            context.move_to(
+                ((self.app.api().screen_width() as f64 - extents.width()) / 2.0
+                    - extents.x_bearing())
+                .floor(),
+                ((PANEL_HEIGHT as f64 - extents.height()) / 2.0 - extents.y_bearing()).floor(),
+            );
+
+            config::THEME.inactive_text.set_source(context);
+            context.show_text(&text).unwrap();
+
+            self.surface.flush();
+            return;
+        }
+
+        let entry_width = self.app.api().screen_width() / entries.len() as u16;
 
-        let (entry_width, justified) = if entry_width > 300 {
-            (300, false)
+        let (entry_width, justified) = if entry_width > config::TASKLIST_MAX_ENTRY_WIDTH {
+            (config::TASKLIST_MAX_ENTRY_WIDTH, false)
+        }
+        else if entry_width < config::TASKLIST_MIN_ENTRY_WIDTH {
+            (config::TASKLIST_MIN_ENTRY_WIDTH, false)
         }
         else {
             (entry_width, true)
         };
 
+        let visible_entry_count = (self.app.api().screen_width() / entry_width) as usize;
+        let scroll_offset = self.clamp_scroll_offset(self.scroll_offset.get(), entries.len());
+        self.scroll_offset.set(scroll_offset);
+
+        let text_x = if config::TASKLIST_SHOW_ICONS {
+            ICON_MARGIN_LEFT + ICON_SIZE + ICON_MARGIN_RIGHT
+        }
+        else {
+            ICON_MARGIN_LEFT
+        };
+
         // TODO investigate what this means
-        let max_len = ((entry_width - ICON_MARGIN_LEFT - ICON_SIZE - ICON_MARGIN_RIGHT) / 9)
-            .saturating_sub(3);
+        let max_len = (entry_width.saturating_sub(text_x) / 9).saturating_sub(3);
 
         let active_client_id = workspace.stack().last().unwrap().id();
 
-        for (index, client) in clients.iter().enumerate() {
-            let offset = index as u16 * entry_width;
-            let is_active = client.id() == active_client_id;
-            let is_last = index == clients.len() - 1;
+        let truncate = |text: &str| {
+            let mut result = String::new();
+
+            for (index, char) in text.chars().enumerate() {
+                if index == max_len as usize {
+                    result.push_str("...");
+                    break;
+                }
+
+                result.push(char);
+            }
+
+            result
+        };
+
+        for (index, entry) in entries.iter().enumerate() {
+            if !(scroll_offset..scroll_offset + visible_entry_count).contains(&index) {
+                continue;
+            }
+
+            let clients = entry.clients();
+            let offset = (index - scroll_offset) as u16 * entry_width;
+            let is_active = clients.iter().any(|client| client.id() == active_client_id);
+            let is_last = index == entries.len() - 1;
 
             let width = if justified && is_last {
                 self.app.api().screen_width() - entry_width
@@ -127,56 +317,103 @@ impl BottomPanel {
             layout.push(offset..=(offset + width));
 
             if is_active {
-                context.set_source_rgb(0.14, 0.14, 0.14);
+                config::THEME.active_entry_background.set_source(context);
+                context.rectangle(offset as _, 0.0, width as _, PANEL_HEIGHT as _);
+                context.fill().unwrap();
+            }
+            else if self.hovered_entry.get() == Some(index) {
+                config::THEME.hovered_entry_background.set_source(context);
                 context.rectangle(offset as _, 0.0, width as _, PANEL_HEIGHT as _);
                 context.fill().unwrap();
             }
 
-            context
-                .set_source_surface(
-                    client
-                        .icon()
-                        .as_deref()
-                        .unwrap_or(&self.app.api().default_icon),
-                    (offset + ICON_MARGIN_LEFT) as _,
-                    (PANEL_HEIGHT - ICON_SIZE) as f64 / 2.0,
-                )
-                .unwrap();
-
-            context.source().set_filter(cairo::Filter::Nearest);
-            context.paint().unwrap();
-
-            let title = client
-                .title()
-                .as_deref()
-                .map(|title| {
-                    let mut result = String::new();
-
-                    for (index, char) in title.chars().enumerate() {
-                        if index == max_len as usize {
-                            result.push_str("...");
-                            break;
-                        }
-
-                        result.push(char);
-                    }
+            if config::TASKLIST_SHOW_ICONS {
+                context
+                    .set_source_surface(
+                        clients[0]
+                            .icon()
+                            .as_deref()
+                            .unwrap_or(&self.app.api().default_icon),
+                        (offset + ICON_MARGIN_LEFT) as _,
+                        (PANEL_HEIGHT - ICON_SIZE) as f64 / 2.0,
+                    )
+                    .unwrap();
+
+                context.source().set_filter(cairo::Filter::Nearest);
+                context.paint().unwrap();
+            }
+
+            // Numbers for Mod4+F1..F9 over the icon. They go by the tasklist, which
+            // the entries don't follow when grouped
+            let tasklist_index = match entry {
+                PanelEntry::Single(client) => workspace
+                    .tasklist()
+                    .iter()
+                    .position(|tasklist_client| tasklist_client.id() == client.id()),
+                PanelEntry::Group(_) => None,
+            };
+
+            if let Some(tasklist_index) = tasklist_index
+                .filter(|tasklist_index| self.app.wm().mod4_held() && *tasklist_index < 9)
+            {
+                config::THEME.urgent_text.set_source(context);
+                context.set_font_size(12.0);
+                context.move_to((offset + 2) as _, 12.0);
+                context
+                    .show_text(&(tasklist_index + 1).to_string())
+                    .unwrap();
+                context.set_font_size(16.0);
+            }
 
-                    result
-                })
-                .unwrap_or_else(|| format!("[{}]", client.id()));
+            // Count of the windows in a group, at the bottom right corner of the icon
+            if let PanelEntry::Group(clients) = entry {
+                if config::TASKLIST_SHOW_ICONS {
+                    config::THEME.urgent_text.set_source(context);
+                    context.set_font_size(12.0);
+                    context.move_to(
+                        (offset + ICON_MARGIN_LEFT + ICON_SIZE) as _,
+                        (PANEL_HEIGHT - 2) as _,
+                    );
+                    context.show_text(&clients.len().to_string()).unwrap();
+                    context.set_font_size(16.0);
+                }
+            }
+
+            if !config::TASKLIST_SHOW_TITLES {
+                continue;
+            }
+
+            let title = match entry {
+                PanelEntry::Single(client) => client
+                    .title()
+                    .as_deref()
+                    .map(truncate)
+                    .unwrap_or_else(|| format!("[{}]", client.id())),
+                PanelEntry::Group(clients) if config::TASKLIST_SHOW_ICONS => {
+                    truncate(clients[0].class().as_deref().unwrap_or_default())
+                }
+                PanelEntry::Group(clients) => truncate(&format!(
+                    "{} ({})",
+                    clients[0].class().as_deref().unwrap_or_default(),
+                    clients.len()
+                )),
+            };
 
             let extents = context.text_extents(&title).unwrap();
 
             context.move_to(
-                (offset + ICON_MARGIN_LEFT + ICON_SIZE + ICON_MARGIN_RIGHT) as _,
+                (offset + text_x) as _,
                 (PANEL_HEIGHT as f64 / 2.0 - extents.y_bearing() / 2.0).floor(),
             );
 
             if is_active {
-                context.set_source_rgb(0.58, 0.61, 0.64);
+                config::THEME.active_text.set_source(context);
+            }
+            else if clients.iter().any(|client| client.urgent()) {
+                config::THEME.urgent_text.set_source(context);
             }
             else {
-                context.set_source_rgb(0.27, 0.27, 0.27);
+                config::THEME.inactive_text.set_source(context);
             }
 
             context.show_text(&title).unwrap();
@@ -185,6 +422,42 @@ impl BottomPanel {
         self.surface.flush();
     }
 
+    // So the last entry stays at the right edge when scrolled as far as it goes
+    fn clamp_scroll_offset(&self, scroll_offset: usize, entry_count: usize) -> usize {
+        let entry_width = (self.app.api().screen_width() / entry_count.max(1) as u16)
+            .max(config::TASKLIST_MIN_ENTRY_WIDTH);
+
+        let visible_entry_count = (self.app.api().screen_width() / entry_width) as usize;
+
+        scroll_offset.min(entry_count.saturating_sub(visible_entry_count))
+    }
+
+    // Over a group it cycles through the group's windows, elsewhere it scrolls the entries
+    fn handle_scroll(&self, event: &ButtonPressEvent, up: bool) {
+        let entries = self.entries();
+
+        if let Some(PanelEntry::Group(clients)) = self
+            .entry_at(event.event_x as _)
+            .and_then(|entry_index| entries.get(entry_index))
+        {
+            self.raise_client(self.cycle_group(clients, !up));
+            return;
+        }
+
+        let scroll_offset = if up {
+            self.scroll_offset.get().saturating_sub(1)
+        }
+        else {
+            self.scroll_offset.get() + 1
+        };
+
+        let scroll_offset = self.clamp_scroll_offset(scroll_offset, entries.len());
+
+        if self.scroll_offset.replace(scroll_offset) != scroll_offset {
+            self.need_redraw.set(true);
+        }
+    }
+
     fn set_cursor(&self, mouse_x: u16) {
         let mouse_on_clickable_text = self
             .layout
@@ -202,25 +475,67 @@ impl BottomPanel {
         self.app.api().set_window_cursor(self.id, cursor);
     }
 
+    // The right button opens the menu for the entry instead of raising it.
+    // Repeated clicks on a group raise its windows one after another
     fn handle_button_press(&self, event: &ButtonPressEvent) {
-        let tasklist_index = self
-            .layout
-            .borrow()
+        let Some(entry_index) = self.entry_at(event.event_x as _)
+        else {
+            return;
+        };
+
+        // The layout might be from before the tasklist has changed
+        let entries = self.entries();
+
+        let Some(entry) = entries.get(entry_index)
+        else {
+            return;
+        };
+
+        let is_menu = ButtonIndex::from(event.detail) == ButtonIndex::M3;
+
+        let client_id = match entry {
+            PanelEntry::Single(client) => client.id(),
+            // The menu is for the group's window which is shown, or would be by a click
+            PanelEntry::Group(clients) if is_menu => self
+                .active_group_client(clients)
+                .map_or(clients[0].id(), |index| clients[index].id()),
+            PanelEntry::Group(clients) => self.cycle_group(clients, true),
+        };
+
+        if is_menu {
+            self.app
+                .menu()
+                .show(client_id, event.root_x, event.root_y, event.time);
+        }
+        else {
+            self.raise_client(client_id);
+        }
+    }
+
+    // Index of the active window in the group, if it's there
+    fn active_group_client(&self, clients: &[Rc<Client>]) -> Option<usize> {
+        let active_client_id = self.app.wm().active_workspace().stack().last()?.id();
+
+        clients
             .iter()
-            .position(|range| range.contains(&(event.root_x as _)));
+            .position(|client| client.id() == active_client_id)
+    }
 
-        if let Some(tasklist_index) = tasklist_index {
-            let client_id = self.app.wm().active_workspace().tasklist()[tasklist_index].id();
+    // The window after or before the active one in the group. The first or last one
+    // if none of them is active
+    fn cycle_group(&self, clients: &[Rc<Client>], forward: bool) -> u32 {
+        let index = match (self.active_group_client(clients), forward) {
+            (Some(index), true) => cycle_next(clients, index),
+            (Some(index), false) => cycle_previous(clients, index),
+            (None, true) => 0,
+            (None, false) => clients.len() - 1,
+        };
 
-            let stack_index = self
-                .app
-                .wm()
-                .active_workspace()
-                .stack()
-                .iter()
-                .position(|client| client.id() == client_id)
-                .unwrap();
+        clients[index].id()
+    }
 
+    fn raise_client(&self, client_id: u32) {
+        if let Some(stack_index) = self.app.wm().active_workspace().stack_index_of(client_id) {
             self.app.wm().raise_client(stack_index);
         }
     }
@@ -231,20 +546,69 @@ impl BottomPanel {
                 if event.event == self.id {
                     self.set_cursor(event.event_x as _);
                     self.last_mouse_x.set(Some(event.event_x as _));
+
+                    self.set_hovered_entry(self.entry_at(event.event_x as _));
                 }
                 else {
                     self.last_mouse_x.set(None);
                 }
             }
             Event::ButtonPress(event) => {
-                if event.event == self.id && ButtonIndex::from(event.detail) == ButtonIndex::M1 {
-                    self.handle_button_press(event);
+                if event.event == self.id {
+                    match ButtonIndex::from(event.detail) {
+                        ButtonIndex::M1 | ButtonIndex::M3 => self.handle_button_press(event),
+                        ButtonIndex::M4 => self.handle_scroll(event, true),
+                        ButtonIndex::M5 => self.handle_scroll(event, false),
+                        _ => {}
+                    }
+
+                    // Same as for TopPanel
+                    self.app.wm().refocus();
+                }
+            }
+            Event::EnterNotify(event) => {
+                if Some(event.event) == self.trigger_id {
+                    self.reveal();
+                }
+            }
+            Event::LeaveNotify(event)
+                if event.event == self.id && event.mode == NotifyMode::NORMAL =>
+            {
+                self.set_hovered_entry(None);
+
+                if self.trigger_id.is_some() {
+                    self.hide();
                 }
             }
             _ => {}
         }
     }
 
+    fn set_hovered_entry(&self, hovered_entry: Option<usize>) {
+        if self.hovered_entry.replace(hovered_entry) != hovered_entry {
+            self.need_redraw.set(true);
+        }
+    }
+
+    // Only changes of clients in the tasklist matter, which is the active workspace's
+    pub fn handle_client_change(&self, change: &ClientChange) {
+        let shown = matches!(
+            change.kind,
+            ClientChangeKind::Title | ClientChangeKind::Icon | ClientChangeKind::State
+        );
+
+        if shown
+            && self
+                .app
+                .wm()
+                .active_workspace()
+                .stack_index_of(change.id)
+                .is_some()
+        {
+            self.notify();
+        }
+    }
+
     pub fn notify(&self) {
         self.need_redraw.set(true);
     }
@@ -1,11 +1,17 @@
 use crate::api::ICON_SIZE;
 use crate::app::App;
 use crate::bottom_panel;
+use crate::config;
+use crate::hints::apply_size_bounds;
+use crate::hints::SizeHints;
+use crate::keycode::get_lock_modifiers;
+use crate::theme::DecorationStyle;
 use crate::top_panel;
 use std::borrow::Cow;
 use std::cell::Cell;
 use std::cell::Ref;
 use std::cell::RefCell;
+use std::ops::RangeInclusive;
 use std::rc::Rc;
 use x11rb::protocol::xproto::ButtonIndex;
 use x11rb::protocol::xproto::CreateWindowAux;
@@ -13,11 +19,40 @@ use x11rb::protocol::xproto::EventMask;
 use x11rb::protocol::xproto::GrabMode;
 use x11rb::protocol::xproto::ModMask;
 
-pub const BORDER_WIDTH: u16 = 5;
+pub const BORDER_WIDTH: u16 = match config::THEME.decoration_style {
+    DecorationStyle::None => 0,
+    _ => 5,
+};
+
 pub const TITLEBAR_HEIGHT: u16 = 25;
 const ICON_MARGIN_LEFT: u16 = 7;
 const ICON_MARGIN_RIGHT: u16 = 9;
 
+// Padlock shown at the right end of the titlebar while the aspect ratio is locked
+const LOCK_WIDTH: f64 = 8.0;
+const LOCK_MARGIN_RIGHT: f64 = 8.0;
+
+// Queued on App by the mutators below and handed to Wm and the panels once per loop iteration,
+// so the client doesn't have to know who displays what
+#[derive(Clone, Copy)]
+pub struct ClientChange {
+    pub id: u32,
+    pub kind: ClientChangeKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ClientChangeKind {
+    Title,
+    Icon,
+    Class,
+
+    // Position or size
+    Geometry,
+
+    // Maximized, shaded, minimized, always on top or urgent
+    State,
+}
+
 pub struct Client {
     app: Rc<App>,
     id: u32,
@@ -28,9 +63,43 @@ pub struct Client {
     width: Cell<u16>,
     height: Cell<u16>,
     maximized: Cell<bool>,
+
+    // Rolled up so only the titlebar is visible, the height is kept for when it's rolled down
+    shaded: Cell<bool>,
+
+    urgent: Cell<bool>,
+
+    // Minimized clients have their container unmapped and are kept at the bottom of the stack
+    minimized: Cell<bool>,
+
+    // Always on top, the container is raised again whenever another one gets raised
+    above: Cell<bool>,
+
+    // Being moved or resized with the keyboard, the frame is drawn in a different color meanwhile
+    resizing: Cell<bool>,
+
+    // Toggled with Mod4+Shift+A, resizing keeps the width / height ratio from that moment
+    aspect_locked: Cell<bool>,
+    locked_ratio: Cell<f64>,
+
+    // The frame is drawn in a different color for a moment after the client gets raised
+    flashing: Cell<bool>,
+
+    // Toggled with Mod4+Shift+F, left out of the monocle layout at its own geometry
+    floating: Cell<bool>,
+
+    // How the client wants to get the focus, from the input field of WM_HINTS and WM_TAKE_FOCUS
+    // in WM_PROTOCOLS. Clients with neither never get it
+    accepts_input: Cell<bool>,
+    takes_focus: Cell<bool>,
+
+    // Server time of when the user last raised or clicked the client, 0 if never
+    last_activity: Cell<u32>,
+
     class: RefCell<Option<String>>,
     title: RefCell<Option<String>>,
     icon: RefCell<Option<cairo::ImageSurface>>,
+    size_hints: Cell<SizeHints>,
 
     surface: cairo::XCBSurface,
     need_redraw: Cell<bool>,
@@ -48,7 +117,8 @@ impl Client {
         class: Option<String>,
         title: Option<String>,
         icon: Option<cairo::ImageSurface>,
-    ) -> Self {
+        size_hints: SizeHints,
+    ) -> Option<Self> {
         let container_id = app.api().generate_id();
         let surface = app.api().create_cairo_xcb_surface(container_id, 1, 1);
 
@@ -61,19 +131,38 @@ impl Client {
             width: Cell::new(width),
             height: Cell::new(height),
             maximized: Cell::new(maximized),
+            shaded: Cell::new(false),
+            urgent: Cell::new(false),
+            minimized: Cell::new(false),
+            above: Cell::new(false),
+            resizing: Cell::new(false),
+            aspect_locked: Cell::new(false),
+            floating: Cell::new(false),
+            flashing: Cell::new(false),
+            locked_ratio: Cell::new(1.0),
+            accepts_input: Cell::new(true),
+            takes_focus: Cell::new(false),
+            last_activity: Cell::new(0),
             class: RefCell::new(class),
             title: RefCell::new(title),
             icon: RefCell::new(icon),
+            size_hints: Cell::new(size_hints),
             surface,
             need_redraw: Cell::new(true),
         };
 
-        this.init();
+        // Dropping it destroys the container
+        if !this.init() {
+            return None;
+        }
 
-        this
+        this.update_focus_model();
+
+        Some(this)
     }
 
-    fn init(&self) {
+    // Returns false if the window has been destroyed before we could reparent it
+    fn init(&self) -> bool {
         self.app.api().create_window(
             self.container_id,
             self.container_x(),
@@ -85,22 +174,33 @@ impl Client {
                     | EventMask::SUBSTRUCTURE_NOTIFY
                     | EventMask::BUTTON_PRESS
                     | EventMask::BUTTON_MOTION
-                    | EventMask::BUTTON_RELEASE,
+                    | EventMask::BUTTON_RELEASE
+                    | EventMask::POINTER_MOTION
+                    | EventMask::LEAVE_WINDOW
+                    | EventMask::FOCUS_CHANGE,
             ),
         );
 
+        // Maximized clients can still be dragged out of maximization, but not resized
+        self.grab_button_on_container(ButtonIndex::M1, self.app.api().cursors.fleur);
+
         if !self.maximized() {
-            self.grab_buttons_on_container();
+            self.grab_button_on_container(
+                ButtonIndex::M3,
+                self.app.api().cursors.bottom_right_corner,
+            );
         }
 
-        self.app.api().add_to_save_set(self.id);
-
-        self.app.api().reparent_window(
+        if !self.app.api().try_reparent_window(
             self.id,
             self.container_id,
             self.inner_offset_x(),
             self.inner_offset_y(),
-        );
+        ) {
+            return false;
+        }
+
+        self.app.api().add_to_save_set(self.id);
 
         self.app.api().grab_button(
             self.id,
@@ -136,9 +236,11 @@ impl Client {
         self.surface
             .set_size(self.container_width() as _, self.container_height() as _)
             .unwrap();
+
+        true
     }
 
-    fn container_x(&self) -> i16 {
+    pub fn container_x(&self) -> i16 {
         if self.maximized() {
             0
         }
@@ -147,16 +249,16 @@ impl Client {
         }
     }
 
-    fn container_y(&self) -> i16 {
+    pub fn container_y(&self) -> i16 {
         if self.maximized() {
-            top_panel::PANEL_HEIGHT as _
+            top_panel::RESERVED_HEIGHT as _
         }
         else {
             self.y() - BORDER_WIDTH as i16 - TITLEBAR_HEIGHT as i16
         }
     }
 
-    fn container_width(&self) -> u16 {
+    pub fn container_width(&self) -> u16 {
         if self.maximized() {
             self.app.api().screen_width()
         }
@@ -165,9 +267,14 @@ impl Client {
         }
     }
 
-    fn container_height(&self) -> u16 {
+    pub fn container_height(&self) -> u16 {
         if self.maximized() {
-            self.app.api().screen_height() - top_panel::PANEL_HEIGHT - bottom_panel::PANEL_HEIGHT
+            self.app.api().screen_height()
+                - top_panel::RESERVED_HEIGHT
+                - bottom_panel::RESERVED_HEIGHT
+        }
+        else if self.shaded() {
+            BORDER_WIDTH * 2 + TITLEBAR_HEIGHT
         }
         else {
             self.height() + BORDER_WIDTH * 2 + TITLEBAR_HEIGHT
@@ -187,45 +294,38 @@ impl Client {
         if self.maximized() {
             0
         }
+        // The window is moved below the container's bottom edge, so it gets clipped entirely
+        else if self.shaded() {
+            (BORDER_WIDTH * 2 + TITLEBAR_HEIGHT) as _
+        }
         else {
             (BORDER_WIDTH + TITLEBAR_HEIGHT) as _
         }
     }
 
-    fn grab_buttons_on_container(&self) {
-        self.app.api().grab_button(
-            self.container_id,
-            EventMask::BUTTON_PRESS | EventMask::BUTTON_MOTION | EventMask::BUTTON_RELEASE,
-            ButtonIndex::M1,
-            ModMask::M4,
-            self.app.api().cursors.fleur,
-            false,
-            GrabMode::ASYNC,
-            GrabMode::ASYNC,
-            x11rb::NONE,
-        );
-
-        self.app.api().grab_button(
-            self.container_id,
-            EventMask::BUTTON_PRESS | EventMask::BUTTON_MOTION | EventMask::BUTTON_RELEASE,
-            ButtonIndex::M3,
-            ModMask::M4,
-            self.app.api().cursors.bottom_right_corner,
-            false,
-            GrabMode::ASYNC,
-            GrabMode::ASYNC,
-            x11rb::NONE,
-        );
+    // Lock modifiers would make grabs with exact modifiers not match, so every state of them is grabbed
+    fn grab_button_on_container(&self, button: ButtonIndex, cursor: u32) {
+        for locks in get_lock_modifiers() {
+            self.app.api().grab_button(
+                self.container_id,
+                EventMask::BUTTON_PRESS | EventMask::BUTTON_MOTION | EventMask::BUTTON_RELEASE,
+                button,
+                ModMask::M4 | locks,
+                cursor,
+                false,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+            );
+        }
     }
 
-    fn ungrab_buttons_on_container(&self) {
-        self.app
-            .api()
-            .ungrab_button(self.container_id, ButtonIndex::M1, ModMask::M4);
-
-        self.app
-            .api()
-            .ungrab_button(self.container_id, ButtonIndex::M3, ModMask::M4);
+    fn ungrab_button_on_container(&self, button: ButtonIndex) {
+        for locks in get_lock_modifiers() {
+            self.app
+                .api()
+                .ungrab_button(self.container_id, button, ModMask::M4 | locks);
+        }
     }
 
     pub fn request_redraw(&self, is_active: bool) {
@@ -240,57 +340,110 @@ impl Client {
         context.set_line_width(1.0);
         context.set_antialias(cairo::Antialias::None);
 
-        self.draw_frame(&context);
+        self.draw_frame(&context, is_active);
         self.draw_titlebar(&context, is_active);
 
         self.surface.flush();
     }
 
-    fn draw_frame(&self, context: &cairo::Context) {
-        context.set_source_rgb(0.75, 0.75, 0.75);
+    fn draw_frame(&self, context: &cairo::Context, is_active: bool) {
+        // There's no border, the titlebar covers the whole container
+        if let DecorationStyle::None = config::THEME.decoration_style {
+            return;
+        }
+
+        self.draw_frame_body(context);
+
+        if let Some(accent) = config::THEME.active_frame_accent.filter(|_| is_active) {
+            accent.set_source(context);
+            context.rectangle(
+                0.5,
+                0.5,
+                self.container_width() as f64 - 1.0,
+                self.container_height() as f64 - 1.0,
+            );
+            context.stroke().unwrap();
+        }
+    }
+
+    fn draw_frame_body(&self, context: &cairo::Context) {
+        if self.resizing() {
+            config::THEME.resize_mode_frame.set_source(context);
+        }
+        else if self.flashing() {
+            config::THEME.raise_flash_frame.set_source(context);
+        }
+        else {
+            config::THEME.frame.set_source(context);
+        }
+
         context.paint().unwrap();
 
+        let DecorationStyle::Flat { color } = config::THEME.decoration_style
+        else {
+            self.draw_bevel(context);
+            return;
+        };
+
+        color.set_source(context);
+        context.rectangle(
+            0.5,
+            0.5,
+            self.container_width() as f64 - 1.0,
+            self.container_height() as f64 - 1.0,
+        );
+        context.stroke().unwrap();
+    }
+
+    fn draw_bevel(&self, context: &cairo::Context) {
         let left = 1.0;
         let right = self.container_width() as f64;
         let top = 1.0;
         let bottom = self.container_height() as f64;
 
-        context.set_source_rgb(1.0, 1.0, 1.0);
+        config::THEME.frame_inner_light.set_source(context);
         context.move_to(left + 1.0, bottom - 2.0);
         context.line_to(left + 1.0, top + 1.0);
         context.line_to(right - 2.0, top + 1.0);
         context.stroke().unwrap();
 
-        context.set_source_rgb(0.5, 0.5, 0.5);
+        config::THEME.frame_inner_shadow.set_source(context);
         context.move_to(left, bottom - 1.0);
         context.line_to(right - 1.0, bottom - 1.0);
         context.line_to(right - 1.0, top);
         context.stroke().unwrap();
 
-        context.set_source_rgb(0.87, 0.87, 0.87);
+        config::THEME.frame_outer_light.set_source(context);
         context.move_to(left, bottom - 1.0);
         context.line_to(left, top);
         context.line_to(right - 1.0, top);
         context.stroke().unwrap();
 
-        context.set_source_rgb(0.0, 0.0, 0.0);
+        config::THEME.frame_outer_shadow.set_source(context);
         context.move_to(left - 1.0, bottom);
         context.line_to(right, bottom);
         context.line_to(right, top - 1.0);
         context.stroke().unwrap();
     }
 
+    // Horizontal extent of the titlebar icon, relative to the container
+    pub fn icon_range(&self) -> RangeInclusive<u16> {
+        let left = BORDER_WIDTH + ICON_MARGIN_LEFT;
+        left..=(left + ICON_SIZE)
+    }
+
     fn draw_titlebar(&self, context: &cairo::Context, is_active: bool) {
         let gradient = cairo::LinearGradient::new(0.0, 0.0, self.width() as _, 0.0);
 
-        if is_active {
-            gradient.add_color_stop_rgb(0.0, 0.0, 0.5, 0.5);
-            gradient.add_color_stop_rgb(1.0, 0.0, 0.67, 0.67);
+        let (left_color, right_color) = if is_active {
+            config::THEME.active_titlebar
         }
         else {
-            gradient.add_color_stop_rgb(0.0, 0.63, 0.55, 0.4);
-            gradient.add_color_stop_rgb(1.0, 0.83, 0.8, 0.73);
-        }
+            config::THEME.inactive_titlebar
+        };
+
+        left_color.add_stop(&gradient, 0.0);
+        right_color.add_stop(&gradient, 1.0);
 
         context.set_source(gradient).unwrap();
 
@@ -324,7 +477,7 @@ impl Client {
             .map(Cow::from)
             .unwrap_or_else(|| format!("[{}]", self.id).into());
 
-        context.set_source_rgb(1.0, 1.0, 1.0);
+        config::THEME.titlebar_text.set_source(context);
 
         context.select_font_face(
             "PxPlus ToshibaTxL2 8x16",
@@ -342,6 +495,31 @@ impl Client {
         );
 
         context.show_text(&title).unwrap();
+
+        if self.aspect_locked() {
+            self.draw_lock(context);
+        }
+    }
+
+    fn draw_lock(&self, context: &cairo::Context) {
+        let right = (BORDER_WIDTH + self.width()) as f64 - LOCK_MARGIN_RIGHT;
+        let left = right - LOCK_WIDTH;
+        let center_y = BORDER_WIDTH as f64 + TITLEBAR_HEIGHT as f64 / 2.0;
+
+        config::THEME.titlebar_text.set_source(context);
+
+        // Shackle, then the body over its ends
+        context.arc(
+            left + LOCK_WIDTH / 2.0,
+            center_y - 2.0,
+            LOCK_WIDTH / 2.0 - 1.5,
+            std::f64::consts::PI,
+            0.0,
+        );
+        context.stroke().unwrap();
+
+        context.rectangle(left, center_y - 1.0, LOCK_WIDTH, 6.0);
+        context.fill().unwrap();
     }
 
     pub fn id(&self) -> u32 {
@@ -358,6 +536,7 @@ impl Client {
 
     pub fn set_x(&self, x: i16) {
         self.x.set(x);
+        self.changed(ClientChangeKind::Geometry);
 
         if !self.maximized() {
             self.app
@@ -372,6 +551,7 @@ impl Client {
 
     pub fn set_y(&self, y: i16) {
         self.y.set(y);
+        self.changed(ClientChangeKind::Geometry);
 
         if !self.maximized() {
             self.app
@@ -388,9 +568,13 @@ impl Client {
         self.height.get()
     }
 
+    // Sizes out of the bounds from the config get clamped, even if the client asks for them
     pub fn set_size(&self, width: u16, height: u16) {
+        let (width, height) = apply_size_bounds(width, height);
+
         self.width.set(width);
         self.height.set(height);
+        self.changed(ClientChangeKind::Geometry);
 
         if !self.maximized() {
             self.app.api().set_window_width(self.id, self.width());
@@ -421,7 +605,12 @@ impl Client {
             return;
         }
 
+        if maximized {
+            self.set_shaded(false);
+        }
+
         self.maximized.set(maximized);
+        self.changed(ClientChangeKind::State);
 
         self.app.api().set_window_x(self.id, self.inner_offset_x());
         self.app.api().set_window_y(self.id, self.inner_offset_y());
@@ -460,52 +649,217 @@ impl Client {
         self.app.api().set_window_height(self.id, height);
 
         if maximized {
-            self.ungrab_buttons_on_container();
+            self.ungrab_button_on_container(ButtonIndex::M3);
         }
         else {
             self.need_redraw.set(true);
-            self.grab_buttons_on_container()
+            self.grab_button_on_container(
+                ButtonIndex::M3,
+                self.app.api().cursors.bottom_right_corner,
+            );
+        }
+    }
+
+    pub fn last_activity(&self) -> u32 {
+        self.last_activity.get()
+    }
+
+    pub fn set_last_activity(&self, time: u32) {
+        self.last_activity.set(time);
+    }
+
+    pub fn shaded(&self) -> bool {
+        self.shaded.get()
+    }
+
+    pub fn set_shaded(&self, shaded: bool) {
+        if shaded == self.shaded() || self.maximized() {
+            return;
+        }
+
+        self.shaded.set(shaded);
+        self.changed(ClientChangeKind::State);
+
+        self.app.api().set_window_y(self.id, self.inner_offset_y());
+
+        self.app
+            .api()
+            .set_window_height(self.container_id, self.container_height());
+
+        self.surface
+            .set_size(self.container_width() as _, self.container_height() as _)
+            .unwrap();
+
+        self.need_redraw.set(true);
+    }
+
+    pub fn set_geometry(&self, x: i16, y: i16, width: u16, height: u16, maximized: bool) {
+        // Geometry of a maximized client is only applied after it gets unmaximized
+        if !maximized {
+            self.set_maximized(false);
+        }
+
+        self.set_x(x);
+        self.set_y(y);
+        self.set_size(width, height);
+        self.set_maximized(maximized);
+    }
+
+    pub fn urgent(&self) -> bool {
+        self.urgent.get()
+    }
+
+    pub fn set_urgent(&self, urgent: bool) {
+        self.urgent.set(urgent);
+        self.changed(ClientChangeKind::State);
+    }
+
+    pub fn minimized(&self) -> bool {
+        self.minimized.get()
+    }
+
+    pub fn set_minimized(&self, minimized: bool) {
+        self.minimized.set(minimized);
+        self.changed(ClientChangeKind::State);
+    }
+
+    pub fn above(&self) -> bool {
+        self.above.get()
+    }
+
+    pub fn set_above(&self, above: bool) {
+        self.above.set(above);
+        self.changed(ClientChangeKind::State);
+    }
+
+    pub fn resizing(&self) -> bool {
+        self.resizing.get()
+    }
+
+    pub fn set_resizing(&self, resizing: bool) {
+        self.resizing.set(resizing);
+        self.need_redraw.set(true);
+    }
+
+    pub fn flashing(&self) -> bool {
+        self.flashing.get()
+    }
+
+    pub fn set_flashing(&self, flashing: bool) {
+        if self.flashing.replace(flashing) != flashing {
+            self.need_redraw.set(true);
+        }
+    }
+
+    pub fn aspect_locked(&self) -> bool {
+        self.aspect_locked.get()
+    }
+
+    // Locks the ratio of the current size
+    pub fn set_aspect_locked(&self, aspect_locked: bool) {
+        self.aspect_locked.set(aspect_locked);
+        self.locked_ratio
+            .set(self.width() as f64 / self.height().max(1) as f64);
+        self.need_redraw.set(true);
+    }
+
+    pub fn floating(&self) -> bool {
+        self.floating.get()
+    }
+
+    pub fn set_floating(&self, floating: bool) {
+        self.floating.set(floating);
+    }
+
+    // Size hints for resizing by the user, where the locked aspect ratio replaces the client's own
+    pub fn resize_hints(&self) -> SizeHints {
+        let mut hints = self.size_hints();
+
+        if self.aspect_locked() {
+            hints.aspect = Some((self.locked_ratio.get(), self.locked_ratio.get()));
         }
+
+        hints
+    }
+
+    pub fn accepts_input(&self) -> bool {
+        self.accepts_input.get()
+    }
+
+    pub fn takes_focus(&self) -> bool {
+        self.takes_focus.get()
+    }
+
+    // Reads WM_HINTS and WM_PROTOCOLS again, they can change while the window is mapped
+    pub fn update_focus_model(&self) {
+        let api = self.app.api();
+
+        self.accepts_input.set(api.get_window_input_hint(self.id));
+
+        self.takes_focus.set(
+            api.get_window_protocols(self.id)
+                .contains(&api.atoms.WM_TAKE_FOCUS),
+        );
     }
 
-    pub fn class(&self) -> Ref<Option<String>> {
+    pub fn class(&self) -> Ref<'_, Option<String>> {
         self.class.borrow()
     }
 
     pub fn set_class(&self, class: Option<String>) {
         *self.class.borrow_mut() = class;
+        self.changed(ClientChangeKind::Class);
     }
 
-    pub fn title(&self) -> Ref<Option<String>> {
+    pub fn title(&self) -> Ref<'_, Option<String>> {
         self.title.borrow()
     }
 
     pub fn set_title(&self, title: Option<String>) {
         *self.title.borrow_mut() = title;
         self.need_redraw.set(true);
+        self.changed(ClientChangeKind::Title);
     }
 
-    pub fn icon(&self) -> Ref<Option<cairo::ImageSurface>> {
+    pub fn icon(&self) -> Ref<'_, Option<cairo::ImageSurface>> {
         self.icon.borrow()
     }
 
     pub fn set_icon(&self, icon: Option<cairo::ImageSurface>) {
         *self.icon.borrow_mut() = icon;
         self.need_redraw.set(true);
+        self.changed(ClientChangeKind::Icon);
+    }
+
+    pub fn size_hints(&self) -> SizeHints {
+        self.size_hints.get()
+    }
+
+    pub fn set_size_hints(&self, size_hints: SizeHints) {
+        self.size_hints.set(size_hints);
     }
 
     pub fn notify(&self) {
         self.need_redraw.set(true);
     }
+
+    fn changed(&self, kind: ClientChangeKind) {
+        self.app
+            .queue_client_change(ClientChange { id: self.id, kind });
+    }
 }
 
 impl Drop for Client {
     fn drop(&mut self) {
-        self.app
+        // A destroyed window is out of the save set already
+        if self
+            .app
             .api()
-            .reparent_window(self.id, self.app.api().root(), self.x(), self.y());
+            .try_reparent_window(self.id, self.app.api().root(), self.x(), self.y())
+        {
+            self.app.api().remove_from_save_set(self.id);
+        }
 
-        self.app.api().remove_from_save_set(self.id);
         self.app.api().destroy_window(self.container_id);
     }
 }
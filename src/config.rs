@@ -0,0 +1,344 @@
+use crate::theme;
+use crate::theme::Theme;
+use serde::Deserialize;
+use serde::Serialize;
+use std::time::Duration;
+
+// Auto-hidden panels stay unmapped until the pointer touches the screen edge they're attached to.
+// They don't reserve any space, so maximized windows take the whole screen height
+pub const TOP_PANEL_AUTO_HIDE: bool = false;
+pub const BOTTOM_PANEL_AUTO_HIDE: bool = false;
+
+// Either of the presets from theme.rs, single fields can be overridden like this:
+// Theme { clock: Color::rgb(1.0, 1.0, 1.0), ..theme::WIN9X }.
+// Transparency of the panel backgrounds only has an effect when a compositor is running
+pub const THEME: Theme = theme::WIN9X;
+
+const _: () = assert!(is_valid_alpha(THEME.top_panel_background.alpha));
+const _: () = assert!(is_valid_alpha(THEME.bottom_panel_background.alpha));
+
+const fn is_valid_alpha(alpha: f64) -> bool {
+    alpha >= 0.0 && alpha <= 1.0
+}
+
+pub const TOP_PANEL_WORKSPACE_STYLE: WorkspaceStyle = WorkspaceStyle::ClassLabel;
+
+// What the top panel shows at its left edge, in the middle and at its right edge, in order.
+// The separator is drawn after the left sections
+pub const TOP_PANEL_SECTIONS_LEFT: &[TopPanelSection] = &[TopPanelSection::Workspaces];
+pub const TOP_PANEL_SECTIONS_CENTER: &[TopPanelSection] = &[];
+pub const TOP_PANEL_SECTIONS_RIGHT: &[TopPanelSection] = &[
+    TopPanelSection::Volume,
+    TopPanelSection::SystemStats,
+    TopPanelSection::Clock,
+];
+pub const TOP_PANEL_SECTION_SEPARATOR: Option<char> = Some('|');
+
+// Tasklist entries share the bottom panel's width within these bounds. If there are too many
+// of them to fit even at the minimum width, scrolling over the panel scrolls the tasklist
+pub const TASKLIST_MIN_ENTRY_WIDTH: u16 = 100;
+pub const TASKLIST_MAX_ENTRY_WIDTH: u16 = 300;
+pub const TASKLIST_SHOW_ICONS: bool = true;
+pub const TASKLIST_SHOW_TITLES: bool = true;
+
+// Tasklist entries are sorted by WM_CLASS, and classes with more than TASKLIST_GROUP_THRESHOLD
+// windows collapse into a single entry. Clicking or scrolling over it cycles through them.
+// Can be toggled with the toggle-tasklist-grouping IPC request
+pub const TASKLIST_GROUPING: bool = false;
+pub const TASKLIST_GROUP_THRESHOLD: usize = 2;
+
+const _: () = assert!(TASKLIST_MIN_ENTRY_WIDTH > 0);
+const _: () = assert!(TASKLIST_MIN_ENTRY_WIDTH <= TASKLIST_MAX_ENTRY_WIDTH);
+
+// System stats are shown in the top panel to the left of the clock
+pub const TOP_PANEL_SHOW_CPU_USAGE: bool = true;
+pub const TOP_PANEL_SHOW_MEMORY_USAGE: bool = true;
+
+// Volume indicator in the top panel, scrolling over it changes the volume by VOLUME_STEP percent.
+// The volume keys go through VOLUME_BACKEND as well
+pub const TOP_PANEL_SHOW_VOLUME: bool = true;
+pub const VOLUME_BACKEND: VolumeBackend = VolumeBackend::Amixer;
+pub const VOLUME_STEP: u8 = 5;
+
+// Commands below can refer to the active window with {window}, {class} and {title}, they're
+// substituted shell-escaped. {window} is the ID of the frame, so decorations are included.
+// Commands using any of them don't run if there's no active window
+
+// Mod4+Shift+PrintScreen
+pub const SCREENSHOT_WINDOW_COMMAND: &str =
+    "maim --hidecursor --window {window} | xclip -selection clipboard -t image/png";
+
+// Commands run on the other media keys
+pub const BRIGHTNESS_UP_COMMAND: &str = "brightnessctl set 5%+";
+pub const BRIGHTNESS_DOWN_COMMAND: &str = "brightnessctl set 5%-";
+pub const PLAY_PAUSE_COMMAND: &str = "playerctl play-pause";
+
+// Mod4+V types the PRIMARY selection into the focused window, the text is passed as the last argument
+pub const PASTE_PRIMARY_COMMAND: &str = "xdotool type --clearmodifiers --";
+
+// How much of the titlebar has to stay on screen when moving a window around
+pub const MIN_VISIBLE_TITLEBAR_WIDTH: u16 = 40;
+
+// Bounds for the size of windows, whatever the user or the window itself asks for.
+// They override the window's own size hints. None means no upper bound
+pub const MIN_WINDOW_WIDTH: u16 = 50;
+pub const MIN_WINDOW_HEIGHT: u16 = 50;
+pub const MAX_WINDOW_WIDTH: Option<u16> = None;
+pub const MAX_WINDOW_HEIGHT: Option<u16> = None;
+
+const _: () = assert!(MIN_WINDOW_WIDTH > 0 && MIN_WINDOW_HEIGHT > 0);
+const _: () = assert!(match MAX_WINDOW_WIDTH {
+    Some(max_width) => max_width >= MIN_WINDOW_WIDTH,
+    None => true,
+});
+const _: () = assert!(match MAX_WINDOW_HEIGHT {
+    Some(max_height) => max_height >= MIN_WINDOW_HEIGHT,
+    None => true,
+});
+
+// Keep the pointer from leaving the screen while moving or resizing a window
+pub const CONFINE_POINTER_DURING_DRAG: bool = false;
+
+// Resizing with the mouse only draws an outline of the new geometry, the window gets resized
+// once the button is released. Smoother with applications which are slow to redraw.
+// Holding Shift when grabbing the border does the same for a single resize
+pub const OUTLINE_RESIZE: bool = false;
+
+// Same for moving windows, for very slow machines
+pub const OUTLINE_MOVE: bool = false;
+
+// All drags only apply the final geometry once the button is released, without an outline
+// unless it's enabled above. There's nothing to see meanwhile, but no X traffic or redraws either
+pub const DEFER_DRAG_GEOMETRY: bool = false;
+
+// Mod4+J/K raise the window below/above the active one on screen instead of cycling the tasklist.
+// Mod4+H/L always raise the window to the left/right
+pub const DIRECTIONAL_FOCUS_JK: bool = false;
+
+// Move the pointer to the center of the window which gets focused by raising it, switching
+// workspaces or closing the previously focused one. Not if the pointer is over that window already
+pub const WARP_POINTER_ON_FOCUS: bool = false;
+
+// Mod4+Shift+Left/Right switch to the workspace the active window gets moved to, like
+// Mod4+Shift+Control+Left/Right always do. Mod4+Shift+1..9 never follow the window
+pub const SHIFT_ARROWS_FOLLOW_WINDOW: bool = false;
+
+// Mod4+Left/Right switch only between workspaces which have windows.
+// Moving a window with Shift still goes through all of them
+pub const CYCLE_SKIP_EMPTY: bool = false;
+
+// Resting the pointer in a corner of the screen for HOT_CORNER_DELAY triggers the action.
+// E.g. &[(Corner::TopLeft, HotCornerAction::Overview),
+// (Corner::BottomRight, HotCornerAction::PreviousWorkspace)]
+pub const HOT_CORNERS: &[(Corner, HotCornerAction)] =
+    &[(Corner::TopRight, HotCornerAction::Overview)];
+pub const HOT_CORNER_DELAY: Duration = Duration::from_millis(300);
+
+// How far from the very corner the pointer counts as being in it, in px
+pub const HOT_CORNER_SIZE: u16 = 2;
+
+const _: () = assert!(HOT_CORNER_SIZE > 0);
+
+// Played when a window gets mapped. Closing isn't animated, by the time we find out about it
+// the window's contents are gone already
+pub const WINDOW_ANIMATION: WindowAnimation = WindowAnimation::None;
+pub const WINDOW_ANIMATION_DURATION: Duration = Duration::from_millis(150);
+
+// Containers of the old and new workspace slide horizontally when switching between them
+pub const WORKSPACE_SWITCH_ANIMATION: bool = false;
+pub const WORKSPACE_SWITCH_ANIMATION_DURATION: Duration = Duration::from_millis(150);
+
+// The frame of a raised window flashes in the theme's raise_flash_frame color
+pub const RAISE_FLASH: bool = false;
+pub const RAISE_FLASH_DURATION: Duration = Duration::from_millis(120);
+
+// New windows with these WM_CLASS values open on the given workspace, counting from 0,
+// without switching to it. E.g. &[("firefox", 1), ("Code", 0)]
+pub const WORKSPACE_ASSIGNMENTS: &[(&str, usize)] = &[];
+
+const _: () = {
+    let mut index = 0;
+
+    while index < WORKSPACE_ASSIGNMENTS.len() {
+        assert!(WORKSPACE_ASSIGNMENTS[index].1 < 9);
+        index += 1;
+    }
+};
+
+// At most this many windows with the WM_CLASS can be open at once. Extra ones are destroyed
+// as soon as they get mapped, and the existing window is raised instead. E.g. &[("firefox", 1)]
+pub const MAX_INSTANCES: &[(&str, usize)] = &[];
+
+// Windows with these WM_CLASS values aren't managed at all: no frame, no focus, not in the
+// tasklist. They're mapped where they ask to be and kept above all windows, like on-screen
+// keyboards or annotation tools. E.g. &[("onboard", UnmanagedLayer::AbovePanels)]
+pub const UNMANAGED_CLASSES: &[(&str, UnmanagedLayer)] = &[];
+
+// Workspace to start on, counting from 0. None continues on the one which was active before
+// a restart
+pub const STARTUP_WORKSPACE: Option<usize> = None;
+
+const _: () = assert!(matches!(STARTUP_WORKSPACE, None | Some(0..=8)));
+
+// Layout of each workspace at startup, counting from 0. Mod4+Shift+M switches the active one
+// between floating and monocle, that survives restarts.
+// E.g. [Layout::Monocle, Layout::Floating, Layout::Floating, ...]
+pub const WORKSPACE_LAYOUTS: [Layout; 9] = [Layout::Floating; 9];
+
+// The root window is painted with the theme's root_background gradient. Turn it off when
+// the wallpaper is set by another program, e.g. feh
+pub const PAINT_ROOT_BACKGROUND: bool = true;
+
+// PNG image painted on the root window while each workspace is active, counting from 0. It's
+// scaled to cover the screen. Workspaces with None get the gradient, or keep the wallpaper of
+// the previous one without it. E.g. [Some("/home/user/sunset.png"), None, ...]
+pub const WORKSPACE_WALLPAPERS: [Option<&str>; 9] = [None; 9];
+
+// What Mod4+Escape does, after asking for confirmation
+pub const ESCAPE_ACTION: EscapeAction = EscapeAction::Restart;
+
+// Dialogs are centered over the window they belong to instead
+pub const NEW_WINDOW_PLACEMENT: Placement = Placement::Center;
+
+// Size of new windows which are mapped tiny, smaller than 100 px either way,
+// as they'd only resize themselves later
+pub const DEFAULT_WINDOW_WIDTH: u16 = 800;
+pub const DEFAULT_WINDOW_HEIGHT: u16 = 600;
+
+pub const NEW_WINDOW_FOCUS: NewWindowFocus = NewWindowFocus::StealFocus;
+
+#[allow(dead_code)]
+#[derive(PartialEq, Eq)]
+pub enum NewWindowFocus {
+    // New windows are raised and focused
+    StealFocus,
+
+    // New windows are put under all others without taking the focus
+    SilentBackground,
+
+    // Same as SilentBackground but the window is marked as urgent in the tasklist until raised
+    UrgentBackground,
+}
+
+#[allow(dead_code)]
+#[derive(PartialEq, Eq)]
+pub enum EscapeAction {
+    // Replaces the running vaporwm with the binary it was started from, keeping the windows
+    Restart,
+
+    // Gives the windows back to the X server and exits
+    Quit,
+
+    // Mod4+Escape isn't grabbed, so it goes to the focused window
+    Disabled,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
+pub enum Layout {
+    // Clients stay wherever they're put
+    #[default]
+    Floating,
+
+    // Every client is maximized, so only the active one is visible and the others are reached
+    // through the tasklist or Mod4+J/K. Always on top clients aren't kept above the active one.
+    // Mod4+Shift+F lets a single client float at its own geometry, above the maximized ones
+    Monocle,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    // In the middle of the screen
+    Center,
+
+    // In the top left corner, right below the top panel
+    TopLeft,
+
+    // Anywhere as long as the whole window is on screen
+    Random,
+
+    // Each new window is offset from the previous one, starting over from the top left corner
+    // once it'd run off the screen
+    Cascade,
+
+    // Where it overlaps the other windows on the workspace the least
+    Smart,
+
+    // Centered on the pointer
+    UnderPointer,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UnmanagedLayer {
+    // The panels stay over them
+    BelowPanels,
+
+    AbovePanels,
+}
+
+#[allow(dead_code)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HotCornerAction {
+    // Shows all workspaces with their windows, clicking one switches to it
+    Overview,
+
+    NextWorkspace,
+    PreviousWorkspace,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TopPanelSection {
+    Workspaces,
+
+    // Shown only if TOP_PANEL_SHOW_CPU_USAGE or TOP_PANEL_SHOW_MEMORY_USAGE is set
+    SystemStats,
+
+    // Shown only if TOP_PANEL_SHOW_VOLUME is set
+    Volume,
+
+    Clock,
+}
+
+#[allow(dead_code)]
+pub enum WorkspaceStyle {
+    // Class of the first window in the tasklist, or the workspace number if it's empty
+    ClassLabel,
+
+    // Always the workspace number
+    NumberOnly,
+
+    // A dot per workspace, filled if the workspace has windows
+    Dots,
+}
+
+#[allow(dead_code)]
+#[derive(PartialEq, Eq)]
+pub enum WindowAnimation {
+    None,
+
+    // Requires a compositor which respects _NET_WM_WINDOW_OPACITY
+    Fade,
+
+    // The window slides up into its position
+    Slide,
+}
+
+#[allow(dead_code)]
+pub enum VolumeBackend {
+    // ALSA via the amixer command
+    Amixer,
+
+    // PulseAudio (or PipeWire's PulseAudio server) via the pactl command
+    Pactl,
+}
@@ -0,0 +1,162 @@
+use crate::app::App;
+use crate::config;
+use crate::keycode::Key;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+use x11rb::protocol::xproto::CreateWindowAux;
+use x11rb::protocol::xproto::EventMask;
+use x11rb::protocol::xproto::KeyPressEvent;
+use x11rb::protocol::Event;
+
+const WIDTH: u16 = 420;
+const HEIGHT: u16 = 90;
+
+// A popup asking to confirm an action, answered with Enter or Escape.
+// It grabs the keyboard while shown, so nothing else receives keys until it's answered
+pub struct Dialog {
+    app: Rc<App>,
+    id: u32,
+    surface: cairo::XCBSurface,
+    need_redraw: Cell<bool>,
+    message: RefCell<String>,
+
+    // What to do if the user confirms, only present while the dialog is shown
+    on_confirm: RefCell<Option<Box<dyn FnOnce()>>>,
+}
+
+impl Dialog {
+    pub fn new(app: Rc<App>) -> Self {
+        let id = app.api().generate_id();
+
+        app.api().create_window(
+            id,
+            ((app.api().screen_width() - WIDTH) / 2) as _,
+            ((app.api().screen_height() - HEIGHT) / 2) as _,
+            WIDTH,
+            HEIGHT,
+            CreateWindowAux::new().event_mask(EventMask::KEY_PRESS | EventMask::EXPOSURE),
+        );
+
+        let surface = app.api().create_cairo_xcb_surface(id, WIDTH, HEIGHT);
+
+        Self {
+            app,
+            id,
+            surface,
+            need_redraw: Cell::new(false),
+            message: RefCell::new(String::new()),
+            on_confirm: RefCell::new(None),
+        }
+    }
+
+    // Replaces the question which is being asked already, if any
+    pub fn ask(&self, message: &str, on_confirm: impl FnOnce() + 'static) {
+        *self.message.borrow_mut() = message.to_owned();
+
+        let already_shown = self
+            .on_confirm
+            .replace(Some(Box::new(on_confirm)))
+            .is_some();
+
+        self.need_redraw.set(true);
+
+        if !already_shown {
+            self.app.api().map_window(self.id);
+            self.app.api().raise_window(self.id);
+
+            // Enter and Escape would never reach it
+            if !self.app.api().grab_keyboard(self.id) {
+                self.close();
+            }
+        }
+    }
+
+    // Returns the action to run if the dialog was shown
+    fn close(&self) -> Option<Box<dyn FnOnce()>> {
+        let on_confirm = self.on_confirm.take()?;
+
+        self.app.api().ungrab_keyboard();
+        self.app.api().unmap_window(self.id);
+
+        self.app.wm().refocus();
+
+        Some(on_confirm)
+    }
+
+    pub fn request_redraw(&self) {
+        if !self.need_redraw.take() || self.on_confirm.borrow().is_none() {
+            return;
+        }
+
+        let context = cairo::Context::new(&self.surface).unwrap();
+
+        context.set_line_width(1.0);
+        context.set_antialias(cairo::Antialias::None);
+
+        config::THEME.frame.set_source(&context);
+        context.paint().unwrap();
+
+        config::THEME.active_titlebar.0.set_source(&context);
+        context.rectangle(0.5, 0.5, WIDTH as f64 - 1.0, HEIGHT as f64 - 1.0);
+        context.stroke().unwrap();
+
+        context.select_font_face(
+            "PxPlus ToshibaTxL2 8x16",
+            cairo::FontSlant::Normal,
+            cairo::FontWeight::Normal,
+        );
+
+        context.set_font_size(16.0);
+        config::THEME.frame_outer_shadow.set_source(&context);
+
+        let message = self.message.borrow();
+        let lines = [message.as_str(), "[Enter] Yes / [Esc] No"];
+
+        for (index, line) in lines.into_iter().enumerate() {
+            let extents = context.text_extents(line).unwrap();
+
+            context.move_to(
+                (WIDTH as f64 - extents.width()) / 2.0 - extents.x_bearing(),
+                HEIGHT as f64 * (index + 1) as f64 / 3.0 - extents.y_bearing() / 2.0,
+            );
+
+            context.show_text(line).unwrap();
+        }
+
+        self.surface.flush();
+    }
+
+    fn handle_key_press(&self, event: &KeyPressEvent) {
+        let keymap = self.app.keymap();
+
+        if keymap.get_keycode(Key::Return) == Some(event.detail) {
+            if let Some(on_confirm) = self.close() {
+                on_confirm();
+            }
+        }
+        else if keymap.get_keycode(Key::Escape) == Some(event.detail) {
+            self.close();
+        }
+    }
+
+    pub fn handle_event(&self, event: &Event) {
+        match event {
+            Event::KeyPress(event) => {
+                if event.event == self.id {
+                    self.handle_key_press(event);
+                }
+            }
+            Event::Expose(event) => {
+                if event.window == self.id {
+                    self.need_redraw.set(true);
+                }
+            }
+            // Someone else has unmapped the dialog, we mustn't leave the keyboard grabbed
+            Event::UnmapNotify(event) if event.window == self.id => {
+                self.close();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use crate::config;
+use x11rb::properties::WmSizeHints;
+use x11rb::protocol::xproto::Gravity;
+
+// Sizes from WM_NORMAL_HINTS, only the ones that matter for resizing
+#[derive(Clone, Copy, Default)]
+pub struct SizeHints {
+    pub min: Option<(u16, u16)>,
+    pub max: Option<(u16, u16)>,
+    pub base: Option<(u16, u16)>,
+    pub increment: Option<(u16, u16)>,
+
+    // Minimum and maximum width / height ratio
+    pub aspect: Option<(f64, f64)>,
+
+    // Point of the window which stays in place when it resizes itself, see gravity_shift()
+    pub gravity: Gravity,
+}
+
+// The dimension which has been changed by the user and should be kept,
+// if possible, when the other one gets adjusted to satisfy the aspect ratio
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Width,
+    Height,
+}
+
+impl From<WmSizeHints> for SizeHints {
+    fn from(hints: WmSizeHints) -> Self {
+        let size = |(width, height): (i32, i32)| {
+            (
+                width.clamp(0, u16::MAX as _) as u16,
+                height.clamp(0, u16::MAX as _) as u16,
+            )
+        };
+
+        Self {
+            min: hints.min_size.map(size),
+            max: hints.max_size.map(size),
+            base: hints.base_size.map(size),
+            increment: hints
+                .size_increment
+                .map(size)
+                .filter(|(width, height)| *width > 0 && *height > 0),
+            aspect: hints
+                .aspect
+                .filter(|(min, max)| {
+                    min.numerator > 0
+                        && min.denominator > 0
+                        && max.numerator > 0
+                        && max.denominator > 0
+                })
+                .map(|(min, max)| {
+                    (
+                        min.numerator as f64 / min.denominator as f64,
+                        max.numerator as f64 / max.denominator as f64,
+                    )
+                }),
+            gravity: hints.win_gravity.unwrap_or(Gravity::NORTH_WEST),
+        }
+    }
+}
+
+pub fn apply_size_hints(width: u16, height: u16, hints: &SizeHints, kept: Dimension) -> (u16, u16) {
+    let mut width = width.max(1) as f64;
+    let mut height = height.max(1) as f64;
+
+    if let Some((min_aspect, max_aspect)) = hints.aspect {
+        let aspect = width / height;
+
+        let target_aspect = if aspect < min_aspect {
+            Some(min_aspect)
+        }
+        else if aspect > max_aspect {
+            Some(max_aspect)
+        }
+        else {
+            None
+        };
+
+        if let Some(target_aspect) = target_aspect {
+            match kept {
+                Dimension::Width => height = (width / target_aspect).round(),
+                Dimension::Height => width = (height * target_aspect).round(),
+            }
+        }
+    }
+
+    let mut width = width.clamp(1.0, u16::MAX as _) as u16;
+    let mut height = height.clamp(1.0, u16::MAX as _) as u16;
+
+    if let Some((width_increment, height_increment)) = hints.increment {
+        // ICCCM says to use the minimum size as the base size if the latter isn't set
+        let (base_width, base_height) = hints.base.or(hints.min).unwrap_or((0, 0));
+
+        if width > base_width {
+            width -= (width - base_width) % width_increment;
+        }
+
+        if height > base_height {
+            height -= (height - base_height) % height_increment;
+        }
+    }
+
+    if let Some((min_width, min_height)) = hints.min {
+        width = width.max(min_width);
+        height = height.max(min_height);
+    }
+
+    if let Some((max_width, max_height)) = hints.max {
+        if max_width > 0 {
+            width = width.min(max_width);
+        }
+
+        if max_height > 0 {
+            height = height.min(max_height);
+        }
+    }
+
+    apply_size_bounds(width.max(1), height.max(1))
+}
+
+// Bounds from the config, they take precedence over WM_NORMAL_HINTS
+pub fn apply_size_bounds(width: u16, height: u16) -> (u16, u16) {
+    let width = width.max(config::MIN_WINDOW_WIDTH);
+    let height = height.max(config::MIN_WINDOW_HEIGHT);
+
+    (
+        config::MAX_WINDOW_WIDTH.map_or(width, |max_width| width.min(max_width)),
+        config::MAX_WINDOW_HEIGHT.map_or(height, |max_height| height.min(max_height)),
+    )
+}
+
+// How far the window has to move when its size changes, so the point given by its gravity stays
+// where it was. E.g. the right edge for east gravities, the middle for north, center and south.
+// Only the size changes within the frame, so the frame moves along with the window
+pub fn gravity_shift(gravity: Gravity, old_size: (u16, u16), new_size: (u16, u16)) -> (i16, i16) {
+    let dx = old_size.0 as i16 - new_size.0 as i16;
+    let dy = old_size.1 as i16 - new_size.1 as i16;
+
+    let shift_x = match gravity {
+        Gravity::NORTH | Gravity::CENTER | Gravity::SOUTH => dx / 2,
+        Gravity::NORTH_EAST | Gravity::EAST | Gravity::SOUTH_EAST => dx,
+        _ => 0,
+    };
+
+    let shift_y = match gravity {
+        Gravity::WEST | Gravity::CENTER | Gravity::EAST => dy / 2,
+        Gravity::SOUTH_WEST | Gravity::SOUTH | Gravity::SOUTH_EAST => dy,
+        _ => 0,
+    };
+
+    (shift_x, shift_y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIXTEEN_NINE: f64 = 16.0 / 9.0;
+
+    fn aspect_hints(aspect: f64) -> SizeHints {
+        SizeHints {
+            aspect: Some((aspect, aspect)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn aspect_keeps_width() {
+        let hints = aspect_hints(SIXTEEN_NINE);
+
+        assert_eq!(
+            apply_size_hints(1600, 500, &hints, Dimension::Width),
+            (1600, 900)
+        );
+    }
+
+    #[test]
+    fn aspect_keeps_height() {
+        let hints = aspect_hints(SIXTEEN_NINE);
+
+        assert_eq!(
+            apply_size_hints(1000, 900, &hints, Dimension::Height),
+            (1600, 900)
+        );
+    }
+
+    #[test]
+    fn aspect_within_range_is_untouched() {
+        let hints = SizeHints {
+            aspect: Some((4.0 / 3.0, SIXTEEN_NINE)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            apply_size_hints(1500, 1000, &hints, Dimension::Width),
+            (1500, 1000)
+        );
+    }
+
+    #[test]
+    fn aspect_then_increments() {
+        let hints = SizeHints {
+            increment: Some((10, 10)),
+            ..aspect_hints(SIXTEEN_NINE)
+        };
+
+        // 1605 / (16 / 9) rounds to 903, then both snap down to the increment
+        assert_eq!(
+            apply_size_hints(1605, 100, &hints, Dimension::Width),
+            (1600, 900)
+        );
+    }
+
+    #[test]
+    fn aspect_increments_and_min_max() {
+        let hints = SizeHints {
+            min: Some((800, 450)),
+            max: Some((1280, 720)),
+            increment: Some((10, 10)),
+            ..aspect_hints(SIXTEEN_NINE)
+        };
+
+        assert_eq!(
+            apply_size_hints(1605, 100, &hints, Dimension::Width),
+            (1280, 720)
+        );
+
+        // Too small for the minimum after the aspect, which is also the base for increments
+        assert_eq!(
+            apply_size_hints(300, 300, &hints, Dimension::Height),
+            (800, 450)
+        );
+    }
+
+    #[test]
+    fn increments_count_from_base_size() {
+        let hints = SizeHints {
+            base: Some((104, 52)),
+            increment: Some((10, 20)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            apply_size_hints(259, 215, &hints, Dimension::Width),
+            (254, 212)
+        );
+    }
+
+    #[test]
+    fn gravity_shift_for_every_gravity() {
+        let shift = |gravity| gravity_shift(gravity, (200, 100), (100, 50));
+
+        assert_eq!(shift(Gravity::NORTH_WEST), (0, 0));
+        assert_eq!(shift(Gravity::NORTH), (50, 0));
+        assert_eq!(shift(Gravity::NORTH_EAST), (100, 0));
+        assert_eq!(shift(Gravity::WEST), (0, 25));
+        assert_eq!(shift(Gravity::CENTER), (50, 25));
+        assert_eq!(shift(Gravity::EAST), (100, 25));
+        assert_eq!(shift(Gravity::SOUTH_WEST), (0, 50));
+        assert_eq!(shift(Gravity::SOUTH), (50, 50));
+        assert_eq!(shift(Gravity::SOUTH_EAST), (100, 50));
+
+        // The window doesn't move relative to the root for these
+        assert_eq!(shift(Gravity::STATIC), (0, 0));
+        assert_eq!(shift(Gravity::BIT_FORGET), (0, 0));
+    }
+
+    // The frame is tight around the window, so moving the window by the shift keeps the point
+    // given by the gravity in place for the frame as well, whatever the border and titlebar are
+    #[test]
+    fn gravity_shift_with_titlebar_frame() {
+        let position = (500, 400);
+        let old_size = (300, 200);
+        let new_size = (451, 121);
+
+        for (border, titlebar) in [(0, 0), (1, 25), (4, 30)] {
+            // (x, y, width, height) of the frame for a window at 'position', as in Client
+            let frame = |(x, y): (i16, i16), (width, height): (u16, u16)| {
+                (
+                    x as i32 - border,
+                    y as i32 - border - titlebar,
+                    width as i32 + border * 2,
+                    height as i32 + border * 2 + titlebar,
+                )
+            };
+
+            let frames = |gravity| {
+                let (shift_x, shift_y) = gravity_shift(gravity, old_size, new_size);
+
+                (
+                    frame(position, old_size),
+                    frame((position.0 + shift_x, position.1 + shift_y), new_size),
+                )
+            };
+
+            let (old, new) = frames(Gravity::NORTH_WEST);
+            assert_eq!((old.0, old.1), (new.0, new.1));
+
+            // Doubled so it stays whole, an odd difference in size moves it by half a pixel
+            let center = |(x, y, width, height)| (x * 2 + width, y * 2 + height);
+            let (old, new) = frames(Gravity::CENTER);
+            assert!((center(old).0 - center(new).0).abs() <= 1);
+            assert!((center(old).1 - center(new).1).abs() <= 1);
+
+            let bottom_right = |(x, y, width, height)| (x + width, y + height);
+            let (old, new) = frames(Gravity::SOUTH_EAST);
+            assert_eq!(bottom_right(old), bottom_right(new));
+        }
+    }
+
+    #[test]
+    fn gravity_shift_when_growing() {
+        assert_eq!(
+            gravity_shift(Gravity::SOUTH_EAST, (100, 50), (200, 100)),
+            (-100, -50)
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use crate::app::App;
+use crate::config;
+use crate::config::Corner;
+use crate::config::HotCornerAction;
+use crate::util::cycle_next;
+use crate::util::cycle_previous;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
+use x11rb::protocol::xproto::CreateWindowAux;
+use x11rb::protocol::xproto::EventMask;
+use x11rb::protocol::xproto::NotifyMode;
+use x11rb::protocol::Event;
+
+// Small input-only windows in the corners of the screen from config::HOT_CORNERS. They're kept
+// above everything else, so they get the pointer even over panels and fullscreen windows
+pub struct HotCorners {
+    app: Rc<App>,
+
+    // Windows and their actions
+    corners: Vec<(u32, HotCornerAction)>,
+
+    // The corner the pointer has entered and when, if it's still there
+    entered: Cell<Option<(HotCornerAction, Instant)>>,
+}
+
+impl HotCorners {
+    pub fn new(app: Rc<App>) -> Self {
+        let size = config::HOT_CORNER_SIZE;
+        let right = (app.api().screen_width() - size) as i16;
+        let bottom = (app.api().screen_height() - size) as i16;
+
+        let corners = config::HOT_CORNERS
+            .iter()
+            .map(|(corner, action)| {
+                let id = app.api().generate_id();
+
+                let (x, y) = match corner {
+                    Corner::TopLeft => (0, 0),
+                    Corner::TopRight => (right, 0),
+                    Corner::BottomLeft => (0, bottom),
+                    Corner::BottomRight => (right, bottom),
+                };
+
+                app.api().create_input_only_window(
+                    id,
+                    x,
+                    y,
+                    size,
+                    size,
+                    CreateWindowAux::new()
+                        .event_mask(EventMask::ENTER_WINDOW | EventMask::LEAVE_WINDOW),
+                );
+
+                app.api().map_window(id);
+
+                (id, *action)
+            })
+            .collect();
+
+        Self {
+            app,
+            corners,
+            entered: Cell::new(None),
+        }
+    }
+
+    pub fn raise(&self) {
+        for (id, _) in &self.corners {
+            self.app.api().raise_window(*id);
+        }
+    }
+
+    // How long we may wait for events before the action has to be triggered
+    pub fn timeout(&self) -> Option<Duration> {
+        let (_, entered_at) = self.entered.get()?;
+        Some(config::HOT_CORNER_DELAY.saturating_sub(entered_at.elapsed()))
+    }
+
+    pub fn request_redraw(&self) {
+        if self
+            .timeout()
+            .is_some_and(|timeout| timeout == Duration::ZERO)
+        {
+            let Some((action, _)) = self.entered.take()
+            else {
+                return;
+            };
+
+            self.trigger(action);
+        }
+    }
+
+    fn trigger(&self, action: HotCornerAction) {
+        let wm = self.app.wm();
+
+        match action {
+            HotCornerAction::Overview => self.app.overview().show(),
+            HotCornerAction::NextWorkspace => {
+                wm.change_active_workspace(cycle_next(wm.workspaces(), wm.active_workspace_index()))
+            }
+            HotCornerAction::PreviousWorkspace => wm.change_active_workspace(cycle_previous(
+                wm.workspaces(),
+                wm.active_workspace_index(),
+            )),
+        }
+    }
+
+    pub fn handle_event(&self, event: &Event) {
+        match event {
+            // Not when a grab ends with the pointer still in the corner, e.g. after
+            // a workspace transition, the action would repeat right away
+            Event::EnterNotify(event) if event.mode == NotifyMode::NORMAL => {
+                if let Some((_, action)) = self.corners.iter().find(|(id, _)| *id == event.event) {
+                    self.entered.set(Some((*action, Instant::now())));
+                }
+            }
+            Event::LeaveNotify(event) if self.corners.iter().any(|(id, _)| *id == event.event) => {
+                self.entered.set(None)
+            }
+            _ => {}
+        }
+    }
+}
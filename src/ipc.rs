@@ -0,0 +1,124 @@
+use crate::app::App;
+use nix::unistd::getuid;
+use std::fs::DirBuilder;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::os::fd::AsFd;
+use std::os::fd::BorrowedFd;
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+
+// A client that connects and never sends anything, or never reads the response, shouldn't
+// freeze the whole WM
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Answers queries from external tools over a Unix socket.
+// Each connection sends one request line and gets one response line back, e.g. 'dump-state'
+pub struct Ipc {
+    app: Rc<App>,
+    listener: UnixListener,
+}
+
+impl Ipc {
+    // None if the socket can't be set up, the WM works without IPC then
+    pub fn new(app: Rc<App>) -> Option<Self> {
+        let listener = get_socket_path().and_then(|path| {
+            // Left over from the previous run, which has either crashed or re-executed itself
+            let _ = std::fs::remove_file(&path);
+
+            let listener = UnixListener::bind(&path)?;
+            listener.set_nonblocking(true)?;
+
+            Ok(listener)
+        });
+
+        match listener {
+            Ok(listener) => Some(Self { app, listener }),
+            Err(error) => {
+                eprintln!("Couldn't set up the IPC socket, running without it: {error}");
+                None
+            }
+        }
+    }
+
+    // Readable when there are connections waiting to be accepted
+    pub fn fd(&self) -> BorrowedFd<'_> {
+        self.listener.as_fd()
+    }
+
+    pub fn handle_connections(&self) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            self.handle_connection(stream);
+        }
+    }
+
+    fn handle_connection(&self, stream: UnixStream) {
+        if stream.set_nonblocking(false).is_err()
+            || stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_err()
+            || stream.set_write_timeout(Some(REQUEST_TIMEOUT)).is_err()
+        {
+            return;
+        }
+
+        let mut request = String::new();
+
+        if BufReader::new(&stream).read_line(&mut request).is_err() {
+            return;
+        }
+
+        let response = match request.trim() {
+            "dump-state" => self.app.wm().dump_state(),
+            "get-clients" => self.app.wm().dump_clients(false),
+            "get-clients --sort=recent" => self.app.wm().dump_clients(true),
+            "toggle-tasklist-grouping" => {
+                self.app.bottom_panel().toggle_grouping();
+                "ok".to_string()
+            }
+            request => format!("error: unknown request '{request}'"),
+        };
+
+        let _ = writeln!(&stream, "{response}");
+    }
+}
+
+// In $XDG_RUNTIME_DIR or a directory in /tmp which only we can access, so another user can't
+// take the path over
+fn get_socket_path() -> io::Result<PathBuf> {
+    let directory = match std::env::var_os("XDG_RUNTIME_DIR").filter(|path| !path.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => get_private_tmp_directory()?,
+    };
+
+    Ok(directory.join(format!("vaporwm{}.sock", std::env::var("DISPLAY").unwrap())))
+}
+
+fn get_private_tmp_directory() -> io::Result<PathBuf> {
+    let uid = getuid();
+    let path = PathBuf::from(format!("/tmp/vaporwm-{uid}"));
+
+    if let Err(error) = DirBuilder::new().mode(0o700).create(&path) {
+        if error.kind() != ErrorKind::AlreadyExists {
+            return Err(error);
+        }
+    }
+
+    // It might have been there already, created by someone else
+    let metadata = std::fs::symlink_metadata(&path)?;
+
+    if !metadata.is_dir() || metadata.uid() != uid.as_raw() || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::other(format!(
+            "{} isn't a private directory",
+            path.display()
+        )));
+    }
+
+    Ok(path)
+}
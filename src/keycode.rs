@@ -1,73 +1,250 @@
-use num_enum::TryFromPrimitive;
+use crate::app::App;
+use crate::config;
+use crate::config::EscapeAction;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use x11rb::protocol::xproto::Mapping;
 use x11rb::protocol::xproto::ModMask;
+use x11rb::protocol::Event;
 
-#[derive(Clone, Copy, TryFromPrimitive)]
-#[repr(u8)]
-pub enum Keycode {
-    K = 45,
-    J = 44,
-    M = 58,
-    X = 53,
-    Number1 = 10,
-    Number2 = 11,
-    Number3 = 12,
-    Number4 = 13,
-    Number5 = 14,
-    Number6 = 15,
-    Number7 = 16,
-    Number8 = 17,
-    Number9 = 18,
-    Left = 113,
-    Right = 114,
-    T = 28,
-    R = 27,
-    Escape = 9,
-    D = 40,
-    G = 42,
-    B = 56,
-    Z = 52,
-    S = 39,
-    PrintScreen = 107,
-    Q = 24,
+// Keys are identified by their keysyms, actual keycodes depend on the keyboard layout
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum Key {
+    K = 0x006b,
+    J = 0x006a,
+    M = 0x006d,
+    X = 0x0078,
+    Number1 = 0x0031,
+    Number2 = 0x0032,
+    Number3 = 0x0033,
+    Number4 = 0x0034,
+    Number5 = 0x0035,
+    Number6 = 0x0036,
+    Number7 = 0x0037,
+    Number8 = 0x0038,
+    Number9 = 0x0039,
+    Left = 0xff51,
+    Up = 0xff52,
+    Right = 0xff53,
+    Down = 0xff54,
+    T = 0x0074,
+    R = 0x0072,
+    Escape = 0xff1b,
+    Return = 0xff0d,
+    D = 0x0064,
+    G = 0x0067,
+    B = 0x0062,
+    Z = 0x007a,
+    S = 0x0073,
+    PrintScreen = 0xff61,
+    Q = 0x0071,
+    O = 0x006f,
+    H = 0x0068,
+    L = 0x006c,
+    V = 0x0076,
+    U = 0x0075,
+    A = 0x0061,
+    F = 0x0066,
+    F1 = 0xffbe,
+    F2 = 0xffbf,
+    F3 = 0xffc0,
+    F4 = 0xffc1,
+    F5 = 0xffc2,
+    F6 = 0xffc3,
+    F7 = 0xffc4,
+    F8 = 0xffc5,
+    F9 = 0xffc6,
+    SuperL = 0xffeb,
+    SuperR = 0xffec,
+    RaiseVolume = 0x1008ff13,
+    LowerVolume = 0x1008ff11,
+    Mute = 0x1008ff12,
+    BrightnessUp = 0x1008ff02,
+    BrightnessDown = 0x1008ff03,
+    PlayPause = 0x1008ff14,
 }
 
-pub fn get_keys_to_grab() -> [(Keycode, ModMask); 36] {
+impl Key {
+    pub fn keysym(self) -> u32 {
+        self as u32
+    }
+}
+
+pub struct Keymap {
+    app: Rc<App>,
+
+    // Keycodes by keysyms, including keys which aren't bound
+    keysym_table: RefCell<HashMap<u32, u8>>,
+
+    // Keys by their keycode and exact modifiers they're bound with
+    bindings: RefCell<HashMap<(u8, ModMask), Key>>,
+}
+
+impl Keymap {
+    pub fn new(app: Rc<App>) -> Self {
+        let this = Self {
+            app,
+            keysym_table: RefCell::new(HashMap::new()),
+            bindings: RefCell::new(HashMap::new()),
+        };
+
+        this.update();
+
+        this
+    }
+
+    fn update(&self) {
+        let keysym_table = self.app.api().get_keysym_table();
+
+        *self.bindings.borrow_mut() = get_keys_to_grab()
+            .into_iter()
+            // So the key reaches applications
+            .filter(|(key, _)| {
+                *key != Key::Escape || config::ESCAPE_ACTION != EscapeAction::Disabled
+            })
+            .filter_map(|(key, modmask)| Some(((*keysym_table.get(&key.keysym())?, modmask), key)))
+            .collect();
+
+        *self.keysym_table.borrow_mut() = keysym_table;
+    }
+
+    // Returns the key bound to the combination along with the modifiers it's bound with
+    pub fn get_key(&self, keycode: u8, state: impl Into<u16>) -> Option<(Key, ModMask)> {
+        let modmask = normalize_modmask(state.into());
+        let bindings = self.bindings.borrow();
+
+        bindings
+            .get(&(keycode, modmask))
+            .map(|key| (*key, modmask))
+            .or_else(|| {
+                bindings
+                    .get(&(keycode, ModMask::ANY))
+                    .map(|key| (*key, ModMask::ANY))
+            })
+    }
+
+    pub fn get_keycode(&self, key: Key) -> Option<u8> {
+        self.keysym_table.borrow().get(&key.keysym()).copied()
+    }
+
+    pub fn grab_keys(&self) {
+        let root = self.app.api().root();
+
+        for (keycode, modmask) in self.bindings.borrow().keys().copied() {
+            if modmask == ModMask::ANY {
+                self.app.api().grab_key(root, modmask, keycode);
+                continue;
+            }
+
+            // Grabs only match exact modifiers, so we have to grab every state of the locks too
+            for locks in get_lock_modifiers() {
+                self.app.api().grab_key(root, modmask | locks, keycode);
+            }
+        }
+    }
+
+    pub fn handle_event(&self, event: &Event) {
+        if let Event::MappingNotify(event) = event {
+            if event.request == Mapping::KEYBOARD {
+                // Existing grabs point at keycodes from the old mapping
+                self.app.api().ungrab_all_keys(self.app.api().root());
+                self.update();
+                self.grab_keys();
+            }
+        }
+    }
+}
+
+// CapsLock and NumLock, which is M2 on pretty much every layout
+pub fn get_lock_modifiers() -> [ModMask; 4] {
+    [
+        ModMask::default(),
+        ModMask::LOCK,
+        ModMask::M2,
+        ModMask::LOCK | ModMask::M2,
+    ]
+}
+
+// Drops lock and mouse button bits, so bindings work regardless of them
+fn normalize_modmask(state: u16) -> ModMask {
+    let modifiers =
+        ModMask::SHIFT | ModMask::CONTROL | ModMask::M1 | ModMask::M3 | ModMask::M4 | ModMask::M5;
+
+    ModMask::from(state & u16::from(modifiers))
+}
+
+pub fn get_keys_to_grab() -> [(Key, ModMask); 68] {
     [
-        (Keycode::K, ModMask::M4),
-        (Keycode::J, ModMask::M4),
-        (Keycode::K, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::J, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::M, ModMask::M4),
-        (Keycode::X, ModMask::M4),
-        (Keycode::Number1, ModMask::M4),
-        (Keycode::Number2, ModMask::M4),
-        (Keycode::Number3, ModMask::M4),
-        (Keycode::Number4, ModMask::M4),
-        (Keycode::Number5, ModMask::M4),
-        (Keycode::Number6, ModMask::M4),
-        (Keycode::Number7, ModMask::M4),
-        (Keycode::Number8, ModMask::M4),
-        (Keycode::Number9, ModMask::M4),
-        (Keycode::Number1, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::Number2, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::Number3, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::Number4, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::Number5, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::Number6, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::Number7, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::Number8, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::Number9, ModMask::M4 | ModMask::SHIFT),
-        (Keycode::Left, ModMask::M4),
-        (Keycode::Right, ModMask::M4),
-        (Keycode::T, ModMask::M4),
-        (Keycode::R, ModMask::M4),
-        (Keycode::Escape, ModMask::M4),
-        (Keycode::D, ModMask::M4),
-        (Keycode::G, ModMask::M4),
-        (Keycode::B, ModMask::M4),
-        (Keycode::Z, ModMask::M4),
-        (Keycode::S, ModMask::M4),
-        (Keycode::PrintScreen, ModMask::ANY),
-        (Keycode::Q, ModMask::M4),
+        (Key::K, ModMask::M4),
+        (Key::J, ModMask::M4),
+        (Key::K, ModMask::M4 | ModMask::SHIFT),
+        (Key::J, ModMask::M4 | ModMask::SHIFT),
+        (Key::M, ModMask::M4),
+        (Key::M, ModMask::M4 | ModMask::SHIFT),
+        (Key::X, ModMask::M4),
+        (Key::Number1, ModMask::M4),
+        (Key::Number2, ModMask::M4),
+        (Key::Number3, ModMask::M4),
+        (Key::Number4, ModMask::M4),
+        (Key::Number5, ModMask::M4),
+        (Key::Number6, ModMask::M4),
+        (Key::Number7, ModMask::M4),
+        (Key::Number8, ModMask::M4),
+        (Key::Number9, ModMask::M4),
+        (Key::Number1, ModMask::M4 | ModMask::SHIFT),
+        (Key::Number2, ModMask::M4 | ModMask::SHIFT),
+        (Key::Number3, ModMask::M4 | ModMask::SHIFT),
+        (Key::Number4, ModMask::M4 | ModMask::SHIFT),
+        (Key::Number5, ModMask::M4 | ModMask::SHIFT),
+        (Key::Number6, ModMask::M4 | ModMask::SHIFT),
+        (Key::Number7, ModMask::M4 | ModMask::SHIFT),
+        (Key::Number8, ModMask::M4 | ModMask::SHIFT),
+        (Key::Number9, ModMask::M4 | ModMask::SHIFT),
+        (Key::Left, ModMask::M4),
+        (Key::Right, ModMask::M4),
+        (Key::Left, ModMask::M4 | ModMask::SHIFT),
+        (Key::Right, ModMask::M4 | ModMask::SHIFT),
+        (Key::Left, ModMask::M4 | ModMask::SHIFT | ModMask::CONTROL),
+        (Key::Right, ModMask::M4 | ModMask::SHIFT | ModMask::CONTROL),
+        (Key::T, ModMask::M4),
+        (Key::R, ModMask::M4),
+        (Key::Escape, ModMask::M4),
+        (Key::D, ModMask::M4),
+        (Key::G, ModMask::M4),
+        (Key::B, ModMask::M4),
+        (Key::Z, ModMask::M4),
+        (Key::S, ModMask::M4),
+        (Key::S, ModMask::M4 | ModMask::SHIFT),
+        (Key::PrintScreen, ModMask::ANY),
+        (Key::PrintScreen, ModMask::M4 | ModMask::SHIFT),
+        (Key::Q, ModMask::M4),
+        (Key::O, ModMask::M4),
+        (Key::H, ModMask::M4),
+        (Key::L, ModMask::M4),
+        (Key::V, ModMask::M4),
+        (Key::U, ModMask::M4),
+        (Key::A, ModMask::M4),
+        (Key::A, ModMask::M4 | ModMask::SHIFT),
+        (Key::F, ModMask::M4 | ModMask::SHIFT),
+        (Key::F1, ModMask::M4),
+        (Key::F2, ModMask::M4),
+        (Key::F3, ModMask::M4),
+        (Key::F4, ModMask::M4),
+        (Key::F5, ModMask::M4),
+        (Key::F6, ModMask::M4),
+        (Key::F7, ModMask::M4),
+        (Key::F8, ModMask::M4),
+        (Key::F9, ModMask::M4),
+        // Only to know whether Mod4 is held, they don't do anything by themselves
+        (Key::SuperL, ModMask::ANY),
+        (Key::SuperR, ModMask::ANY),
+        (Key::RaiseVolume, ModMask::ANY),
+        (Key::LowerVolume, ModMask::ANY),
+        (Key::Mute, ModMask::ANY),
+        (Key::BrightnessUp, ModMask::ANY),
+        (Key::BrightnessDown, ModMask::ANY),
+        (Key::PlayPause, ModMask::ANY),
     ]
 }
@@ -1,20 +1,33 @@
 #![feature(coroutines)]
 #![feature(iter_from_coroutine)]
-#![feature(slice_as_chunks)]
 #![allow(clippy::too_many_arguments)]
 
+mod animation;
 mod api;
 mod app;
 mod bottom_panel;
 mod client;
+mod config;
+mod dialog;
+mod hints;
+mod hot_corner;
+mod ipc;
 mod keycode;
+mod menu;
+mod outline;
+mod overview;
+mod placement;
+mod serialized_state;
 mod spawner;
+mod system_stats;
+mod theme;
 mod top_panel;
 mod util;
+mod volume;
+mod wallpaper;
 mod wm;
 
 use app::App;
-use keycode::get_keys_to_grab;
 use nix::libc::STDERR_FILENO;
 use nix::libc::STDOUT_FILENO;
 use nix::unistd::dup2;
@@ -45,25 +58,59 @@ fn main() {
         .check()
         .expect("There is a window manager running already");
 
+    app.start_ipc();
+
     app.api()
         .set_window_cursor(app.api().root(), app.api().cursors.left_ptr);
 
-    for (keycode, modmask) in get_keys_to_grab() {
-        app.api().grab_key(app.api().root(), modmask, keycode);
-    }
+    app.api()
+        .advertise_supported_hints("vaporwm", &[app.api().atoms._NET_WM_MOVERESIZE]);
+
+    app.keymap().grab_keys();
+
+    // Unmanaged windows were adopted before the panels existed
+    app.wm().raise_panels();
 
     loop {
+        app.dispatch_client_changes();
         app.top_panel().request_redraw();
         app.bottom_panel().request_redraw();
         app.wm().request_redraw();
+        app.hot_corners().request_redraw();
+        app.overview().request_redraw();
+        app.dialog().request_redraw();
+        app.menu().request_redraw();
         app.api().flush();
 
-        for event in app.api().wait_for_events(Duration::from_secs(1)) {
+        let timeout = [app.hot_corners().timeout(), app.wm().timeout()]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(Duration::from_secs(1));
+
+        let fds = [
+            Some(app.top_panel().volume_fd()),
+            app.ipc().map(|ipc| ipc.fd()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+        for event in app.api().wait_for_events(timeout, &fds) {
+            app.keymap().handle_event(&event);
             app.wm().handle_event(&event);
             app.top_panel().handle_event(&event);
             app.bottom_panel().handle_event(&event);
+            app.overview().handle_event(&event);
+            app.hot_corners().handle_event(&event);
+            app.dialog().handle_event(&event);
+            app.menu().handle_event(&event);
             app.spawner().handle_event(&event);
         }
+
+        if let Some(ipc) = app.ipc() {
+            ipc.handle_connections();
+        }
     }
 }
 
@@ -0,0 +1,398 @@
+use crate::app::App;
+use crate::client::Client;
+use crate::config;
+use crate::keycode::Key;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+use x11rb::protocol::xproto::ButtonIndex;
+use x11rb::protocol::xproto::ButtonPressEvent;
+use x11rb::protocol::xproto::CreateWindowAux;
+use x11rb::protocol::xproto::EventMask;
+use x11rb::protocol::Event;
+
+const WIDTH: u16 = 220;
+const ENTRY_HEIGHT: u16 = 24;
+const TEXT_MARGIN_LEFT: f64 = 10.0;
+
+// Maximum interval between two clicks of a double click, in milliseconds
+const DOUBLE_CLICK_TIME: u32 = 400;
+
+// What the user has picked, handled by Wm::handle_menu_action()
+#[derive(Clone, Copy)]
+pub enum MenuAction {
+    Close,
+    ToggleMaximized,
+
+    // Enter the keyboard move and resize modes
+    Move,
+    Resize,
+
+    Minimize,
+    ToggleShaded,
+    ToggleAbove,
+    MoveToWorkspace(usize),
+}
+
+#[derive(Clone, Copy)]
+enum Entry {
+    Action(MenuAction),
+
+    // Replaces the entries with the list of workspaces
+    MoveToWorkspaceSubmenu,
+
+    // Goes back from the workspace list
+    Back,
+}
+
+// A popup with actions for a single client, opened from its tasklist entry, titlebar or its icon.
+// It grabs the pointer and keyboard while shown, clicking outside of it or pressing Escape
+// dismisses it. A second right click right after the one which has opened it shades the client,
+// so double clicking the titlebar works as before
+pub struct Menu {
+    app: Rc<App>,
+    id: u32,
+    surface: cairo::XCBSurface,
+    need_redraw: Cell<bool>,
+
+    // Client the actions are for, only present while the menu is shown
+    client_id: Cell<Option<u32>>,
+
+    // Where the menu has been opened, it's placed so this is one of its corners
+    anchor: Cell<(i16, i16)>,
+
+    // Server time of the click which has opened the menu
+    opened_at: Cell<u32>,
+
+    // Opened by a click on the titlebar icon, a second click closes the client
+    opened_from_icon: Cell<bool>,
+
+    entries: RefCell<Vec<Entry>>,
+    hovered_entry: Cell<Option<usize>>,
+}
+
+impl Menu {
+    pub fn new(app: Rc<App>) -> Self {
+        let id = app.api().generate_id();
+
+        app.api().create_window(
+            id,
+            0,
+            0,
+            WIDTH,
+            ENTRY_HEIGHT,
+            CreateWindowAux::new().event_mask(
+                EventMask::BUTTON_PRESS
+                    | EventMask::POINTER_MOTION
+                    | EventMask::KEY_PRESS
+                    | EventMask::EXPOSURE,
+            ),
+        );
+
+        let surface = app.api().create_cairo_xcb_surface(id, WIDTH, ENTRY_HEIGHT);
+
+        Self {
+            app,
+            id,
+            surface,
+            need_redraw: Cell::new(false),
+            client_id: Cell::new(None),
+            anchor: Cell::new((0, 0)),
+            opened_at: Cell::new(0),
+            opened_from_icon: Cell::new(false),
+            entries: RefCell::new(Vec::new()),
+            hovered_entry: Cell::new(None),
+        }
+    }
+
+    // Replaces the menu which is shown already, if any
+    pub fn show(&self, client_id: u32, x: i16, y: i16, time: u32) {
+        self.opened_at.set(time);
+        self.opened_from_icon.set(false);
+        self.show_main_entries(client_id, x, y);
+    }
+
+    // Same as show(), but a double click closes the client like on the classic window menu
+    pub fn show_for_icon(&self, client_id: u32, x: i16, y: i16, time: u32) {
+        self.show(client_id, x, y, time);
+        self.opened_from_icon.set(true);
+    }
+
+    fn show_main_entries(&self, client_id: u32, x: i16, y: i16) {
+        let already_shown = self.client_id.replace(Some(client_id)).is_some();
+        self.anchor.set((x, y));
+
+        self.set_entries(vec![
+            Entry::Action(MenuAction::ToggleMaximized),
+            Entry::Action(MenuAction::Move),
+            Entry::Action(MenuAction::Resize),
+            Entry::Action(MenuAction::Minimize),
+            Entry::Action(MenuAction::ToggleShaded),
+            Entry::Action(MenuAction::ToggleAbove),
+            Entry::MoveToWorkspaceSubmenu,
+            Entry::Action(MenuAction::Close),
+        ]);
+
+        if !already_shown {
+            self.app.api().map_window(self.id);
+            self.app.api().grab_pointer_for_popup(self.id);
+
+            // It couldn't be closed with Escape or navigated
+            if !self.app.api().grab_keyboard(self.id) {
+                self.close();
+                return;
+            }
+        }
+
+        self.app.api().raise_window(self.id);
+    }
+
+    fn close(&self) {
+        if self.client_id.take().is_none() {
+            return;
+        }
+
+        self.app.api().ungrab_pointer();
+        self.app.api().ungrab_keyboard();
+        self.app.api().unmap_window(self.id);
+
+        self.app.wm().refocus();
+    }
+
+    // Resizes the window to fit the entries, it opens upwards or to the left if it would go off screen
+    fn set_entries(&self, entries: Vec<Entry>) {
+        let height = entries.len() as u16 * ENTRY_HEIGHT;
+        let (anchor_x, anchor_y) = self.anchor.get();
+
+        let x = anchor_x.min((self.app.api().screen_width() - WIDTH) as _);
+
+        let y = if anchor_y as u16 + height > self.app.api().screen_height() {
+            anchor_y - height as i16
+        }
+        else {
+            anchor_y
+        };
+
+        self.app.api().set_window_x(self.id, x.max(0));
+        self.app.api().set_window_y(self.id, y.max(0));
+        self.app.api().set_window_height(self.id, height);
+        self.surface.set_size(WIDTH as _, height as _).unwrap();
+
+        *self.entries.borrow_mut() = entries;
+        self.hovered_entry.set(None);
+        self.need_redraw.set(true);
+    }
+
+    // Whether the client has the state, false if it's gone already
+    fn client_has(&self, state: impl Fn(&Client) -> bool) -> bool {
+        self.client_id.get().is_some_and(|id| {
+            self.app
+                .wm()
+                .active_workspace()
+                .stack()
+                .iter()
+                .any(|client| client.id() == id && state(client))
+        })
+    }
+
+    fn entry_label(&self, entry: Entry) -> String {
+        match entry {
+            Entry::Action(MenuAction::Close) => "Close".to_owned(),
+            Entry::Action(MenuAction::ToggleMaximized) => {
+                if self.client_has(Client::maximized) {
+                    "Restore".to_owned()
+                }
+                else {
+                    "Maximize".to_owned()
+                }
+            }
+            Entry::Action(MenuAction::Move) => "Move".to_owned(),
+            Entry::Action(MenuAction::Resize) => "Size".to_owned(),
+            Entry::Action(MenuAction::Minimize) => "Minimize".to_owned(),
+            Entry::Action(MenuAction::ToggleShaded) => {
+                if self.client_has(Client::shaded) {
+                    "Unshade".to_owned()
+                }
+                else {
+                    "Shade".to_owned()
+                }
+            }
+            Entry::Action(MenuAction::ToggleAbove) => {
+                if self.client_has(Client::above) {
+                    "[x] Always on top".to_owned()
+                }
+                else {
+                    "[ ] Always on top".to_owned()
+                }
+            }
+            Entry::Action(MenuAction::MoveToWorkspace(index)) => {
+                format!("Workspace [{}]", index + 1)
+            }
+            Entry::MoveToWorkspaceSubmenu => "Move to workspace >".to_owned(),
+            Entry::Back => "< Back".to_owned(),
+        }
+    }
+
+    pub fn request_redraw(&self) {
+        if !self.need_redraw.take() || self.client_id.get().is_none() {
+            return;
+        }
+
+        let context = cairo::Context::new(&self.surface).unwrap();
+        let entries = self.entries.borrow();
+        let height = entries.len() as f64 * ENTRY_HEIGHT as f64;
+
+        context.set_line_width(1.0);
+        context.set_antialias(cairo::Antialias::None);
+
+        config::THEME.frame.set_source(&context);
+        context.paint().unwrap();
+
+        context.select_font_face(
+            "PxPlus ToshibaTxL2 8x16",
+            cairo::FontSlant::Normal,
+            cairo::FontWeight::Normal,
+        );
+
+        context.set_font_size(16.0);
+
+        for (index, entry) in entries.iter().enumerate() {
+            let top = index as f64 * ENTRY_HEIGHT as f64;
+            let label = self.entry_label(*entry);
+
+            if self.hovered_entry.get() == Some(index) {
+                config::THEME.active_titlebar.0.set_source(&context);
+                context.rectangle(0.0, top, WIDTH as _, ENTRY_HEIGHT as _);
+                context.fill().unwrap();
+
+                config::THEME.titlebar_text.set_source(&context);
+            }
+            else {
+                config::THEME.frame_outer_shadow.set_source(&context);
+            }
+
+            let extents = context.text_extents(&label).unwrap();
+
+            context.move_to(
+                TEXT_MARGIN_LEFT,
+                (top + ENTRY_HEIGHT as f64 / 2.0 - extents.y_bearing() / 2.0).floor(),
+            );
+
+            context.show_text(&label).unwrap();
+        }
+
+        config::THEME.frame_outer_shadow.set_source(&context);
+        context.rectangle(0.5, 0.5, WIDTH as f64 - 1.0, height - 1.0);
+        context.stroke().unwrap();
+
+        self.surface.flush();
+    }
+
+    // The pointer is grabbed, so events from anywhere on the screen are reported relative to the menu
+    fn entry_at(&self, x: i16, y: i16) -> Option<usize> {
+        if x < 0 || y < 0 || x as u16 >= WIDTH {
+            return None;
+        }
+
+        let index = y as usize / ENTRY_HEIGHT as usize;
+        (index < self.entries.borrow().len()).then_some(index)
+    }
+
+    fn handle_button_press(&self, event: &ButtonPressEvent) {
+        let is_double_click = event.time.wrapping_sub(self.opened_at.get()) <= DOUBLE_CLICK_TIME;
+
+        let double_click_action = match ButtonIndex::from(event.detail) {
+            ButtonIndex::M1 if self.opened_from_icon.get() => Some(MenuAction::Close),
+            ButtonIndex::M3 => Some(MenuAction::ToggleShaded),
+            _ => None,
+        };
+
+        if let Some(action) = double_click_action.filter(|_| is_double_click) {
+            let client_id = self.client_id.get();
+            self.close();
+
+            if let Some(client_id) = client_id {
+                self.app.wm().handle_menu_action(client_id, action);
+            }
+
+            return;
+        }
+
+        let Some(index) = self.entry_at(event.event_x, event.event_y)
+        else {
+            self.close();
+            return;
+        };
+
+        let entry = self.entries.borrow()[index];
+
+        match entry {
+            Entry::Action(action) => {
+                let client_id = self.client_id.get();
+                self.close();
+
+                if let Some(client_id) = client_id {
+                    self.app.wm().handle_menu_action(client_id, action);
+                }
+            }
+            Entry::MoveToWorkspaceSubmenu => {
+                let mut entries = vec![Entry::Back];
+
+                entries.extend(
+                    (0..self.app.wm().workspaces().len())
+                        .map(|index| Entry::Action(MenuAction::MoveToWorkspace(index))),
+                );
+
+                self.set_entries(entries);
+            }
+            Entry::Back => {
+                if let Some(client_id) = self.client_id.get() {
+                    let (x, y) = self.anchor.get();
+                    self.show_main_entries(client_id, x, y);
+                }
+            }
+        }
+    }
+
+    pub fn handle_event(&self, event: &Event) {
+        if self.client_id.get().is_none() {
+            return;
+        }
+
+        match event {
+            Event::ButtonPress(event) => {
+                if event.event == self.id {
+                    self.handle_button_press(event);
+                }
+            }
+            Event::MotionNotify(event) => {
+                if event.event == self.id {
+                    let hovered_entry = self.entry_at(event.event_x, event.event_y);
+
+                    if self.hovered_entry.replace(hovered_entry) != hovered_entry {
+                        self.need_redraw.set(true);
+                    }
+                }
+            }
+            Event::KeyPress(event) => {
+                if event.event == self.id
+                    && self.app.keymap().get_keycode(Key::Escape) == Some(event.detail)
+                {
+                    self.close();
+                }
+            }
+            Event::Expose(event) => {
+                if event.window == self.id {
+                    self.need_redraw.set(true);
+                }
+            }
+            // The client is gone, its actions can't be run anymore
+            Event::UnmapNotify(event)
+                if Some(event.window) == self.client_id.get() || event.window == self.id =>
+            {
+                self.close()
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::app::App;
+use crate::config;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const LINE_WIDTH: f64 = 2.0;
+const DASH_LENGTH: f64 = 6.0;
+
+// Dashed rectangle drawn over everything while a window is dragged with an outline.
+// The overlay window only exists while it's shown
+pub struct Outline {
+    app: Rc<App>,
+    overlay: RefCell<Option<(u32, cairo::XCBSurface)>>,
+}
+
+impl Outline {
+    pub fn new(app: Rc<App>) -> Self {
+        Self {
+            app,
+            overlay: RefCell::new(None),
+        }
+    }
+
+    // The rectangle is where the container would go
+    pub fn show(&self, x: i16, y: i16, width: u16, height: u16) {
+        let mut overlay = self.overlay.borrow_mut();
+
+        let (_, surface) = overlay.get_or_insert_with(|| {
+            let api = self.app.api();
+            let id = api.generate_id();
+
+            // It's the topmost window once mapped, panels included
+            api.create_overlay_window(id);
+            api.map_window(id);
+
+            let surface = api.create_cairo_xcb_surface(id, api.screen_width(), api.screen_height());
+
+            (id, surface)
+        });
+
+        let context = cairo::Context::new(&*surface).unwrap();
+
+        context.set_operator(cairo::Operator::Source);
+        context.set_source_rgba(0.0, 0.0, 0.0, 0.0);
+        context.paint().unwrap();
+
+        context.set_line_width(LINE_WIDTH);
+        context.set_dash(&[DASH_LENGTH, DASH_LENGTH], 0.0);
+        config::THEME.drag_outline.set_source(&context);
+
+        context.rectangle(
+            x as f64 + LINE_WIDTH / 2.0,
+            y as f64 + LINE_WIDTH / 2.0,
+            width as f64 - LINE_WIDTH,
+            height as f64 - LINE_WIDTH,
+        );
+
+        context.stroke().unwrap();
+        surface.flush();
+    }
+
+    pub fn hide(&self) {
+        if let Some((id, _)) = self.overlay.take() {
+            self.app.api().destroy_window(id);
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use crate::api::ICON_SIZE;
+use crate::app::App;
+use crate::client::Client;
+use crate::config;
+use crate::keycode::Key;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+use x11rb::protocol::xproto::ButtonIndex;
+use x11rb::protocol::xproto::CreateWindowAux;
+use x11rb::protocol::xproto::EventMask;
+use x11rb::protocol::xproto::NotifyMode;
+use x11rb::protocol::Event;
+
+const MARGIN: f64 = 40.0;
+
+pub struct Overview {
+    app: Rc<App>,
+    id: u32,
+    surface: cairo::XCBSurface,
+    visible: Cell<bool>,
+    need_redraw: Cell<bool>,
+
+    // Areas where workspaces and their clients are drawn, in drawing order, so the topmost is last
+    layout: RefCell<Vec<Area>>,
+
+    // Same as for TopPanel
+    deferred_click: Cell<Option<(i16, i16)>>,
+}
+
+struct Area {
+    workspace_index: usize,
+    client_id: Option<u32>,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Area {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        (self.x..=self.x + self.width).contains(&x) && (self.y..=self.y + self.height).contains(&y)
+    }
+}
+
+impl Overview {
+    pub fn new(app: Rc<App>) -> Self {
+        let id = app.api().generate_id();
+
+        app.api().create_window(
+            id,
+            0,
+            0,
+            app.api().screen_width(),
+            app.api().screen_height(),
+            CreateWindowAux::new().event_mask(
+                EventMask::BUTTON_PRESS | EventMask::KEY_PRESS | EventMask::LEAVE_WINDOW,
+            ),
+        );
+
+        let surface = app.api().create_cairo_xcb_surface(
+            id,
+            app.api().screen_width(),
+            app.api().screen_height(),
+        );
+
+        Self {
+            app,
+            id,
+            surface,
+            visible: Cell::new(false),
+            need_redraw: Cell::new(false),
+            layout: RefCell::new(Vec::new()),
+            deferred_click: Cell::new(None),
+        }
+    }
+
+    // Through a hot corner, see config::HOT_CORNERS
+    pub fn show(&self) {
+        self.visible.set(true);
+        self.need_redraw.set(true);
+
+        self.app.api().map_window(self.id);
+        self.app.api().raise_window(self.id);
+
+        // It couldn't be left with Escape
+        if !self.app.api().grab_keyboard(self.id) {
+            self.hide();
+        }
+    }
+
+    fn hide(&self) {
+        if !self.visible.take() {
+            return;
+        }
+
+        self.app.api().ungrab_keyboard();
+        self.app.api().unmap_window(self.id);
+
+        self.app.wm().refocus();
+    }
+
+    fn redraw(&self) {
+        if !self.need_redraw.take() || !self.visible.get() {
+            return;
+        }
+
+        let context = cairo::Context::new(&self.surface).unwrap();
+
+        context.set_line_width(1.0);
+        context.set_antialias(cairo::Antialias::None);
+
+        context.set_operator(cairo::Operator::Source);
+        context.set_source_rgba(0.0, 0.0, 0.0, 0.8);
+        context.paint().unwrap();
+        context.set_operator(cairo::Operator::Over);
+
+        context.select_font_face(
+            "PxPlus ToshibaTxL2 8x16",
+            cairo::FontSlant::Normal,
+            cairo::FontWeight::Normal,
+        );
+
+        let screen_width = self.app.api().screen_width() as f64;
+        let screen_height = self.app.api().screen_height() as f64;
+
+        // Workspaces are laid out in a 3x3 grid, each one being a scaled down screen
+        let scale = ((screen_width - MARGIN * 4.0) / (screen_width * 3.0))
+            .min((screen_height - MARGIN * 4.0) / (screen_height * 3.0));
+
+        let cell_width = (screen_width * scale).floor();
+        let cell_height = (screen_height * scale).floor();
+        let left = ((screen_width - cell_width * 3.0 - MARGIN * 2.0) / 2.0).floor();
+        let top = ((screen_height - cell_height * 3.0 - MARGIN * 2.0) / 2.0).floor();
+
+        let mut layout = self.layout.borrow_mut();
+        layout.clear();
+
+        for (index, workspace) in self.app.wm().workspaces().iter().enumerate() {
+            let x = left + (index % 3) as f64 * (cell_width + MARGIN);
+            let y = top + (index / 3) as f64 * (cell_height + MARGIN);
+
+            self.draw_workspace(&context, index, x, y, cell_width, cell_height);
+
+            layout.push(Area {
+                workspace_index: index,
+                client_id: None,
+                x,
+                y,
+                width: cell_width,
+                height: cell_height,
+            });
+
+            let stack = workspace.stack();
+
+            for (stack_index, client) in stack.iter().enumerate() {
+                let area = Area {
+                    workspace_index: index,
+                    client_id: Some(client.id()),
+                    x: x + (client.container_x() as f64 * scale).round(),
+                    y: y + (client.container_y() as f64 * scale).round(),
+                    width: (client.container_width() as f64 * scale).round(),
+                    height: (client.container_height() as f64 * scale).round(),
+                };
+
+                let is_active = stack_index == stack.len() - 1;
+                self.draw_client(&context, &area, client, is_active);
+
+                layout.push(area);
+            }
+        }
+
+        self.surface.flush();
+    }
+
+    fn draw_workspace(
+        &self,
+        context: &cairo::Context,
+        index: usize,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) {
+        context.set_source_rgb(0.15, 0.15, 0.15);
+        context.rectangle(x, y, width, height);
+        context.fill().unwrap();
+
+        if index == self.app.wm().active_workspace_index() {
+            config::THEME.active_titlebar.1.set_source(context);
+        }
+        else {
+            config::THEME.inactive_text.set_source(context);
+        }
+
+        context.rectangle(x - 0.5, y - 0.5, width + 1.0, height + 1.0);
+        context.stroke().unwrap();
+
+        config::THEME.active_text.set_source(context);
+        context.set_font_size(16.0);
+        context.move_to(x, y - 8.0);
+        context.show_text(&format!("[{}]", index + 1)).unwrap();
+    }
+
+    fn draw_client(&self, context: &cairo::Context, area: &Area, client: &Client, is_active: bool) {
+        context.save().unwrap();
+        context.rectangle(area.x, area.y, area.width, area.height);
+        context.clip();
+
+        // Same colors as the titlebars have
+        if is_active {
+            config::THEME.active_titlebar.0.set_source(context);
+        }
+        else {
+            config::THEME.inactive_titlebar.0.set_source(context);
+        }
+
+        context.paint().unwrap();
+
+        context
+            .set_source_surface(
+                client
+                    .icon()
+                    .as_deref()
+                    .unwrap_or(&self.app.api().default_icon),
+                area.x + 4.0,
+                area.y + 4.0,
+            )
+            .unwrap();
+
+        context.source().set_filter(cairo::Filter::Nearest);
+        context.paint().unwrap();
+
+        let title = client
+            .title()
+            .clone()
+            .unwrap_or_else(|| format!("[{}]", client.id()));
+
+        config::THEME.titlebar_text.set_source(context);
+        context.set_font_size(12.0);
+
+        let extents = context.text_extents(&title).unwrap();
+
+        context.move_to(
+            area.x + ICON_SIZE as f64 + 8.0,
+            area.y + 4.0 + (ICON_SIZE as f64 - extents.y_bearing()) / 2.0,
+        );
+
+        context.show_text(&title).unwrap();
+        context.restore().unwrap();
+
+        config::THEME.frame_outer_shadow.set_source(context);
+        context.rectangle(
+            area.x + 0.5,
+            area.y + 0.5,
+            area.width - 1.0,
+            area.height - 1.0,
+        );
+        context.stroke().unwrap();
+    }
+
+    fn handle_click(&self, x: i16, y: i16) {
+        let target = self
+            .layout
+            .borrow()
+            .iter()
+            .rev()
+            .find(|area| area.contains(x as _, y as _))
+            .map(|area| (area.workspace_index, area.client_id));
+
+        self.hide();
+
+        let Some((workspace_index, client_id)) = target
+        else {
+            return;
+        };
+
+        self.app.wm().change_active_workspace(workspace_index);
+
+        let Some(client_id) = client_id
+        else {
+            return;
+        };
+
+        if let Some(stack_index) = self.app.wm().active_workspace().stack_index_of(client_id) {
+            self.app.wm().raise_client(stack_index);
+        }
+    }
+
+    pub fn request_redraw(&self) {
+        self.redraw();
+
+        if let Some((x, y)) = self.deferred_click.take() {
+            self.handle_click(x, y);
+        }
+    }
+
+    pub fn handle_event(&self, event: &Event) {
+        match event {
+            Event::LeaveNotify(event) => {
+                if event.event == self.id && event.mode == NotifyMode::NORMAL {
+                    self.hide();
+                }
+            }
+            Event::ButtonPress(event) => {
+                if event.event == self.id && ButtonIndex::from(event.detail) == ButtonIndex::M1 {
+                    self.deferred_click
+                        .set(Some((event.event_x, event.event_y)));
+                }
+            }
+            Event::KeyPress(event)
+                if event.event == self.id
+                    && self.app.keymap().get_keycode(Key::Escape) == Some(event.detail) =>
+            {
+                self.hide()
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use crate::config::Placement;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::hash::Hasher;
+
+// How far each cascaded window is from the previous one
+const CASCADE_STEP: i16 = 30;
+
+// Rectangles are (x, y, width, height) of containers, 'area' is the part of the screen between
+// the panels. Returns where the new container's top left corner goes, it stays within the area
+// if it fits there
+pub fn place_container(
+    placement: Placement,
+    width: u16,
+    height: u16,
+    area: (i16, i16, u16, u16),
+    containers: &[(i16, i16, u16, u16)],
+    previous: Option<(i16, i16)>,
+    pointer: (i16, i16),
+) -> (i16, i16) {
+    let (area_x, area_y, area_width, area_height) = area;
+
+    let centered = (
+        area_x + (area_width as i16 - width as i16) / 2,
+        area_y + (area_height as i16 - height as i16) / 2,
+    );
+
+    let (x, y) = match placement {
+        Placement::Center => centered,
+        Placement::TopLeft => (area_x, area_y),
+        Placement::Random => (
+            area_x + random_below(area_width.saturating_sub(width) as u64 + 1) as i16,
+            area_y + random_below(area_height.saturating_sub(height) as u64 + 1) as i16,
+        ),
+        Placement::UnderPointer => (pointer.0 - width as i16 / 2, pointer.1 - height as i16 / 2),
+        Placement::Cascade => {
+            let (x, y) = previous
+                .map(|(x, y)| (x + CASCADE_STEP, y + CASCADE_STEP))
+                .unwrap_or((area_x, area_y));
+
+            // Starts over from the top left corner once it would run off the area
+            if fits(x, y, width, height, area) {
+                (x, y)
+            }
+            else {
+                (area_x, area_y)
+            }
+        }
+        Placement::Smart => {
+            find_least_overlapping(width, height, area, containers).unwrap_or(centered)
+        }
+    };
+
+    clamp_to_area(x, y, width, height, area)
+}
+
+// For dialogs, regardless of the placement policy. 'parent' is the container they belong to
+pub fn center_over_parent(
+    width: u16,
+    height: u16,
+    area: (i16, i16, u16, u16),
+    (parent_x, parent_y, parent_width, parent_height): (i16, i16, u16, u16),
+) -> (i16, i16) {
+    clamp_to_area(
+        parent_x + (parent_width as i16 - width as i16) / 2,
+        parent_y + (parent_height as i16 - height as i16) / 2,
+        width,
+        height,
+        area,
+    )
+}
+
+fn fits(
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+    (area_x, area_y, area_width, area_height): (i16, i16, u16, u16),
+) -> bool {
+    x >= area_x
+        && y >= area_y
+        && x as i32 + width as i32 <= area_x as i32 + area_width as i32
+        && y as i32 + height as i32 <= area_y as i32 + area_height as i32
+}
+
+fn clamp_to_area(
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+    (area_x, area_y, area_width, area_height): (i16, i16, u16, u16),
+) -> (i16, i16) {
+    let x = x.min(area_x + area_width as i16 - width as i16).max(area_x);
+    let y = y
+        .min(area_y + area_height as i16 - height as i16)
+        .max(area_y);
+
+    (x, y)
+}
+
+// Candidates are the area's corner and positions right next to the other containers, so windows
+// end up tiled edge to edge while there's free space. Earlier candidates win ties, which favors
+// the top left. None if the container doesn't fit into the area at all
+fn find_least_overlapping(
+    width: u16,
+    height: u16,
+    area: (i16, i16, u16, u16),
+    containers: &[(i16, i16, u16, u16)],
+) -> Option<(i16, i16)> {
+    let (area_x, area_y, _, _) = area;
+
+    let xs = std::iter::once(area_x)
+        .chain(containers.iter().map(|(x, _, width, _)| x + *width as i16))
+        .chain(containers.iter().map(|(x, ..)| x - width as i16))
+        .collect::<Vec<_>>();
+
+    let ys = std::iter::once(area_y)
+        .chain(
+            containers
+                .iter()
+                .map(|(_, y, _, height)| y + *height as i16),
+        )
+        .chain(containers.iter().map(|(_, y, ..)| y - height as i16))
+        .collect::<Vec<_>>();
+
+    let mut result = None;
+    let mut min_overlap = i64::MAX;
+
+    for &y in &ys {
+        for &x in &xs {
+            if !fits(x, y, width, height, area) {
+                continue;
+            }
+
+            let overlap = containers
+                .iter()
+                .map(|container| overlap((x, y, width, height), *container))
+                .sum::<i64>();
+
+            if overlap < min_overlap {
+                result = Some((x, y));
+                min_overlap = overlap;
+            }
+        }
+    }
+
+    result
+}
+
+// Every RandomState is seeded differently, good enough for scattering windows around
+fn random_below(bound: u64) -> u64 {
+    RandomState::new().build_hasher().finish() % bound
+}
+
+fn overlap(a: (i16, i16, u16, u16), b: (i16, i16, u16, u16)) -> i64 {
+    let length = |a_start: i16, a_length: u16, b_start: i16, b_length: u16| {
+        let start = a_start.max(b_start) as i64;
+        let end = (a_start as i64 + a_length as i64).min(b_start as i64 + b_length as i64);
+
+        (end - start).max(0)
+    };
+
+    length(a.0, a.2, b.0, b.2) * length(a.1, a.3, b.1, b.3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Between a 30 pixel top panel and a 30 pixel bottom panel
+    const AREA: (i16, i16, u16, u16) = (0, 30, 1280, 660);
+
+    fn place(placement: Placement, containers: &[(i16, i16, u16, u16)]) -> (i16, i16) {
+        place_container(placement, 400, 300, AREA, containers, None, (0, 0))
+    }
+
+    #[test]
+    fn center() {
+        assert_eq!(place(Placement::Center, &[]), (440, 210));
+    }
+
+    #[test]
+    fn top_left() {
+        assert_eq!(place(Placement::TopLeft, &[]), (0, 30));
+    }
+
+    #[test]
+    fn cascade() {
+        let cascade =
+            |previous| place_container(Placement::Cascade, 400, 300, AREA, &[], previous, (0, 0));
+
+        assert_eq!(cascade(None), (0, 30));
+        assert_eq!(cascade(Some((100, 130))), (130, 160));
+    }
+
+    #[test]
+    fn cascade_wraps_around() {
+        let cascade =
+            |previous| place_container(Placement::Cascade, 400, 300, AREA, &[], previous, (0, 0));
+
+        // The right edge would go past the area
+        assert_eq!(cascade(Some((900, 100))), (0, 30));
+
+        // The bottom edge would
+        assert_eq!(cascade(Some((100, 380))), (0, 30));
+    }
+
+    #[test]
+    fn smart_goes_next_to_others() {
+        assert_eq!(place(Placement::Smart, &[]), (0, 30));
+        assert_eq!(place(Placement::Smart, &[(0, 30, 400, 300)]), (400, 30));
+    }
+
+    #[test]
+    fn smart_picks_least_overlap() {
+        // The top half and the bottom left quarter are taken
+        let containers = [(0, 30, 1280, 330), (0, 360, 640, 330)];
+
+        assert_eq!(place(Placement::Smart, &containers), (640, 360));
+    }
+
+    #[test]
+    fn smart_centers_what_doesnt_fit() {
+        let placed = place_container(Placement::Smart, 1300, 200, AREA, &[], None, (0, 0));
+
+        // Too wide, so it's at the left edge, but still centered vertically
+        assert_eq!(placed, (0, 260));
+    }
+
+    #[test]
+    fn under_pointer() {
+        let under_pointer =
+            |pointer| place_container(Placement::UnderPointer, 400, 300, AREA, &[], None, pointer);
+
+        assert_eq!(under_pointer((640, 400)), (440, 250));
+
+        // Clamped to the area
+        assert_eq!(under_pointer((10, 1000)), (0, 390));
+        assert_eq!(under_pointer((1270, 0)), (880, 30));
+    }
+
+    #[test]
+    fn random_stays_in_area() {
+        for _ in 0..100 {
+            let (x, y) = place(Placement::Random, &[]);
+            assert!(fits(x, y, 400, 300, AREA));
+        }
+
+        // There's no room to move it around
+        let placed = place_container(Placement::Random, 1280, 660, AREA, &[], None, (0, 0));
+        assert_eq!(placed, (0, 30));
+    }
+
+    #[test]
+    fn center_over_parent_clamps() {
+        assert_eq!(
+            center_over_parent(200, 100, AREA, (100, 100, 600, 400)),
+            (300, 250)
+        );
+
+        // The parent is partly off the area
+        assert_eq!(
+            center_over_parent(400, 300, AREA, (1100, 500, 300, 300)),
+            (880, 390)
+        );
+    }
+}
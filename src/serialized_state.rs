@@ -0,0 +1,422 @@
+use crate::config::Layout;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use std::collections::HashSet;
+
+// Bumped whenever the meaning of a field in the saved state changes, fields which are only added
+// get serde defaults instead. State saved by a newer build is ignored
+pub const SERIALIZED_STATE_VERSION: u32 = 1;
+
+// Saved before restarting and read back on start, so it may be stale, come from an older build
+// or be edited by hand. Nothing in it is trusted, sanitize() makes it satisfy the invariants:
+// - the active workspace index is less than the workspace count
+// - client IDs are unique within a workspace's stack
+// - the tasklist has each ID from the same workspace's stack exactly once and nothing else
+// - the last focused ID, if any, is in the same workspace's stack
+// - client sizes aren't zero
+// Clients whose windows no longer exist are skipped when restoring
+#[derive(Serialize, Deserialize, Default)]
+pub struct SerializedState {
+    // Missing in state saved before it was introduced, which is version 0
+    #[serde(default)]
+    pub version: u32,
+
+    pub workspaces: [SerializedWorkspace; 9],
+    pub active_workspace_index: usize,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct SerializedWorkspace {
+    pub stack: Vec<SerializedClient>,
+    pub tasklist: Vec<u32>,
+
+    #[serde(default)]
+    pub last_focused: Option<u32>,
+
+    // None in state saved before layouts existed, config::WORKSPACE_LAYOUTS applies then
+    #[serde(default, deserialize_with = "deserialize_layout")]
+    pub layout: Option<Layout>,
+}
+
+// Unknown layouts, e.g. from a newer build, fall back to floating instead of discarding the state.
+// Null stays None, so config::WORKSPACE_LAYOUTS still applies
+fn deserialize_layout<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Layout>, D::Error> {
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+
+    Ok(value.map(|value| serde_json::from_value(value).unwrap_or_default()))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SerializedClient {
+    pub id: u32,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub maximized: bool,
+
+    #[serde(default)]
+    pub shaded: bool,
+
+    #[serde(default)]
+    pub last_activity: u32,
+
+    #[serde(default)]
+    pub minimized: bool,
+
+    #[serde(default)]
+    pub above: bool,
+
+    #[serde(default)]
+    pub floating: bool,
+
+    // Only for IPC queries, they're read from the window itself when restoring
+    #[serde(default)]
+    pub class: Option<String>,
+
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+impl SerializedState {
+    pub fn sanitize(&mut self) {
+        if self.active_workspace_index >= self.workspaces.len() {
+            self.active_workspace_index = 0;
+        }
+
+        for workspace in &mut self.workspaces {
+            workspace.sanitize();
+        }
+    }
+
+    // Describes the first invariant which doesn't hold
+    pub fn check_invariants(&self) -> Result<(), String> {
+        if self.active_workspace_index >= self.workspaces.len() {
+            return Err(format!(
+                "active workspace index {} is out of range",
+                self.active_workspace_index
+            ));
+        }
+
+        for (index, workspace) in self.workspaces.iter().enumerate() {
+            workspace
+                .check_invariants()
+                .map_err(|error| format!("workspace {index}: {error}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SerializedWorkspace {
+    fn sanitize(&mut self) {
+        let mut ids = HashSet::new();
+        self.stack.retain(|client| ids.insert(client.id));
+
+        // Saved order is kept, clients which are missing go to the end in stack order
+        let mut tasklist_ids = HashSet::new();
+        self.tasklist
+            .retain(|id| ids.contains(id) && tasklist_ids.insert(*id));
+
+        for client in &self.stack {
+            if tasklist_ids.insert(client.id) {
+                self.tasklist.push(client.id);
+            }
+        }
+
+        self.last_focused = self.last_focused.filter(|id| ids.contains(id));
+
+        for client in &mut self.stack {
+            client.width = client.width.max(1);
+            client.height = client.height.max(1);
+        }
+    }
+
+    fn check_invariants(&self) -> Result<(), String> {
+        let mut ids = HashSet::new();
+
+        for client in &self.stack {
+            if !ids.insert(client.id) {
+                return Err(format!("client {} is in the stack twice", client.id));
+            }
+
+            if client.width == 0 || client.height == 0 {
+                return Err(format!("client {} has a zero size", client.id));
+            }
+        }
+
+        let mut tasklist_ids = HashSet::new();
+
+        for id in &self.tasklist {
+            if !ids.contains(id) {
+                return Err(format!(
+                    "client {id} is in the tasklist but not in the stack"
+                ));
+            }
+
+            if !tasklist_ids.insert(*id) {
+                return Err(format!("client {id} is in the tasklist twice"));
+            }
+        }
+
+        if tasklist_ids.len() != ids.len() {
+            return Err("some clients from the stack are missing from the tasklist".to_string());
+        }
+
+        match self.last_focused {
+            Some(id) if !ids.contains(&id) => {
+                Err(format!("last focused client {id} isn't in the stack"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serialized_client(id: u32) -> SerializedClient {
+        SerializedClient {
+            id,
+            x: 10,
+            y: 20,
+            width: 300,
+            height: 200,
+            maximized: false,
+            shaded: false,
+            last_activity: 0,
+            minimized: false,
+            above: false,
+            floating: false,
+            class: None,
+            title: None,
+        }
+    }
+
+    fn round_trip(state: &SerializedState) -> SerializedState {
+        serde_json::from_str(&serde_json::to_string(state).unwrap()).unwrap()
+    }
+
+    fn assert_same(state: &SerializedState, other: &SerializedState) {
+        assert_eq!(
+            serde_json::to_value(state).unwrap(),
+            serde_json::to_value(other).unwrap()
+        );
+    }
+
+    #[test]
+    fn round_trip_keeps_flags_and_tasklist_order() {
+        let mut state = SerializedState {
+            version: SERIALIZED_STATE_VERSION,
+            active_workspace_index: 2,
+            ..Default::default()
+        };
+
+        state.workspaces[2] = SerializedWorkspace {
+            stack: vec![
+                SerializedClient {
+                    minimized: true,
+                    above: true,
+                    last_activity: 42,
+                    ..serialized_client(1)
+                },
+                SerializedClient {
+                    maximized: true,
+                    shaded: true,
+                    floating: true,
+                    class: Some("XTerm".to_string()),
+                    title: Some("~".to_string()),
+                    ..serialized_client(2)
+                },
+                serialized_client(3),
+            ],
+            tasklist: vec![3, 1, 2],
+            last_focused: Some(2),
+            layout: Some(Layout::Monocle),
+        };
+
+        let restored = round_trip(&state);
+        assert_same(&state, &restored);
+
+        let workspace = &restored.workspaces[2];
+        let flags = |client: &SerializedClient| {
+            (
+                client.minimized,
+                client.above,
+                client.maximized,
+                client.shaded,
+                client.floating,
+            )
+        };
+
+        assert_eq!(restored.active_workspace_index, 2);
+        assert_eq!(workspace.tasklist, [3, 1, 2]);
+        assert_eq!(workspace.last_focused, Some(2));
+        assert_eq!(workspace.layout, Some(Layout::Monocle));
+        assert_eq!(
+            flags(&workspace.stack[0]),
+            (true, true, false, false, false)
+        );
+        assert_eq!(flags(&workspace.stack[1]), (false, false, true, true, true));
+        assert_eq!(
+            flags(&workspace.stack[2]),
+            (false, false, false, false, false)
+        );
+        assert_eq!(workspace.stack[0].last_activity, 42);
+    }
+
+    #[test]
+    fn round_trip_empty_workspaces() {
+        let state = SerializedState::default();
+        let restored = round_trip(&state);
+
+        assert_same(&state, &restored);
+        assert!(restored
+            .workspaces
+            .iter()
+            .all(|workspace| workspace.stack.is_empty() && workspace.tasklist.is_empty()));
+    }
+
+    #[test]
+    fn round_trip_client_missing_from_tasklist() {
+        let mut state = SerializedState::default();
+
+        state.workspaces[0].stack = vec![serialized_client(1), serialized_client(2)];
+        state.workspaces[0].tasklist = vec![2];
+
+        let restored = round_trip(&state);
+
+        assert_same(&state, &restored);
+        assert_eq!(restored.workspaces[0].stack.len(), 2);
+        assert_eq!(restored.workspaces[0].tasklist, [2]);
+    }
+
+    #[test]
+    fn old_schema_gets_defaults() {
+        // As saved before versions, flags other than maximized, last focused IDs and layouts
+        let client =
+            r#"{"id": 1, "x": 10, "y": 20, "width": 300, "height": 200, "maximized": true}"#;
+        let workspace = format!(r#"{{"stack": [{client}], "tasklist": [1]}}"#);
+        let workspaces = vec![workspace; 9].join(", ");
+        let json = format!(r#"{{"workspaces": [{workspaces}], "active_workspace_index": 4}}"#);
+
+        let state: SerializedState = serde_json::from_str(&json).unwrap();
+        let workspace = &state.workspaces[0];
+        let client = &workspace.stack[0];
+
+        assert_eq!(state.version, 0);
+        assert_eq!(state.active_workspace_index, 4);
+        assert_eq!(workspace.tasklist, [1]);
+        assert_eq!(workspace.last_focused, None);
+        assert_eq!(workspace.layout, None);
+        assert!(client.maximized);
+        assert!(!client.shaded && !client.minimized && !client.above && !client.floating);
+        assert_eq!(client.last_activity, 0);
+        assert_eq!(client.class, None);
+    }
+
+    #[test]
+    fn unknown_layout_falls_back_to_floating() {
+        let workspace: SerializedWorkspace =
+            serde_json::from_str(r#"{"stack": [], "tasklist": [], "layout": "Spiral"}"#).unwrap();
+
+        assert_eq!(workspace.layout, Some(Layout::Floating));
+    }
+
+    fn state_with_stack(stack: Vec<SerializedClient>, tasklist: Vec<u32>) -> SerializedState {
+        let mut state = SerializedState::default();
+
+        state.workspaces[0].stack = stack;
+        state.workspaces[0].tasklist = tasklist;
+        state
+    }
+
+    fn sanitized(mut state: SerializedState) -> SerializedState {
+        state.sanitize();
+        assert_eq!(state.check_invariants(), Ok(()));
+        state
+    }
+
+    #[test]
+    fn default_state_satisfies_invariants() {
+        assert_eq!(SerializedState::default().check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn sanitize_active_workspace_index() {
+        let state = SerializedState {
+            active_workspace_index: 9,
+            ..Default::default()
+        };
+
+        assert!(state.check_invariants().is_err());
+        assert_eq!(sanitized(state).active_workspace_index, 0);
+    }
+
+    #[test]
+    fn sanitize_tasklist() {
+        let stack = vec![
+            serialized_client(1),
+            serialized_client(2),
+            serialized_client(3),
+        ];
+
+        // Unknown and repeated IDs are dropped, missing ones are appended in stack order
+        let state = state_with_stack(stack, vec![3, 7, 3, 1]);
+        assert!(state.check_invariants().is_err());
+        assert_eq!(sanitized(state).workspaces[0].tasklist, [3, 1, 2]);
+    }
+
+    #[test]
+    fn sanitize_repeated_stack_ids() {
+        let stack = vec![
+            serialized_client(1),
+            serialized_client(2),
+            serialized_client(1),
+        ];
+
+        let state = state_with_stack(stack, vec![1, 2]);
+        assert!(state.check_invariants().is_err());
+
+        let state = sanitized(state);
+        let ids = state.workspaces[0]
+            .stack
+            .iter()
+            .map(|client| client.id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(ids, [1, 2]);
+    }
+
+    #[test]
+    fn sanitize_last_focused() {
+        let mut state = state_with_stack(vec![serialized_client(1)], vec![1]);
+        state.workspaces[0].last_focused = Some(2);
+        assert!(state.check_invariants().is_err());
+        assert_eq!(sanitized(state).workspaces[0].last_focused, None);
+
+        let mut state = state_with_stack(vec![serialized_client(1)], vec![1]);
+        state.workspaces[0].last_focused = Some(1);
+        assert_eq!(sanitized(state).workspaces[0].last_focused, Some(1));
+    }
+
+    #[test]
+    fn sanitize_zero_sizes() {
+        let client = SerializedClient {
+            width: 0,
+            height: 0,
+            ..serialized_client(1)
+        };
+
+        let state = state_with_stack(vec![client], vec![1]);
+        assert!(state.check_invariants().is_err());
+
+        let state = sanitized(state);
+        let client = &state.workspaces[0].stack[0];
+        assert_eq!((client.width, client.height), (1, 1));
+    }
+}
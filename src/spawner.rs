@@ -1,40 +1,131 @@
-use crate::keycode::Keycode;
+use crate::app::App;
+use crate::config;
+use crate::keycode::Key;
+use crate::volume::VolumeChange;
 use std::process::Command;
 use std::process::Stdio;
+use std::rc::Rc;
+use std::time::Duration;
+use x11rb::protocol::xproto::AtomEnum;
+use x11rb::protocol::xproto::ModMask;
 use x11rb::protocol::Event;
 
-pub struct Spawner;
+// A hung selection owner shouldn't freeze the whole WM
+const SELECTION_TIMEOUT: Duration = Duration::from_millis(500);
+
+// What config commands can refer to, in the order of the values passed to substitute()
+const PLACEHOLDERS: [&str; 3] = ["{window}", "{class}", "{title}"];
+
+pub struct Spawner {
+    app: Rc<App>,
+}
 
 impl Spawner {
-    pub fn new() -> Self {
-        Self
+    pub fn new(app: Rc<App>) -> Self {
+        Self { app }
     }
 
     pub fn handle_event(&self, event: &Event) {
         if let Event::KeyPress(event) = event {
-            let Ok(keycode) = Keycode::try_from(event.detail)
+            // Same as for Wm. In resize mode the keyboard is grabbed on the root window,
+            // those keys are only meant for resizing
+            if event.event != self.app.api().root() || self.app.wm().in_input_mode() {
+                return;
+            }
+
+            let Some((key, modmask)) = self.app.keymap().get_key(event.detail, event.state)
             else {
                 return;
             };
 
-            match keycode {
-                Keycode::PrintScreen => bash("maim --hidecursor | xclip -selection clipboard -t image/png"),
-                Keycode::S => bash("maim --select --highlight --color=255,255,255,0.05 --hidecursor | xclip -selection clipboard -t image/png"),
-                Keycode::T => bash("xfce4-terminal &"),
-                Keycode::D => bash("thunar &"),
-                Keycode::G => bash("xfce4-taskmanager &"),
-                Keycode::B => bash("firefox &"),
-                Keycode::Q => bash("copyq show &"),
-                Keycode::R => bash("rofi -show drun &"),
+            match key {
+                Key::PrintScreen if modmask == ModMask::M4 | ModMask::SHIFT => {
+                    self.spawn(config::SCREENSHOT_WINDOW_COMMAND)
+                }
+                Key::PrintScreen => bash("maim --hidecursor | xclip -selection clipboard -t image/png"),
+                Key::S if modmask == ModMask::M4 => bash("maim --select --highlight --color=255,255,255,0.05 --hidecursor | xclip -selection clipboard -t image/png"),
+                Key::T => bash("xfce4-terminal"),
+                Key::D => bash("thunar"),
+                Key::G => bash("xfce4-taskmanager"),
+                Key::B => bash("firefox"),
+                Key::Q => bash("copyq show"),
+                Key::R => bash("rofi -show drun"),
+                Key::RaiseVolume => self.app.top_panel().change_volume(VolumeChange::Raise),
+                Key::LowerVolume => self.app.top_panel().change_volume(VolumeChange::Lower),
+                Key::Mute => self.app.top_panel().change_volume(VolumeChange::ToggleMute),
+                Key::BrightnessUp => self.spawn(config::BRIGHTNESS_UP_COMMAND),
+                Key::BrightnessDown => self.spawn(config::BRIGHTNESS_DOWN_COMMAND),
+                Key::PlayPause => self.spawn(config::PLAY_PAUSE_COMMAND),
+                Key::V => self.paste_primary_selection(),
                 _ => {}
             }
         }
     }
+
+    // Runs a command from the config with the active window's placeholders substituted
+    fn spawn(&self, template: &str) {
+        if !PLACEHOLDERS
+            .iter()
+            .any(|placeholder| template.contains(placeholder))
+        {
+            bash(template);
+            return;
+        }
+
+        let stack = self.app.wm().active_workspace().stack();
+
+        let Some(client) = stack.last().filter(|client| !client.minimized())
+        else {
+            return;
+        };
+
+        let values = [
+            client.container_id().to_string(),
+            shell_escape(client.class().as_deref().unwrap_or_default()),
+            shell_escape(client.title().as_deref().unwrap_or_default()),
+        ];
+
+        let command = substitute(template, &values);
+        drop(stack);
+
+        bash(&command);
+    }
+
+    fn paste_primary_selection(&self) {
+        if self.app.wm().active_workspace().stack().is_empty() {
+            return;
+        }
+
+        let Some(text) = self
+            .app
+            .api()
+            .get_selection(AtomEnum::PRIMARY.into(), SELECTION_TIMEOUT)
+        else {
+            return;
+        };
+
+        // The text goes as a separate argument, so the shell doesn't interpret it
+        Command::new("bash")
+            .args([
+                "-c",
+                &format!("{} \"$1\" &", config::PASTE_PRIMARY_COMMAND),
+                "bash",
+                &text,
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap();
+    }
 }
 
+// In the background, so a hung command can't stall the WM. The shell exits right away,
+// waiting for it only reaps it
 fn bash(command: &str) {
     Command::new("bash")
-        .args(["-c", command])
+        .args(["-c", &format!("(\n{command}\n) &")])
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .spawn()
@@ -42,3 +133,98 @@ fn bash(command: &str) {
         .wait()
         .unwrap();
 }
+
+// In a single pass, so placeholders which come with the values aren't substituted again
+fn substitute(template: &str, values: &[String; PLACEHOLDERS.len()]) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        match PLACEHOLDERS
+            .iter()
+            .zip(values)
+            .find(|(placeholder, _)| rest.starts_with(*placeholder))
+        {
+            Some((placeholder, value)) => {
+                result.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+// Single quotes keep everything literal, except for single quotes themselves
+fn shell_escape(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(window: &str, class: &str, title: &str) -> [String; PLACEHOLDERS.len()] {
+        [window.to_string(), shell_escape(class), shell_escape(title)]
+    }
+
+    #[test]
+    fn substitutes_every_placeholder() {
+        assert_eq!(
+            substitute(
+                "xdotool windowkill {window} # {class} {title} {window}",
+                &values("42", "Firefox", "Mozilla Firefox")
+            ),
+            "xdotool windowkill 42 # 'Firefox' 'Mozilla Firefox' 42"
+        );
+    }
+
+    #[test]
+    fn keeps_placeholders_from_values() {
+        assert_eq!(
+            substitute("echo {title} {class}", &values("1", "{window}", "{title}")),
+            "echo '{title}' '{window}'"
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_braces() {
+        assert_eq!(
+            substitute(
+                "awk '{print $1}' {window} {{class}} {",
+                &values("1", "Term", "")
+            ),
+            "awk '{print $1}' 1 {'Term'} {"
+        );
+    }
+
+    #[test]
+    fn escapes_single_quotes() {
+        assert_eq!(shell_escape("it's"), r"'it'\''s'");
+        assert_eq!(shell_escape("''"), r"''\'''\'''");
+    }
+
+    // What bash gets back after parsing has to be the original text
+    #[test]
+    fn escaped_titles_reach_the_command_intact() {
+        let title = "it's $HOME `id` \"quoted\" \\ ; {class}";
+
+        let output = Command::new("bash")
+            .args([
+                "-c",
+                &substitute("printf %s {title}", &values("1", "", title)),
+            ])
+            .output()
+            .unwrap();
+
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), title);
+    }
+}
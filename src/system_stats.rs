@@ -0,0 +1,92 @@
+use std::fs;
+
+#[derive(Default)]
+pub struct SystemStats {
+    cpu_usage: Option<f64>,
+    memory_usage: Option<f64>,
+
+    // CPU usage is calculated from the difference between two consecutive readings
+    previous_cpu_times: Option<CpuTimes>,
+}
+
+#[derive(Clone, Copy)]
+struct CpuTimes {
+    idle: u64,
+    total: u64,
+}
+
+impl SystemStats {
+    pub fn new() -> Self {
+        let mut this = Self::default();
+        this.update();
+        this
+    }
+
+    pub fn update(&mut self) {
+        let cpu_times = read_cpu_times();
+
+        self.cpu_usage = match (self.previous_cpu_times, cpu_times) {
+            (Some(previous), Some(current)) if current.total > previous.total => {
+                let idle = current.idle.saturating_sub(previous.idle) as f64;
+                let total = (current.total - previous.total) as f64;
+                Some(1.0 - idle / total)
+            }
+            _ => None,
+        };
+
+        self.previous_cpu_times = cpu_times;
+        self.memory_usage = read_memory_usage();
+    }
+
+    pub fn cpu_usage(&self) -> Option<f64> {
+        self.cpu_usage
+    }
+
+    pub fn memory_usage(&self) -> Option<f64> {
+        self.memory_usage
+    }
+}
+
+fn read_cpu_times() -> Option<CpuTimes> {
+    let stat = fs::read_to_string("/proc/stat").ok()?;
+
+    // cpu  user nice system idle iowait irq softirq steal guest guest_nice
+    let times = stat
+        .lines()
+        .next()?
+        .strip_prefix("cpu ")?
+        .split_whitespace()
+        .map(|time| time.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(CpuTimes {
+        idle: times.get(3)? + times.get(4).unwrap_or(&0),
+        total: times.iter().sum(),
+    })
+}
+
+fn read_memory_usage() -> Option<f64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+
+    let mut total = None;
+    let mut available = None;
+
+    for line in meminfo.lines() {
+        let Some((key, value)) = line.split_once(':')
+        else {
+            continue;
+        };
+
+        let value = value.trim().trim_end_matches(" kB").parse::<u64>().ok();
+
+        match key {
+            "MemTotal" => total = value,
+            "MemAvailable" => available = value,
+            _ => {}
+        }
+    }
+
+    let total = total.filter(|total| *total != 0)?;
+
+    Some(1.0 - available? as f64 / total as f64)
+}
@@ -0,0 +1,152 @@
+#[derive(Clone, Copy)]
+pub struct Color {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub alpha: f64,
+}
+
+impl Color {
+    pub const fn rgb(red: f64, green: f64, blue: f64) -> Self {
+        Self::rgba(red, green, blue, 1.0)
+    }
+
+    pub const fn rgba(red: f64, green: f64, blue: f64, alpha: f64) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    pub fn set_source(self, context: &cairo::Context) {
+        context.set_source_rgba(self.red, self.green, self.blue, self.alpha);
+    }
+
+    pub fn add_stop(self, gradient: &cairo::LinearGradient, offset: f64) {
+        gradient.add_color_stop_rgba(offset, self.red, self.green, self.blue, self.alpha);
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum DecorationStyle {
+    // The raised bevel drawn with the frame colors
+    Bevel3D,
+
+    // A single line around the container, the rest of the border is filled with the frame color
+    Flat { color: Color },
+
+    // No border at all, only the titlebar is drawn
+    None,
+}
+
+pub struct Theme {
+    // Titlebar gradients go from the left edge to the right one
+    pub active_titlebar: (Color, Color),
+    pub inactive_titlebar: (Color, Color),
+    pub titlebar_text: Color,
+
+    // The frame is a raised bevel: outer and inner lines on the top left and bottom right sides.
+    // Only 'frame' is used with the flat style, none of them without decorations
+    pub decoration_style: DecorationStyle,
+    pub frame: Color,
+    pub frame_outer_light: Color,
+    pub frame_inner_light: Color,
+    pub frame_inner_shadow: Color,
+    pub frame_outer_shadow: Color,
+
+    // Replaces 'frame' on the client being resized with the keyboard
+    pub resize_mode_frame: Color,
+
+    // Replaces 'frame' for a moment after a client gets raised, if config::RAISE_FLASH is set
+    pub raise_flash_frame: Color,
+
+    // Drawn over the outer edge of the active client's frame, so it stands out more than
+    // by its titlebar. Not drawn without decorations
+    pub active_frame_accent: Option<Color>,
+
+    // Gradient painted on the root window from the top edge to the bottom one,
+    // see config::PAINT_ROOT_BACKGROUND
+    pub root_background: (Color, Color),
+
+    // Dashed rectangle showing where a window goes when dragging it with an outline
+    pub drag_outline: Color,
+
+    // Panel backgrounds are painted with their alpha as is
+    pub top_panel_background: Color,
+    pub bottom_panel_background: Color,
+
+    // Each workspace label is the class or number between the prefix and suffix,
+    // consecutive labels are separated by the separator. E.g. "{", "}" and " | "
+    pub workspace_label_prefix: &'static str,
+    pub workspace_label_suffix: &'static str,
+    pub workspace_label_separator: &'static str,
+
+    // Workspace labels and tasklist entries
+    pub active_text: Color,
+    pub inactive_text: Color,
+    pub urgent_text: Color,
+    pub active_entry_background: Color,
+    pub hovered_entry_background: Color,
+
+    pub clock: Color,
+}
+
+pub const WIN9X: Theme = Theme {
+    active_titlebar: (Color::rgb(0.0, 0.5, 0.5), Color::rgb(0.0, 0.67, 0.67)),
+    inactive_titlebar: (Color::rgb(0.63, 0.55, 0.4), Color::rgb(0.83, 0.8, 0.73)),
+    titlebar_text: Color::rgb(1.0, 1.0, 1.0),
+    decoration_style: DecorationStyle::Bevel3D,
+    frame: Color::rgb(0.75, 0.75, 0.75),
+    frame_outer_light: Color::rgb(0.87, 0.87, 0.87),
+    frame_inner_light: Color::rgb(1.0, 1.0, 1.0),
+    frame_inner_shadow: Color::rgb(0.5, 0.5, 0.5),
+    frame_outer_shadow: Color::rgb(0.0, 0.0, 0.0),
+    resize_mode_frame: Color::rgb(0.85, 0.55, 0.2),
+    raise_flash_frame: Color::rgb(1.0, 1.0, 1.0),
+    active_frame_accent: None,
+    root_background: (Color::rgb(0.0, 0.5, 0.5), Color::rgb(0.0, 0.5, 0.5)),
+    drag_outline: Color::rgb(1.0, 1.0, 1.0),
+    top_panel_background: Color::rgba(0.0, 0.0, 0.0, 0.8),
+    bottom_panel_background: Color::rgb(0.0, 0.0, 0.0),
+    workspace_label_prefix: "[",
+    workspace_label_suffix: "]",
+    workspace_label_separator: "  ",
+    active_text: Color::rgb(0.58, 0.61, 0.64),
+    inactive_text: Color::rgb(0.27, 0.27, 0.27),
+    urgent_text: Color::rgb(0.85, 0.55, 0.2),
+    active_entry_background: Color::rgb(0.14, 0.14, 0.14),
+    hovered_entry_background: Color::rgb(0.08, 0.08, 0.08),
+    clock: Color::rgb(0.58, 0.61, 0.64),
+};
+
+#[allow(dead_code)]
+pub const VAPORWAVE: Theme = Theme {
+    active_titlebar: (Color::rgb(0.45, 0.2, 0.6), Color::rgb(0.95, 0.45, 0.75)),
+    inactive_titlebar: (Color::rgb(0.3, 0.3, 0.45), Color::rgb(0.55, 0.6, 0.75)),
+    titlebar_text: Color::rgb(1.0, 1.0, 1.0),
+    decoration_style: DecorationStyle::Bevel3D,
+    frame: Color::rgb(0.78, 0.74, 0.85),
+    frame_outer_light: Color::rgb(0.9, 0.87, 0.95),
+    frame_inner_light: Color::rgb(1.0, 1.0, 1.0),
+    frame_inner_shadow: Color::rgb(0.5, 0.45, 0.6),
+    frame_outer_shadow: Color::rgb(0.1, 0.05, 0.15),
+    resize_mode_frame: Color::rgb(0.55, 0.95, 0.95),
+    raise_flash_frame: Color::rgb(0.95, 0.45, 0.75),
+    active_frame_accent: Some(Color::rgb(0.95, 0.45, 0.75)),
+    root_background: (Color::rgb(0.1, 0.02, 0.25), Color::rgb(0.95, 0.45, 0.75)),
+    drag_outline: Color::rgb(0.55, 0.95, 0.95),
+    top_panel_background: Color::rgba(0.1, 0.02, 0.15, 0.8),
+    bottom_panel_background: Color::rgb(0.1, 0.02, 0.15),
+    workspace_label_prefix: "[",
+    workspace_label_suffix: "]",
+    workspace_label_separator: "  ",
+    active_text: Color::rgb(0.55, 0.95, 0.95),
+    inactive_text: Color::rgb(0.4, 0.3, 0.5),
+    urgent_text: Color::rgb(1.0, 0.5, 0.7),
+    active_entry_background: Color::rgb(0.22, 0.1, 0.3),
+    hovered_entry_background: Color::rgb(0.16, 0.06, 0.22),
+    clock: Color::rgb(0.95, 0.45, 0.75),
+};
@@ -1,4 +1,13 @@
 use crate::app::App;
+use crate::client::ClientChange;
+use crate::client::ClientChangeKind;
+use crate::config;
+use crate::config::TopPanelSection;
+use crate::config::WorkspaceStyle;
+use crate::system_stats::SystemStats;
+use crate::volume::Volume;
+use crate::volume::VolumeChange;
+use crate::volume::VolumeMonitor;
 use chrono::DateTime;
 use chrono::Datelike;
 use chrono::Local;
@@ -7,32 +16,69 @@ use chrono::Weekday;
 use std::cell::Cell;
 use std::cell::RefCell;
 use std::ops::RangeInclusive;
+use std::os::fd::BorrowedFd;
 use std::rc::Rc;
 use x11rb::protocol::xproto::ButtonIndex;
 use x11rb::protocol::xproto::CreateWindowAux;
 use x11rb::protocol::xproto::EventMask;
+use x11rb::protocol::xproto::NotifyMode;
 use x11rb::protocol::Event;
 
 pub const PANEL_HEIGHT: u16 = 28;
 
+pub const RESERVED_HEIGHT: u16 = if config::TOP_PANEL_AUTO_HIDE {
+    0
+}
+else {
+    PANEL_HEIGHT
+};
+
+const BADGE_RADIUS: f64 = 6.0;
+
+const DOT_RADIUS: f64 = 8.0;
+const ACTIVE_DOT_RADIUS: f64 = 10.0;
+
+// Distance between centers of neighbouring dots
+const DOT_SPACING: f64 = 20.0;
+
+// Distance from the screen edges to the left and right sections
+const MARGIN_LEFT: f64 = 10.0;
+const MARGIN_RIGHT: f64 = 12.0;
+
+// Horizontal gap between neighbouring sections
+const SECTION_SPACING: f64 = 30.0;
+
 pub struct TopPanel {
     app: Rc<App>,
     id: u32,
+
+    // A 1px high strip along the screen edge which reveals the panel when the pointer enters it.
+    // Only exists when the panel is auto-hidden
+    trigger_id: Option<u32>,
+
     surface: cairo::XCBSurface,
+    context: cairo::Context,
     need_redraw: Cell<bool>,
     time: Cell<DateTime<Local>>,
+    system_stats: RefCell<SystemStats>,
+    volume: Cell<Option<Volume>>,
+    volume_monitor: VolumeMonitor,
 
     // Information about where (on x coordinate) clickable text is drawn
-    // We calculate it as we draw and use when handling MotionNotify or ButtonPress
+    // We calculate it as we draw and use when handling MotionNotify or ButtonPress.
+    // Coordinates are relative to the panel window, so they're compared with event_x, not root_x
     layout: RefCell<Vec<RangeInclusive<u16>>>,
 
+    // Same as 'layout' but for the volume indicator, which reacts to scrolling instead of clicks
+    volume_layout: RefCell<Option<RangeInclusive<u16>>>,
+
     // When we receive MotionNotify events, we have to defer their handling
     // So we keep info about the event happening, also with its x coordinate
     // Also note that we only care about the latest MotionNotify event
     deferred_motion_notify_x: Cell<Option<u16>>,
 
-    // Same as for 'deferred_motion_notify_x'
-    deferred_click_x: Cell<Option<u16>>,
+    // Same as for 'deferred_motion_notify_x', along with the button
+    deferred_click: Cell<Option<(u16, ButtonIndex)>>,
 }
 
 impl TopPanel {
@@ -45,10 +91,31 @@ impl TopPanel {
             0,
             app.api().screen_width(),
             PANEL_HEIGHT,
-            CreateWindowAux::new().event_mask(EventMask::BUTTON_PRESS | EventMask::POINTER_MOTION),
+            CreateWindowAux::new().event_mask(
+                EventMask::BUTTON_PRESS | EventMask::POINTER_MOTION | EventMask::LEAVE_WINDOW,
+            ),
         );
 
-        app.api().map_window(id);
+        let trigger_id = config::TOP_PANEL_AUTO_HIDE.then(|| {
+            let trigger_id = app.api().generate_id();
+
+            app.api().create_input_only_window(
+                trigger_id,
+                0,
+                0,
+                app.api().screen_width(),
+                1,
+                CreateWindowAux::new().event_mask(EventMask::ENTER_WINDOW),
+            );
+
+            app.api().map_window(trigger_id);
+
+            trigger_id
+        });
+
+        if trigger_id.is_none() {
+            app.api().map_window(id);
+        }
 
         let surface =
             app.api()
@@ -57,17 +124,37 @@ impl TopPanel {
         Self {
             app,
             id,
+            trigger_id,
+            context: cairo::Context::new(&surface).unwrap(),
             surface,
             need_redraw: Cell::new(true),
             time: Cell::new(Local::now()),
+            system_stats: RefCell::new(SystemStats::new()),
+            volume: Cell::new(None),
+            volume_monitor: VolumeMonitor::new(config::TOP_PANEL_SHOW_VOLUME),
             layout: RefCell::new(Vec::new()),
+            volume_layout: RefCell::new(None),
             deferred_motion_notify_x: Cell::new(None),
-            deferred_click_x: Cell::new(None),
+            deferred_click: Cell::new(None),
         }
     }
 
-    pub fn id(&self) -> u32 {
-        self.id
+    pub fn raise(&self) {
+        if let Some(trigger_id) = self.trigger_id {
+            self.app.api().raise_window(trigger_id);
+        }
+
+        self.app.api().raise_window(self.id);
+    }
+
+    fn reveal(&self) {
+        self.app.api().map_window(self.id);
+        self.app.api().raise_window(self.id);
+        self.need_redraw.set(true);
+    }
+
+    fn hide(&self) {
+        self.app.api().unmap_window(self.id);
     }
 
     fn redraw(&self) {
@@ -75,23 +162,145 @@ impl TopPanel {
             return;
         }
 
-        let context = cairo::Context::new(&self.surface).unwrap();
+        let context = &self.context;
+
+        // The context is reused, so we have to reset the state left from the previous redraw
+        context.reset_clip();
+        context.identity_matrix();
+        context.new_path();
 
         context.set_line_width(1.0);
         context.set_antialias(cairo::Antialias::None);
 
         context.set_operator(cairo::Operator::Source);
-        context.set_source_rgba(0.0, 0.0, 0.0, 0.8);
+        config::THEME.top_panel_background.set_source(context);
         context.paint().unwrap();
         context.set_operator(cairo::Operator::Over);
 
-        self.draw_workspace_labels(&context);
-        self.draw_clock(&context);
+        self.layout.borrow_mut().clear();
+        *self.volume_layout.borrow_mut() = None;
+
+        let screen_width = self.app.api().screen_width() as f64;
+
+        let left_end =
+            self.draw_sections(context, config::TOP_PANEL_SECTIONS_LEFT, |_| MARGIN_LEFT);
+
+        self.draw_sections(context, config::TOP_PANEL_SECTIONS_CENTER, |width| {
+            (screen_width - width) / 2.0
+        });
+
+        self.draw_sections(context, config::TOP_PANEL_SECTIONS_RIGHT, |width| {
+            screen_width - MARGIN_RIGHT - width
+        });
+
+        if let Some(separator) = config::TOP_PANEL_SECTION_SEPARATOR {
+            if left_end > MARGIN_LEFT {
+                self.draw_separator(context, separator, left_end + SECTION_SPACING / 2.0);
+            }
+        }
+
+        // Keys don't do what they normally do while the WM holds the keyboard, so it has to be visible
+        if let Some(label) = self.app.wm().input_mode_label() {
+            set_text_font(context);
+            config::THEME.urgent_text.set_source(context);
+
+            context.save().unwrap();
+            context.translate((left_end + SECTION_SPACING).round(), 0.0);
+            draw_text(context, label);
+            context.restore().unwrap();
+        }
 
         self.surface.flush();
     }
 
-    fn draw_workspace_labels(&self, context: &cairo::Context) {
+    // Draws the sections in a row. 'get_x' gets the width of the row and returns its left edge.
+    // Returns the right edge
+    fn draw_sections(
+        &self,
+        context: &cairo::Context,
+        sections: &[TopPanelSection],
+        get_x: impl FnOnce(f64) -> f64,
+    ) -> f64 {
+        // Sections are drawn at x = 0 into groups first, we need to know their widths to place them
+        let drawn_sections = sections
+            .iter()
+            .filter_map(|section| {
+                let layout_start = self.layout.borrow().len();
+
+                context.push_group();
+                let width = self.draw_section(context, *section);
+                let pattern = context.pop_group().unwrap();
+
+                let layout_end = self.layout.borrow().len();
+
+                (width > 0.0).then_some((*section, pattern, width, layout_start..layout_end))
+            })
+            .collect::<Vec<_>>();
+
+        let width = drawn_sections
+            .iter()
+            .map(|(_, _, width, _)| width + SECTION_SPACING)
+            .sum::<f64>()
+            - SECTION_SPACING;
+
+        let mut x = get_x(width.max(0.0)).round();
+
+        for (section, pattern, width, layout_range) in drawn_sections {
+            context.save().unwrap();
+            context.translate(x, 0.0);
+            context.set_source(&pattern).unwrap();
+            context.paint().unwrap();
+            context.restore().unwrap();
+
+            // Layouts are recorded relative to the section too
+            let shift =
+                |range: &RangeInclusive<u16>| (range.start() + x as u16)..=(range.end() + x as u16);
+
+            for range in &mut self.layout.borrow_mut()[layout_range] {
+                *range = shift(range);
+            }
+
+            if section == TopPanelSection::Volume {
+                let mut volume_layout = self.volume_layout.borrow_mut();
+                *volume_layout = volume_layout.as_ref().map(shift);
+            }
+
+            x += (width + SECTION_SPACING).round();
+        }
+
+        x - SECTION_SPACING
+    }
+
+    // Draws the section starting at x = 0 and returns its width, 0 if there's nothing to show
+    fn draw_section(&self, context: &cairo::Context, section: TopPanelSection) -> f64 {
+        match section {
+            TopPanelSection::Workspaces => self.draw_workspace_labels(context),
+            TopPanelSection::SystemStats => self.draw_system_stats(context),
+            TopPanelSection::Volume => self.draw_volume(context),
+            TopPanelSection::Clock => self.draw_clock(context),
+        }
+    }
+
+    fn draw_separator(&self, context: &cairo::Context, separator: char, x: f64) {
+        set_text_font(context);
+        config::THEME.inactive_text.set_source(context);
+
+        let text = separator.to_string();
+        let extents = context.text_extents(&text).unwrap();
+
+        context.move_to(
+            (x - extents.width() / 2.0).round(),
+            PANEL_HEIGHT as f64 / 2.0 - extents.y_bearing() / 2.25,
+        );
+
+        context.show_text(&text).unwrap();
+    }
+
+    fn draw_workspace_labels(&self, context: &cairo::Context) -> f64 {
+        if let WorkspaceStyle::Dots = config::TOP_PANEL_WORKSPACE_STYLE {
+            return self.draw_workspace_dots(context);
+        }
+
         let workspaces = self.app.wm().workspaces();
         let active_workspace_index = self.app.wm().active_workspace_index();
 
@@ -104,55 +313,141 @@ impl TopPanel {
         context.set_font_size(18.0);
 
         let mut layout = self.layout.borrow_mut();
-        layout.clear();
-
-        let mut offset = 10;
+        let mut offset = 0;
 
         for (index, workspace) in workspaces.iter().enumerate() {
-            let label = match workspace.tasklist().first() {
-                Some(client) => match client.class().as_deref() {
-                    Some(class) => format!("[{}]", class.to_uppercase()),
-                    None => format!("[{}]", index + 1),
+            let name = match (
+                &config::TOP_PANEL_WORKSPACE_STYLE,
+                workspace.tasklist().first(),
+            ) {
+                (WorkspaceStyle::ClassLabel, Some(client)) => match client.class().as_deref() {
+                    Some(class) => class.to_uppercase(),
+                    None => (index + 1).to_string(),
                 },
-                None => format!("[{}]", index + 1),
+                _ => (index + 1).to_string(),
             };
 
+            let label = format!(
+                "{}{}{}",
+                config::THEME.workspace_label_prefix,
+                name,
+                config::THEME.workspace_label_suffix
+            );
+
             let extents = context.text_extents(&label).unwrap();
+            let baseline = (PANEL_HEIGHT as f64 + extents.height() / 1.5) / 2.0;
 
-            context.move_to(
-                offset as _,
-                (PANEL_HEIGHT as f64 + extents.height() / 1.5) / 2.0,
-            );
+            // Part of neither label, clicking it doesn't do anything
+            if index > 0 {
+                let separator = config::THEME.workspace_label_separator;
+
+                context.move_to(offset as _, baseline);
+                config::THEME.inactive_text.set_source(context);
+                context.show_text(separator).unwrap();
+
+                offset += context.text_extents(separator).unwrap().x_advance().round() as u16;
+            }
+
+            context.move_to(offset as _, baseline);
 
             if index == active_workspace_index {
-                context.set_source_rgb(0.58, 0.61, 0.64);
+                config::THEME.active_text.set_source(context);
             }
             else {
-                context.set_source_rgb(0.27, 0.27, 0.27);
+                config::THEME.inactive_text.set_source(context);
             }
 
             context.show_text(&label).unwrap();
 
             let start = offset;
             let width = extents.width().round() as u16;
-            let end = start + width;
+            let mut end = start + width;
+
+            let window_count = workspace.stack().len();
+
+            if window_count > 0 {
+                let badge_x = end as f64 + 2.0;
+                let badge_y = (baseline + extents.y_bearing()).max(BADGE_RADIUS);
+
+                self.draw_badge(context, badge_x, badge_y, window_count);
+
+                end = (badge_x + BADGE_RADIUS).ceil() as u16;
+                context.set_font_size(18.0);
+            }
 
             layout.push(start..=end);
 
-            offset = end + 30;
+            offset = end;
         }
+
+        layout.last().map_or(0.0, |range| *range.end() as _)
     }
 
-    fn draw_clock(&self, context: &cairo::Context) {
-        context.set_font_size(16.0);
+    fn draw_workspace_dots(&self, context: &cairo::Context) -> f64 {
+        let active_workspace_index = self.app.wm().active_workspace_index();
 
-        context.select_font_face(
-            "PxPlus ToshibaTxL2 8x16",
-            cairo::FontSlant::Normal,
-            cairo::FontWeight::Bold,
+        let mut layout = self.layout.borrow_mut();
+
+        let y = PANEL_HEIGHT as f64 / 2.0;
+        let workspaces = self.app.wm().workspaces();
+
+        for (index, workspace) in workspaces.iter().enumerate() {
+            let x = ACTIVE_DOT_RADIUS + index as f64 * DOT_SPACING;
+
+            let radius = if index == active_workspace_index {
+                ACTIVE_DOT_RADIUS
+            }
+            else {
+                DOT_RADIUS
+            };
+
+            if index == active_workspace_index {
+                config::THEME.active_titlebar.1.set_source(context);
+            }
+            else {
+                config::THEME.active_text.set_source(context);
+            }
+
+            context.new_sub_path();
+            context.arc(x, y, radius, 0.0, std::f64::consts::TAU);
+
+            if workspace.stack().is_empty() {
+                context.stroke().unwrap();
+            }
+            else {
+                context.fill().unwrap();
+            }
+
+            layout.push((x - radius).floor() as u16..=(x + radius).ceil() as u16);
+        }
+
+        ACTIVE_DOT_RADIUS * 2.0 + (workspaces.len() - 1) as f64 * DOT_SPACING
+    }
+
+    fn draw_badge(&self, context: &cairo::Context, x: f64, y: f64, count: usize) {
+        config::THEME.active_titlebar.0.set_source(context);
+        context.arc(x, y, BADGE_RADIUS, 0.0, std::f64::consts::TAU);
+        context.fill().unwrap();
+
+        // There's no room for more digits
+        let text = count.min(99).to_string();
+
+        context.set_font_size(10.0);
+        config::THEME.titlebar_text.set_source(context);
+
+        let extents = context.text_extents(&text).unwrap();
+
+        context.move_to(
+            x - extents.x_bearing() - extents.width() / 2.0,
+            y - extents.y_bearing() - extents.height() / 2.0,
         );
 
-        context.set_source_rgb(0.58, 0.61, 0.64);
+        context.show_text(&text).unwrap();
+    }
+
+    fn draw_clock(&self, context: &cairo::Context) -> f64 {
+        set_text_font(context);
+        config::THEME.clock.set_source(context);
 
         let time = self.time.get();
 
@@ -176,14 +471,94 @@ impl TopPanel {
             time.year()
         );
 
-        let extents = context.text_extents(&text).unwrap();
+        draw_text(context, &text)
+    }
 
-        context.move_to(
-            (self.app.api().screen_width() - 12) as f64 - extents.width(),
-            PANEL_HEIGHT as f64 / 2.0 - extents.y_bearing() / 2.25,
-        );
+    fn draw_system_stats(&self, context: &cairo::Context) -> f64 {
+        let system_stats = self.system_stats.borrow();
+        let mut parts = Vec::new();
 
-        context.show_text(&text).unwrap();
+        if config::TOP_PANEL_SHOW_CPU_USAGE {
+            if let Some(usage) = system_stats.cpu_usage() {
+                parts.push(format!("CPU {:>3.0}%", usage * 100.0));
+            }
+        }
+
+        if config::TOP_PANEL_SHOW_MEMORY_USAGE {
+            if let Some(usage) = system_stats.memory_usage() {
+                parts.push(format!("MEM {:>3.0}%", usage * 100.0));
+            }
+        }
+
+        if parts.is_empty() {
+            return 0.0;
+        }
+
+        set_text_font(context);
+        config::THEME.clock.set_source(context);
+
+        draw_text(context, &parts.join(" // "))
+    }
+
+    fn draw_volume(&self, context: &cairo::Context) -> f64 {
+        let Some(volume) = self.volume.get()
+        else {
+            return 0.0;
+        };
+
+        let text = if volume.muted {
+            "VOL MUTE".to_owned()
+        }
+        else {
+            format!("VOL {:>3}%", volume.level)
+        };
+
+        set_text_font(context);
+        config::THEME.clock.set_source(context);
+
+        let width = draw_text(context, &text);
+        *self.volume_layout.borrow_mut() = Some(0..=width.round() as u16);
+
+        width
+    }
+
+    // Readable when the volume has changed
+    pub fn volume_fd(&self) -> BorrowedFd<'_> {
+        self.volume_monitor.fd()
+    }
+
+    // Also for the volume keys, so the indicator follows right away
+    pub fn change_volume(&self, change: VolumeChange) {
+        self.volume_monitor.change_volume(change);
+    }
+
+    fn update_volume(&self) {
+        let Some(volume) = self.volume_monitor.take_update()
+        else {
+            return;
+        };
+
+        if self.volume.get() != volume {
+            self.volume.set(volume);
+            self.need_redraw.set(true);
+        }
+    }
+
+    fn handle_scroll(&self, mouse_x: u16, raise: bool) {
+        let on_volume = self
+            .volume_layout
+            .borrow()
+            .as_ref()
+            .is_some_and(|range| range.contains(&mouse_x));
+
+        if on_volume {
+            self.change_volume(if raise {
+                VolumeChange::Raise
+            }
+            else {
+                VolumeChange::Lower
+            });
+        }
     }
 
     fn set_cursor(&self, mouse_x: u16) {
@@ -203,22 +578,38 @@ impl TopPanel {
         self.app.api().set_window_cursor(self.id, cursor);
     }
 
-    fn handle_click(&self, mouse_x: u16) {
+    // The left button switches to the workspace, the middle one sends the active client there
+    // and the right one does both
+    fn handle_click(&self, mouse_x: u16, button: ButtonIndex) {
         let workspace_index = self
             .layout
             .borrow()
             .iter()
             .position(|range| range.contains(&mouse_x));
 
-        if let Some(index) = workspace_index {
-            self.app.wm().change_active_workspace(index);
+        let Some(index) = workspace_index
+        else {
+            return;
+        };
+
+        match button {
+            ButtonIndex::M2 => self.app.wm().move_active_client_to_workspace(index),
+            ButtonIndex::M3 => self
+                .app
+                .wm()
+                .move_active_client_to_workspace_and_follow(index),
+            _ => self.app.wm().change_active_workspace(index),
         }
     }
 
     pub fn request_redraw(&self) {
+        self.update_volume();
+
         let time = Local::now();
 
-        if self.time.get() != time {
+        // The stats are sampled once a second and the clock only shows minutes
+        if self.time.get().timestamp() != time.timestamp() {
+            self.system_stats.borrow_mut().update();
             self.time.set(time);
             self.need_redraw.set(true);
         }
@@ -229,8 +620,8 @@ impl TopPanel {
             self.set_cursor(mouse_x);
         }
 
-        if let Some(mouse_x) = self.deferred_click_x.take() {
-            self.handle_click(mouse_x);
+        if let Some((mouse_x, button)) = self.deferred_click.take() {
+            self.handle_click(mouse_x, button);
         }
 
         // After we have handled the events we might need to redraw again
@@ -248,15 +639,65 @@ impl TopPanel {
                 }
             }
             Event::ButtonPress(event) => {
-                if event.event == self.id && ButtonIndex::from(event.detail) == ButtonIndex::M1 {
-                    self.deferred_click_x.set(Some(event.event_x as _));
+                if event.event == self.id {
+                    match ButtonIndex::from(event.detail) {
+                        button @ (ButtonIndex::M1 | ButtonIndex::M2 | ButtonIndex::M3) => {
+                            self.deferred_click.set(Some((event.event_x as _, button)))
+                        }
+                        ButtonIndex::M4 => self.handle_scroll(event.event_x as _, true),
+                        ButtonIndex::M5 => self.handle_scroll(event.event_x as _, false),
+                        _ => {}
+                    }
+
+                    // The panel is never meant to take the focus from the active client
+                    self.app.wm().refocus();
+                }
+            }
+            Event::EnterNotify(event) => {
+                if Some(event.event) == self.trigger_id {
+                    self.reveal();
                 }
             }
+            Event::LeaveNotify(event)
+                if event.event == self.id
+                    && event.mode == NotifyMode::NORMAL
+                    && self.trigger_id.is_some() =>
+            {
+                self.hide()
+            }
             _ => {}
         }
     }
 
+    // Workspace labels show classes
+    pub fn handle_client_change(&self, change: &ClientChange) {
+        if change.kind == ClientChangeKind::Class {
+            self.notify();
+        }
+    }
+
     pub fn notify(&self) {
         self.need_redraw.set(true);
     }
 }
+
+// Font of everything but workspace labels
+fn set_text_font(context: &cairo::Context) {
+    context.set_font_size(16.0);
+
+    context.select_font_face(
+        "PxPlus ToshibaTxL2 8x16",
+        cairo::FontSlant::Normal,
+        cairo::FontWeight::Bold,
+    );
+}
+
+// Draws the text at x = 0, vertically centered, and returns its width
+fn draw_text(context: &cairo::Context, text: &str) -> f64 {
+    let extents = context.text_extents(text).unwrap();
+
+    context.move_to(0.0, PANEL_HEIGHT as f64 / 2.0 - extents.y_bearing() / 2.25);
+    context.show_text(text).unwrap();
+
+    extents.width()
+}
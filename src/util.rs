@@ -1,5 +1,7 @@
+// Both return 0 for an empty slice, so callers don't have to check for it. An index past
+// the end, e.g. one which was taken before the slice shrank, counts as the last one
 pub fn cycle_next<T>(items: &[T], current: usize) -> usize {
-    if current == items.len() - 1 {
+    if current >= items.len().saturating_sub(1) {
         0
     }
     else {
@@ -8,10 +10,287 @@ pub fn cycle_next<T>(items: &[T], current: usize) -> usize {
 }
 
 pub fn cycle_previous<T>(items: &[T], current: usize) -> usize {
-    if current == 0 {
-        items.len() - 1
+    let last = items.len().saturating_sub(1);
+
+    match current.min(last) {
+        0 => last,
+        current => current - 1,
+    }
+}
+
+// X timestamps are milliseconds which wrap around every ~49.7 days. Ordering by this instead of by
+// the timestamps themselves keeps times from before the wrap older than the ones after it
+pub fn milliseconds_since(now: u32, time: u32) -> u32 {
+    now.wrapping_sub(time)
+}
+
+#[derive(Clone, Copy)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+// Rectangles are (x, y, width, height).
+// Returns index of the rectangle whose center is the closest one to the center of 'from'
+// among those lying in 'direction' from it. Ties are treated as if nothing was found
+pub fn find_closest_in_direction(
+    from: (i16, i16, u16, u16),
+    rectangles: &[(i16, i16, u16, u16)],
+    direction: Direction,
+) -> Option<usize> {
+    let center = |(x, y, width, height): (i16, i16, u16, u16)| {
+        (
+            x as f64 + width as f64 / 2.0,
+            y as f64 + height as f64 / 2.0,
+        )
+    };
+
+    let (from_x, from_y) = center(from);
+    let mut result = None;
+    let mut min_distance = f64::INFINITY;
+    let mut tie = false;
+
+    for (index, rectangle) in rectangles.iter().enumerate() {
+        let (x, y) = center(*rectangle);
+
+        let in_direction = match direction {
+            Direction::Left => x < from_x,
+            Direction::Right => x > from_x,
+            Direction::Up => y < from_y,
+            Direction::Down => y > from_y,
+        };
+
+        if !in_direction {
+            continue;
+        }
+
+        let distance = (x - from_x).hypot(y - from_y);
+
+        if distance < min_distance {
+            result = Some(index);
+            min_distance = distance;
+            tie = false;
+        }
+        else if distance == min_distance {
+            tie = true;
+        }
+    }
+
+    if tie {
+        None
     }
     else {
-        current - 1
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_empty_slice_returns_0() {
+        let items: [(); 0] = [];
+
+        assert_eq!(cycle_next(&items, 0), 0);
+        assert_eq!(cycle_previous(&items, 0), 0);
+        assert_eq!(cycle_next(&items, 5), 0);
+        assert_eq!(cycle_previous(&items, 5), 0);
+    }
+
+    #[test]
+    fn cycle_single_element_stays() {
+        let items = [1];
+
+        assert_eq!(cycle_next(&items, 0), 0);
+        assert_eq!(cycle_previous(&items, 0), 0);
+    }
+
+    #[test]
+    fn cycle_wraps_around_both_ends() {
+        let items = [1, 2, 3];
+
+        let forward = [0, 1, 2, 0, 1]
+            .windows(2)
+            .all(|pair| cycle_next(&items, pair[0]) == pair[1]);
+
+        let backward = [2, 1, 0, 2, 1]
+            .windows(2)
+            .all(|pair| cycle_previous(&items, pair[0]) == pair[1]);
+
+        assert!(forward);
+        assert!(backward);
+    }
+
+    #[test]
+    fn cycle_boundary_indices() {
+        let items = [1, 2, 3, 4];
+
+        assert_eq!(cycle_next(&items, 0), 1);
+        assert_eq!(cycle_previous(&items, 0), 3);
+        assert_eq!(cycle_next(&items, 3), 0);
+        assert_eq!(cycle_previous(&items, 3), 2);
+    }
+
+    #[test]
+    fn cycle_out_of_range_index_counts_as_last() {
+        let items = [1, 2, 3];
+
+        assert_eq!(cycle_next(&items, 10), 0);
+        assert_eq!(cycle_previous(&items, 10), 1);
+        assert_eq!(cycle_next(&items, usize::MAX), 0);
+        assert_eq!(cycle_previous(&items, usize::MAX), 1);
+    }
+
+    #[test]
+    fn milliseconds_since_wraps_around() {
+        assert_eq!(milliseconds_since(1000, 400), 600);
+        assert_eq!(milliseconds_since(1000, 1000), 0);
+
+        // 'now' has wrapped, but 'time' hasn't yet
+        assert_eq!(milliseconds_since(100, u32::MAX - 99), 200);
+
+        // So a time from right before the wrap counts as older than one right after it
+        let now = 500;
+        assert!(milliseconds_since(now, u32::MAX - 10) > milliseconds_since(now, 5));
+    }
+
+    // Neighbours of a 100x100 rectangle at (100, 100), one on each side
+    const FROM: (i16, i16, u16, u16) = (100, 100, 100, 100);
+    const LEFT: (i16, i16, u16, u16) = (-50, 100, 100, 100);
+    const RIGHT: (i16, i16, u16, u16) = (250, 100, 100, 100);
+    const UP: (i16, i16, u16, u16) = (100, -50, 100, 100);
+    const DOWN: (i16, i16, u16, u16) = (100, 250, 100, 100);
+
+    #[test]
+    fn closest_in_each_direction() {
+        let rectangles = [LEFT, RIGHT, UP, DOWN];
+
+        assert_eq!(
+            find_closest_in_direction(FROM, &rectangles, Direction::Left),
+            Some(0)
+        );
+        assert_eq!(
+            find_closest_in_direction(FROM, &rectangles, Direction::Right),
+            Some(1)
+        );
+        assert_eq!(
+            find_closest_in_direction(FROM, &rectangles, Direction::Up),
+            Some(2)
+        );
+        assert_eq!(
+            find_closest_in_direction(FROM, &rectangles, Direction::Down),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn closest_picks_nearest_center() {
+        let far_right = (400, 100, 100, 100);
+        let rectangles = [far_right, RIGHT];
+
+        assert_eq!(
+            find_closest_in_direction(FROM, &rectangles, Direction::Right),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn closest_with_nothing_in_direction() {
+        assert_eq!(find_closest_in_direction(FROM, &[], Direction::Left), None);
+        assert_eq!(
+            find_closest_in_direction(FROM, &[RIGHT], Direction::Left),
+            None
+        );
+
+        // Same center, so it isn't in any direction
+        assert_eq!(
+            find_closest_in_direction(FROM, &[FROM], Direction::Right),
+            None
+        );
+    }
+
+    #[test]
+    fn closest_with_overlapping_rectangles() {
+        // Overlaps 'from', only the centers count
+        let overlapping = (150, 120, 100, 100);
+
+        assert_eq!(
+            find_closest_in_direction(FROM, &[overlapping, RIGHT], Direction::Right),
+            Some(0)
+        );
+        assert_eq!(
+            find_closest_in_direction(FROM, &[overlapping, RIGHT], Direction::Down),
+            Some(0)
+        );
+        assert_eq!(
+            find_closest_in_direction(FROM, &[overlapping], Direction::Left),
+            None
+        );
+    }
+
+    #[test]
+    fn closest_tie_returns_none() {
+        let above_right = (250, 50, 100, 100);
+        let below_right = (250, 150, 100, 100);
+
+        assert_eq!(
+            find_closest_in_direction(FROM, &[above_right, below_right], Direction::Right),
+            None
+        );
+
+        // A closer one breaks the tie
+        assert_eq!(
+            find_closest_in_direction(FROM, &[above_right, below_right, RIGHT], Direction::Right),
+            Some(2)
+        );
+    }
+}
+
+#[cfg(test)]
+mod properties {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn cycle_stays_in_bounds(len in 1..50_usize, current in any::<usize>()) {
+            let items = vec![(); len];
+
+            prop_assert!(cycle_next(&items, current) < len);
+            prop_assert!(cycle_previous(&items, current) < len);
+        }
+
+        #[test]
+        fn cycle_previous_undoes_cycle_next(len in 1..50_usize, seed in any::<usize>()) {
+            let items = vec![(); len];
+            let current = seed % len;
+
+            prop_assert_eq!(cycle_previous(&items, cycle_next(&items, current)), current);
+            prop_assert_eq!(cycle_next(&items, cycle_previous(&items, current)), current);
+        }
+
+        #[test]
+        fn cycle_next_len_times_returns_to_start(len in 1..50_usize, seed in any::<usize>()) {
+            let items = vec![(); len];
+            let start = seed % len;
+            let end = (0..len).fold(start, |index, _| cycle_next(&items, index));
+
+            prop_assert_eq!(end, start);
+        }
+
+        #[test]
+        fn cycle_single_element_returns_0(current in any::<usize>()) {
+            prop_assert_eq!(cycle_next(&[()], current), 0);
+            prop_assert_eq!(cycle_previous(&[()], current), 0);
+        }
+    }
+
+    #[test]
+    fn cycle_two_elements_alternates() {
+        assert_eq!(cycle_next(&[(), ()], 0), 1);
+        assert_eq!(cycle_next(&[(), ()], 1), 0);
     }
 }
@@ -0,0 +1,199 @@
+use crate::config;
+use crate::config::VolumeBackend;
+use std::io::Read;
+use std::io::Write;
+use std::os::fd::AsFd;
+use std::os::fd::BorrowedFd;
+use std::os::unix::net::UnixStream;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+// Volume can be changed externally so we have to poll it
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Volume {
+    pub level: u8,
+    pub muted: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum VolumeChange {
+    // By config::VOLUME_STEP
+    Raise,
+    Lower,
+
+    ToggleMute,
+}
+
+// Runs the volume commands on a thread of its own, so a slow or hung sound server can't stall
+// the event loop. If watched, the volume is read every POLL_INTERVAL and after changing it
+pub struct VolumeMonitor {
+    changes: Sender<VolumeChange>,
+
+    updates: Receiver<Option<Volume>>,
+
+    // Readable when there's an update, so waiting for events wakes up for it
+    wakeup: UnixStream,
+}
+
+impl VolumeMonitor {
+    // Without watching it only changes the volume, there are never any updates
+    pub fn new(watch: bool) -> Self {
+        let (changes, pending_changes) = mpsc::channel();
+        let (update_sender, updates) = mpsc::channel();
+        let (wakeup, mut wakeup_sender) = UnixStream::pair().unwrap();
+
+        wakeup.set_nonblocking(true).unwrap();
+
+        thread::spawn(move || {
+            let mut last_volume = None;
+
+            loop {
+                let volume = if watch { get_volume() } else { None };
+
+                if watch && last_volume != Some(volume) {
+                    last_volume = Some(volume);
+
+                    // The monitor is gone
+                    if update_sender.send(volume).is_err() || wakeup_sender.write_all(&[0]).is_err()
+                    {
+                        return;
+                    }
+                }
+
+                let change = if watch {
+                    pending_changes.recv_timeout(POLL_INTERVAL)
+                }
+                else {
+                    pending_changes
+                        .recv()
+                        .map_err(|_| RecvTimeoutError::Disconnected)
+                };
+
+                match change {
+                    Ok(change) => {
+                        change_volume(change);
+
+                        // Scrolling quickly queues up several changes, the volume is read back once for all
+                        for change in pending_changes.try_iter() {
+                            change_volume(change);
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        });
+
+        Self {
+            changes,
+            updates,
+            wakeup,
+        }
+    }
+
+    pub fn fd(&self) -> BorrowedFd<'_> {
+        self.wakeup.as_fd()
+    }
+
+    pub fn change_volume(&self, change: VolumeChange) {
+        let _ = self.changes.send(change);
+    }
+
+    // The latest volume read by the thread, if there's been a change since the last call.
+    // None inside means the volume couldn't be read
+    pub fn take_update(&self) -> Option<Option<Volume>> {
+        let mut buffer = [0; 64];
+        while matches!((&self.wakeup).read(&mut buffer), Ok(length) if length > 0) {}
+
+        self.updates.try_iter().last()
+    }
+}
+
+fn get_volume() -> Option<Volume> {
+    match config::VOLUME_BACKEND {
+        VolumeBackend::Amixer => {
+            // ...Playback 42000 [64%] [on]
+            let output = run(&["amixer", "get", "Master"])?;
+            let level = parse_first_percentage(&output)?;
+            let muted = output.contains("[off]");
+
+            Some(Volume { level, muted })
+        }
+        VolumeBackend::Pactl => {
+            // Volume: front-left: 42000 /  64% / -11.63 dB, ...
+            let output = run(&["pactl", "get-sink-volume", "@DEFAULT_SINK@"])?;
+            let level = parse_first_percentage(&output)?;
+
+            // Mute: no
+            let output = run(&["pactl", "get-sink-mute", "@DEFAULT_SINK@"])?;
+            let muted = output.trim() == "Mute: yes";
+
+            Some(Volume { level, muted })
+        }
+    }
+}
+
+fn change_volume(change: VolumeChange) {
+    let sign = match change {
+        VolumeChange::Raise => '+',
+        VolumeChange::Lower => '-',
+        VolumeChange::ToggleMute => {
+            let _ = match config::VOLUME_BACKEND {
+                VolumeBackend::Amixer => run(&["amixer", "-q", "set", "Master", "toggle"]),
+                VolumeBackend::Pactl => {
+                    run(&["pactl", "set-sink-mute", "@DEFAULT_SINK@", "toggle"])
+                }
+            };
+
+            return;
+        }
+    };
+
+    let _ = match config::VOLUME_BACKEND {
+        VolumeBackend::Amixer => run(&[
+            "amixer",
+            "-q",
+            "set",
+            "Master",
+            &format!("{}%{}", config::VOLUME_STEP, sign),
+        ]),
+        VolumeBackend::Pactl => run(&[
+            "pactl",
+            "set-sink-volume",
+            "@DEFAULT_SINK@",
+            &format!("{}{}%", sign, config::VOLUME_STEP),
+        ]),
+    };
+}
+
+fn run(args: &[&str]) -> Option<String> {
+    let output = Command::new(args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn parse_first_percentage(text: &str) -> Option<u8> {
+    let end = text.find('%')?;
+
+    let start = text[..end]
+        .rfind(|char: char| !char.is_ascii_digit())
+        .map_or(0, |index| index + 1);
+
+    text[start..end].parse().ok()
+}
@@ -0,0 +1,117 @@
+use crate::app::App;
+use crate::config;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Background {
+    // Path of one of config::WORKSPACE_WALLPAPERS
+    Image(&'static str),
+
+    // The theme's root_background
+    Gradient,
+}
+
+// Paints the root window with the image configured for the active workspace,
+// see config::WORKSPACE_WALLPAPERS, or the theme's gradient
+pub struct Wallpaper {
+    app: Rc<App>,
+
+    // Decoded images by their paths, None if loading failed so it isn't retried on every switch
+    images: RefCell<HashMap<&'static str, Option<cairo::ImageSurface>>>,
+
+    // What's on the root window right now, None if we haven't painted it
+    shown: Cell<Option<Background>>,
+}
+
+impl Wallpaper {
+    pub fn new(app: Rc<App>) -> Self {
+        Self {
+            app,
+            images: RefCell::new(HashMap::new()),
+            shown: Cell::new(None),
+        }
+    }
+
+    pub fn show(&self, workspace_index: usize) {
+        let background = match config::WORKSPACE_WALLPAPERS[workspace_index] {
+            Some(path) if self.image(path).is_some() => Background::Image(path),
+            _ if config::PAINT_ROOT_BACKGROUND => Background::Gradient,
+            // Whatever is on the root window stays
+            _ => return,
+        };
+
+        if self.shown.get() == Some(background) {
+            return;
+        }
+
+        match background {
+            Background::Image(path) => self.paint_image(path),
+            Background::Gradient => self.paint_gradient(),
+        }
+
+        self.shown.set(Some(background));
+    }
+
+    fn image(&self, path: &'static str) -> Option<cairo::ImageSurface> {
+        self.images
+            .borrow_mut()
+            .entry(path)
+            .or_insert_with(|| load_image(path))
+            .clone()
+    }
+
+    fn paint_image(&self, path: &'static str) {
+        let Some(image) = self.image(path)
+        else {
+            return;
+        };
+
+        let api = self.app.api();
+        let (screen_width, screen_height) = (api.screen_width() as f64, api.screen_height() as f64);
+        let (image_width, image_height) = (image.width() as f64, image.height() as f64);
+
+        // Covers the whole screen keeping the aspect ratio, whatever sticks out is cut off evenly
+        let scale = (screen_width / image_width).max(screen_height / image_height);
+
+        api.set_root_background(|context| {
+            context.translate(
+                (screen_width - image_width * scale) / 2.0,
+                (screen_height - image_height * scale) / 2.0,
+            );
+            context.scale(scale, scale);
+            context.set_source_surface(&image, 0.0, 0.0).unwrap();
+            context.source().set_filter(cairo::Filter::Good);
+            context.paint().unwrap();
+        });
+    }
+
+    fn paint_gradient(&self) {
+        let api = self.app.api();
+        let (top_color, bottom_color) = config::THEME.root_background;
+        let gradient = cairo::LinearGradient::new(0.0, 0.0, 0.0, api.screen_height() as _);
+
+        top_color.add_stop(&gradient, 0.0);
+        bottom_color.add_stop(&gradient, 1.0);
+
+        api.set_root_background(|context| {
+            context.set_source(&gradient).unwrap();
+            context.paint().unwrap();
+        });
+    }
+}
+
+fn load_image(path: &str) -> Option<cairo::ImageSurface> {
+    let image = File::open(path)
+        .ok()
+        .and_then(|mut file| cairo::ImageSurface::create_from_png(&mut file).ok());
+
+    if image.is_none() {
+        eprintln!("Couldn't load the wallpaper {path}");
+    }
+
+    image
+}
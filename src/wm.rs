@@ -1,17 +1,43 @@
+use crate::animation;
+use crate::animation::Animation;
+use crate::animation::AnimationKind;
+use crate::api::WindowSnapshot;
 use crate::app::App;
 use crate::bottom_panel;
 use crate::client;
 use crate::client::Client;
-use crate::keycode::Keycode;
+use crate::client::ClientChange;
+use crate::config;
+use crate::config::EscapeAction;
+use crate::config::Layout;
+use crate::config::NewWindowFocus;
+use crate::config::Placement;
+use crate::config::UnmanagedLayer;
+use crate::config::WindowAnimation;
+use crate::hints::apply_size_hints;
+use crate::hints::gravity_shift;
+use crate::hints::Dimension;
+use crate::keycode::Key;
+use crate::menu::MenuAction;
+use crate::outline::Outline;
+use crate::placement::center_over_parent;
+use crate::placement::place_container;
+use crate::serialized_state::SerializedClient;
+use crate::serialized_state::SerializedState;
+use crate::serialized_state::SerializedWorkspace;
+use crate::serialized_state::SERIALIZED_STATE_VERSION;
 use crate::top_panel;
 use crate::util::cycle_next;
 use crate::util::cycle_previous;
+use crate::util::find_closest_in_direction;
+use crate::util::milliseconds_since;
+use crate::util::Direction;
+use crate::wallpaper::Wallpaper;
 use nix::unistd::execvp;
-use serde::Deserialize;
-use serde::Serialize;
 use std::cell::Cell;
 use std::cell::Ref;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::ffi::CString;
 use std::fs::File;
@@ -19,77 +45,240 @@ use std::io::BufReader;
 use std::io::BufWriter;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
 use x11rb::protocol::xproto::AtomEnum;
 use x11rb::protocol::xproto::ButtonIndex;
 use x11rb::protocol::xproto::ButtonPressEvent;
+use x11rb::protocol::xproto::ClientMessageEvent;
 use x11rb::protocol::xproto::ConfigWindow;
 use x11rb::protocol::xproto::ConfigureRequestEvent;
+use x11rb::protocol::xproto::FocusOutEvent;
 use x11rb::protocol::xproto::KeyButMask;
 use x11rb::protocol::xproto::KeyPressEvent;
+use x11rb::protocol::xproto::KeyReleaseEvent;
+use x11rb::protocol::xproto::LeaveNotifyEvent;
 use x11rb::protocol::xproto::MapRequestEvent;
 use x11rb::protocol::xproto::MapState;
 use x11rb::protocol::xproto::ModMask;
 use x11rb::protocol::xproto::MotionNotifyEvent;
+use x11rb::protocol::xproto::NotifyDetail;
+use x11rb::protocol::xproto::NotifyMode;
 use x11rb::protocol::xproto::PropertyNotifyEvent;
+use x11rb::protocol::xproto::ReparentNotifyEvent;
 use x11rb::protocol::xproto::UnmapNotifyEvent;
 use x11rb::protocol::Event;
 
+// How much of the title has to be the same for a recreated window to take over a saved client
+const RESTORED_TITLE_PREFIX_LENGTH: usize = 10;
+
+// How many pixels a key press in resize mode grows or shrinks the client by
+const RESIZE_STEP: u16 = 20;
+
+// Same for moving it in move mode
+const MOVE_STEP: u16 = 20;
+
+// New windows smaller than this either way are most likely going to resize themselves
+// once mapped, they get config::DEFAULT_WINDOW_WIDTH and HEIGHT instead
+const TINY_WINDOW_SIZE: u16 = 100;
+
 pub struct Wm {
     app: Rc<App>,
     workspaces: [Workspace; 9],
     active_workspace_index: Cell<usize>,
     drag_state: Cell<Option<DragState>>,
+    outline: Outline,
+
+    // Windows from config::UNMANAGED_CLASSES, we only keep them in their layer of the stacking
+    // order
+    unmanaged_windows: RefCell<Vec<(u32, UnmanagedLayer)>>,
+    wallpaper: Wallpaper,
+
+    // Server time of the last key or button press, raising a client is attributed to it
+    last_input_time: Cell<u32>,
+
+    animations: RefCell<Vec<Animation>>,
+    workspace_transition: Cell<Option<WorkspaceTransition>>,
+    input_mode: Cell<InputMode>,
+
+    // Tracked through the grabbed Super keys, the tasklist shows entry numbers for Mod4+F1..F9
+    mod4_held: Cell<bool>,
+
+    // Where the container of the last new client has been placed, cascading continues from there
+    last_placement: Cell<Option<(i16, i16)>>,
+
+    // Clients only change in response to events, so when there were none the frames are left alone
+    need_redraw: Cell<bool>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum InputMode {
+    Normal,
+
+    // The keyboard is grabbed and keys resize the client until Return or Escape is pressed
+    Resize { client_id: u32 },
+
+    // Same, but keys move the client
+    Move { client_id: u32 },
+}
+
+impl InputMode {
+    fn client_id(self) -> Option<u32> {
+        match self {
+            InputMode::Normal => None,
+            InputMode::Resize { client_id } | InputMode::Move { client_id } => Some(client_id),
+        }
+    }
+}
+
+// Containers of both workspaces slide horizontally, the old one gets unmapped once it's over
+#[derive(Clone, Copy)]
+struct WorkspaceTransition {
+    from: usize,
+
+    // 1 if the new workspace comes from the right, -1 if from the left
+    direction: i16,
+
+    started_at: Instant,
 }
 
 #[derive(Default)]
 pub struct Workspace {
     stack: RefCell<Vec<Rc<Client>>>,
     tasklist: RefCell<Vec<Rc<Client>>>,
+
+    // Client which had focus most recently, so it gets it back when we return to this workspace
+    last_focused: RefCell<Option<u32>>,
+
+    layout: Cell<Layout>,
+
+    // Whether clients were maximized and shaded before they got into the monocle layout,
+    // they get back to that when switching to floating
+    floating_states: RefCell<HashMap<u32, (bool, bool)>>,
 }
 
 impl Workspace {
-    pub fn stack(&self) -> Ref<Vec<Rc<Client>>> {
+    pub fn stack(&self) -> Ref<'_, Vec<Rc<Client>>> {
         self.stack.borrow()
     }
 
-    pub fn tasklist(&self) -> Ref<Vec<Rc<Client>>> {
+    pub fn tasklist(&self) -> Ref<'_, Vec<Rc<Client>>> {
         self.tasklist.borrow()
     }
+
+    // Only borrows the stack for the lookup, so the result can be passed to Wm::raise_client()
+    pub fn stack_index_of(&self, id: u32) -> Option<usize> {
+        self.stack().iter().position(|client| client.id() == id)
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout.get()
+    }
+
+    // Whether the layout decides the client's geometry instead of the user
+    pub fn arranges(&self, client: &Client) -> bool {
+        self.layout() == Layout::Monocle && !client.floating()
+    }
 }
 
 #[derive(Clone, Copy)]
 struct DragState {
+    client_id: u32,
     kind: DragKind,
     x: u16,
     y: u16,
+
+    // Pointer position and client geometry at the moment the drag has started.
+    // Resizing is calculated relative to them, so small movements don't get lost to size hints
+    start_x: u16,
+    start_y: u16,
+    start_geometry: (i16, i16, u16, u16),
+
+    // The client gets the geometry only when the drag ends, not on every motion
+    deferred: bool,
+
+    // The outline follows the pointer meanwhile, only deferred drags are outlined
+    outlined: bool,
+
+    // Where the client is, or would be if the drag is deferred
+    geometry: (i16, i16, u16, u16),
 }
 
 #[derive(Clone, Copy)]
 enum DragKind {
     Move,
-    Resize,
+    Resize(ResizeEdges),
 }
 
-#[derive(Serialize, Deserialize, Default)]
-struct SerializedState {
-    workspaces: [SerializedWorkspace; 9],
-    active_workspace_index: usize,
+// Edges of the window which follow the pointer while resizing
+#[derive(Clone, Copy)]
+struct ResizeEdges {
+    left: bool,
+    right: bool,
+    top: bool,
+    bottom: bool,
+}
+
+impl ResizeEdges {
+    const BOTTOM_RIGHT: Self = Self {
+        left: false,
+        right: true,
+        top: false,
+        bottom: true,
+    };
+
+    // Directions of _NET_WM_MOVERESIZE go clockwise starting from the top left corner
+    fn from_net_wm_moveresize_direction(direction: u32) -> Option<Self> {
+        let (left, right, top, bottom) = match direction {
+            0 => (true, false, true, false),
+            1 => (false, false, true, false),
+            2 => (false, true, true, false),
+            3 => (false, true, false, false),
+            4 => (false, true, false, true),
+            5 => (false, false, false, true),
+            6 => (true, false, false, true),
+            7 => (true, false, false, false),
+            _ => return None,
+        };
+
+        Some(Self {
+            left,
+            right,
+            top,
+            bottom,
+        })
+    }
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
-struct SerializedWorkspace {
-    stack: Vec<SerializedClient>,
-    tasklist: Vec<u32>,
+impl From<&Client> for SerializedClient {
+    fn from(client: &Client) -> Self {
+        Self {
+            id: client.id(),
+            x: client.x(),
+            y: client.y(),
+            width: client.width(),
+            height: client.height(),
+            maximized: client.maximized(),
+            shaded: client.shaded(),
+            last_activity: client.last_activity(),
+            minimized: client.minimized(),
+            above: client.above(),
+            floating: client.floating(),
+            class: client.class().clone(),
+            title: client.title().clone(),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct SerializedClient {
-    id: u32,
-    x: i16,
-    y: i16,
-    width: u16,
-    height: u16,
-    maximized: bool,
+impl SerializedClient {
+    // Geometry is passed to Client::new() instead, the rest of the state is applied afterwards
+    fn restore_state(&self, client: &Client) {
+        client.set_shaded(self.shaded);
+        client.set_last_activity(self.last_activity);
+        client.set_minimized(self.minimized);
+        client.set_above(self.above);
+        client.set_floating(self.floating);
+    }
 }
 
 enum ExistingClientInfo {
@@ -99,30 +288,68 @@ enum ExistingClientInfo {
 
 impl Wm {
     pub fn new(app: Rc<App>) -> Self {
-        let serialized_state: SerializedState = File::open(get_serialized_state_file_path())
+        let mut serialized_state: SerializedState = File::open(get_serialized_state_file_path())
             .ok()
             .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .filter(|state: &SerializedState| state.version <= SERIALIZED_STATE_VERSION)
             .unwrap_or_default();
 
+        serialized_state.sanitize();
+        debug_assert_eq!(serialized_state.check_invariants(), Ok(()));
+
         let this = Self {
+            outline: Outline::new(app.clone()),
+            wallpaper: Wallpaper::new(app.clone()),
+            unmanaged_windows: RefCell::new(Vec::new()),
             app,
             workspaces: Default::default(),
-            active_workspace_index: Cell::new(serialized_state.active_workspace_index),
+            active_workspace_index: Cell::new(
+                config::STARTUP_WORKSPACE
+                    .or(Some(serialized_state.active_workspace_index))
+                    .filter(|index| *index < serialized_state.workspaces.len())
+                    .unwrap_or_default(),
+            ),
             drag_state: Cell::new(None),
+            last_input_time: Cell::new(0),
+            animations: RefCell::new(Vec::new()),
+            workspace_transition: Cell::new(None),
+            input_mode: Cell::new(InputMode::Normal),
+            mod4_held: Cell::new(false),
+            last_placement: Cell::new(None),
+            need_redraw: Cell::new(true),
         };
 
         this.init(serialized_state.workspaces);
+        this.wallpaper.show(this.active_workspace_index());
 
         this
     }
 
-    fn init(&self, serialized_workspaces: [SerializedWorkspace; 9]) {
-        let mut existing_client_ids: HashSet<_> = self
+    fn init(&self, mut serialized_workspaces: [SerializedWorkspace; 9]) {
+        // Fetched all at once, with a lot of windows doing it one by one makes restarts sluggish
+        let mut snapshots = self
             .app
             .api()
-            .get_window_children(self.app.api().root())
-            .into_iter()
-            .collect();
+            .get_window_snapshots(&self.app.api().get_window_children(self.app.api().root()));
+
+        // They're left as they are, without a frame. The panels don't exist yet,
+        // so raise_panels() gets called once they do
+        snapshots.retain(|id, snapshot| {
+            let Some(layer) = get_unmanaged_layer(snapshot.class.as_deref())
+            else {
+                return true;
+            };
+
+            if snapshot.attributes.map_state != MapState::UNMAPPED
+                && !snapshot.attributes.override_redirect
+            {
+                self.unmanaged_windows.borrow_mut().push((*id, layer));
+            }
+
+            false
+        });
+
+        match_recreated_windows(&mut serialized_workspaces, &snapshots);
 
         for ((workspace_index, workspace), serialized_workspace) in self
             .workspaces
@@ -130,18 +357,32 @@ impl Wm {
             .enumerate()
             .zip(serialized_workspaces)
         {
+            workspace.layout.set(
+                serialized_workspace
+                    .layout
+                    .unwrap_or(config::WORKSPACE_LAYOUTS[workspace_index]),
+            );
+
             for client in serialized_workspace.stack {
-                if !existing_client_ids.remove(&client.id) {
+                let Some(snapshot) = snapshots.remove(&client.id)
+                else {
                     continue;
-                }
+                };
 
                 let Some(client) =
-                    self.manage_existing_client(ExistingClientInfo::Serialized(client))
+                    self.manage_existing_client(ExistingClientInfo::Serialized(client), snapshot)
                 else {
                     continue;
                 };
 
-                if workspace_index == self.active_workspace_index() {
+                // Clients are saved with their floating state
+                self.add_client_to_layout(
+                    workspace,
+                    &client,
+                    (client.maximized(), client.shaded()),
+                );
+
+                if workspace_index == self.active_workspace_index() && !client.minimized() {
                     self.app.api().map_window(client.container_id());
                 }
 
@@ -150,26 +391,48 @@ impl Wm {
 
             for id in serialized_workspace.tasklist {
                 let stack = workspace.stack();
+                let mut tasklist = workspace.tasklist.borrow_mut();
+
+                // Ids might repeat if a recreated window has taken over one which is still present
+                if tasklist.iter().any(|client| client.id() == id) {
+                    continue;
+                }
 
                 let Some(client) = stack.iter().find(|client| client.id() == id)
                 else {
                     continue;
                 };
 
-                workspace.tasklist.borrow_mut().push(client.clone());
+                tasklist.push(client.clone());
+            }
+
+            // Clients which are missing from the saved tasklist go to its end, in stack order
+            for client in workspace.stack().iter() {
+                let mut tasklist = workspace.tasklist.borrow_mut();
+
+                if !tasklist.iter().any(|c| c.id() == client.id()) {
+                    tasklist.push(client.clone());
+                }
             }
+
+            let last_focused = serialized_workspace
+                .last_focused
+                .filter(|id| workspace.stack().iter().any(|client| client.id() == *id));
+
+            *workspace.last_focused.borrow_mut() = last_focused;
         }
 
         let active_workspace = self.active_workspace();
         let mut active_workspace_stack = active_workspace.stack.borrow_mut();
         let mut active_workspace_tasklist = active_workspace.tasklist.borrow_mut();
 
-        for id in existing_client_ids {
-            let Some(client) = self.manage_existing_client(ExistingClientInfo::Id(id))
+        for (id, snapshot) in snapshots {
+            let Some(client) = self.manage_existing_client(ExistingClientInfo::Id(id), snapshot)
             else {
                 continue;
             };
 
+            self.add_client_to_layout(active_workspace, &client, (client.maximized(), false));
             self.app.api().map_window(client.container_id());
 
             let client = Rc::new(client);
@@ -178,18 +441,20 @@ impl Wm {
             active_workspace_tasklist.push(client);
         }
 
-        self.app
-            .api()
-            .set_focus(active_workspace_stack.last().map(|client| client.id()));
+        self.set_focus(&active_workspace_stack);
     }
 
-    fn manage_existing_client(&self, info: ExistingClientInfo) -> Option<Client> {
+    fn manage_existing_client(
+        &self,
+        info: ExistingClientInfo,
+        snapshot: WindowSnapshot,
+    ) -> Option<Client> {
         let id = match info {
             ExistingClientInfo::Id(id) => id,
             ExistingClientInfo::Serialized(ref client) => client.id,
         };
 
-        let attrs = self.app.api().get_window_attributes(id);
+        let attrs = snapshot.attributes;
 
         if attrs.map_state == MapState::UNMAPPED {
             return None;
@@ -200,14 +465,14 @@ impl Wm {
         }
 
         let (x, y, width, height, maximized) = match info {
-            ExistingClientInfo::Id(id) => {
-                let geometry = self.app.api().get_window_geometry(id);
+            ExistingClientInfo::Id(_) => {
+                let geometry = snapshot.geometry;
 
                 let maximized = geometry.width == self.app.api().screen_width()
                     && geometry.height
                         == self.app.api().screen_height()
-                            - top_panel::PANEL_HEIGHT
-                            - bottom_panel::PANEL_HEIGHT;
+                            - top_panel::RESERVED_HEIGHT
+                            - bottom_panel::RESERVED_HEIGHT;
 
                 (
                     geometry.x,
@@ -217,16 +482,17 @@ impl Wm {
                     maximized,
                 )
             }
-            ExistingClientInfo::Serialized(client) => (
+            // Creating a window with a zero dimension is a BadValue error
+            ExistingClientInfo::Serialized(ref client) => (
                 client.x,
                 client.y,
-                client.width,
-                client.height,
+                client.width.max(1),
+                client.height.max(1),
                 client.maximized,
             ),
         };
 
-        Some(Client::new(
+        let client = Client::new(
             self.app.clone(),
             id,
             x,
@@ -234,15 +500,24 @@ impl Wm {
             width,
             height,
             maximized,
-            self.app.api().get_window_class(id),
-            self.app.api().get_window_title(id),
-            self.app.api().get_window_icon(id),
-        ))
+            snapshot.class,
+            snapshot.title,
+            snapshot.icon,
+            snapshot.size_hints,
+        )?;
+
+        if let ExistingClientInfo::Serialized(serialized_client) = info {
+            serialized_client.restore_state(&client);
+        }
+
+        Some(client)
     }
 
     fn handle_map_request(&self, event: &MapRequestEvent) {
-        let id = event.window;
+        self.manage_new_window(event.window);
+    }
 
+    fn manage_new_window(&self, id: u32) {
         let client_already_managed = self.workspaces.iter().any(|workspace| {
             workspace
                 .stack
@@ -255,12 +530,46 @@ impl Wm {
             return;
         }
 
+        let class = self.app.api().get_window_class(id);
+
+        if let Some(layer) = get_unmanaged_layer(class.as_deref()) {
+            self.unmanaged_windows.borrow_mut().push((id, layer));
+            self.app.api().map_window(id);
+            self.raise_panels();
+            return;
+        }
+
+        // Dialogs have the class of their application, they aren't instances of it
+        let max_instances = config::MAX_INSTANCES
+            .iter()
+            .find(|(max_instances_class, _)| class.as_deref() == Some(*max_instances_class))
+            .map(|(_, max_instances)| *max_instances)
+            .filter(|_| self.app.api().get_window_transient_for(id).is_none());
+
+        if let (Some(class), Some(max_instances)) = (&class, max_instances) {
+            if self.count_clients_by_class(class) >= max_instances {
+                self.app.api().unmap_window(id);
+                self.app.api().destroy_window(id);
+                self.raise_client_by_class(class);
+                return;
+            }
+        }
+
+        if let Some(startup_id) = self.app.api().get_startup_id(id) {
+            self.app.api().complete_startup(&startup_id);
+
+            self.app
+                .api()
+                .set_window_cursor(self.app.api().root(), self.app.api().cursors.left_ptr);
+        }
+
         let geometry = self.app.api().get_window_geometry(id);
 
         let maximized_width = self.app.api().screen_width();
 
-        let maximized_height =
-            self.app.api().screen_height() - top_panel::PANEL_HEIGHT - bottom_panel::PANEL_HEIGHT;
+        let maximized_height = self.app.api().screen_height()
+            - top_panel::RESERVED_HEIGHT
+            - bottom_panel::RESERVED_HEIGHT;
 
         let maximized = geometry.width == maximized_width;
 
@@ -274,16 +583,24 @@ impl Wm {
         let (width, height) = if maximized {
             (1000, 800)
         }
+        else if geometry.width < TINY_WINDOW_SIZE || geometry.height < TINY_WINDOW_SIZE {
+            self.app
+                .api()
+                .set_window_width(id, config::DEFAULT_WINDOW_WIDTH);
+
+            self.app
+                .api()
+                .set_window_height(id, config::DEFAULT_WINDOW_HEIGHT);
+
+            (config::DEFAULT_WINDOW_WIDTH, config::DEFAULT_WINDOW_HEIGHT)
+        }
         else {
             (geometry.width, geometry.height)
         };
 
-        let x = (self.app.api().screen_width() as i16 - width as i16) / 2;
-        let y = (self.app.api().screen_height() as i16 + top_panel::PANEL_HEIGHT as i16
-            - height as i16)
-            / 2;
+        let (x, y) = self.place_new_client(id, width, height);
 
-        let client = Rc::new(Client::new(
+        let Some(client) = Client::new(
             self.app.clone(),
             id,
             x,
@@ -291,21 +608,50 @@ impl Wm {
             width,
             height,
             maximized,
-            self.app.api().get_window_class(id),
+            class,
             self.app.api().get_window_title(id),
             self.app.api().get_window_icon(id),
-        ));
+            self.app.api().get_window_size_hints(id),
+        )
+        else {
+            return;
+        };
+
+        let client = Rc::new(client);
+
+        let assigned_workspace_index = config::WORKSPACE_ASSIGNMENTS
+            .iter()
+            .find(|(class, _)| client.class().as_deref() == Some(*class))
+            .map(|(_, workspace_index)| *workspace_index);
+
+        // The container gets mapped once its workspace becomes active
+        if let Some(workspace_index) = assigned_workspace_index
+            .filter(|workspace_index| *workspace_index != self.active_workspace_index())
+        {
+            self.app.api().map_window(client.id());
+
+            let workspace = &self.workspaces[workspace_index];
+            self.add_client_to_layout(workspace, &client, (maximized, false));
+            workspace.stack.borrow_mut().push(client.clone());
+            workspace.tasklist.borrow_mut().push(client);
+
+            self.app.top_panel().notify();
+            return;
+        }
+
+        self.add_client_to_layout(self.active_workspace(), &client, (maximized, false));
+
+        if config::WINDOW_ANIMATION != WindowAnimation::None {
+            self.start_animation(&client, AnimationKind::Map);
+        }
 
         self.app.api().map_window(client.id());
         self.app.api().map_window(client.container_id());
-        self.app.api().set_focus(client.id());
 
         let mut stack = self.active_workspace().stack.borrow_mut();
         let mut tasklist = self.active_workspace().tasklist.borrow_mut();
 
         if let Some(active_client) = stack.last() {
-            active_client.notify();
-
             let tasklist_index = tasklist
                 .iter()
                 .position(|client| client.id() == active_client.id())
@@ -317,16 +663,210 @@ impl Wm {
             tasklist.push(client.clone());
         }
 
-        stack.push(client);
+        // There has to be some other window to keep the focus
+        let in_background =
+            !stack.is_empty() && config::NEW_WINDOW_FOCUS != NewWindowFocus::StealFocus;
+
+        if in_background {
+            client.set_urgent(config::NEW_WINDOW_FOCUS == NewWindowFocus::UrgentBackground);
+            self.app.api().lower_window(client.container_id());
+            stack.insert(0, client);
+        }
+        else {
+            // The dragged client would end up under the new one while still following the pointer
+            self.cancel_drag();
+
+            if let Some(active_client) = stack.last() {
+                active_client.notify();
+            }
+
+            stack.push(client);
+            self.set_focus(&stack);
+            self.raise_above_clients(&stack);
 
-        self.app.api().raise_window(self.app.top_panel().id());
-        self.app.api().raise_window(self.app.bottom_panel().id());
+            self.raise_panels();
+        }
 
         self.app.top_panel().notify();
         self.app.bottom_panel().notify();
     }
 
+    // Returns position of the client itself, not its container
+    fn place_new_client(&self, id: u32, width: u16, height: u16) -> (i16, i16) {
+        let container_width = width + client::BORDER_WIDTH * 2;
+        let container_height = height + client::BORDER_WIDTH * 2 + client::TITLEBAR_HEIGHT;
+
+        let area = (
+            0,
+            top_panel::RESERVED_HEIGHT as i16,
+            self.app.api().screen_width(),
+            self.app.api().screen_height()
+                - top_panel::RESERVED_HEIGHT
+                - bottom_panel::RESERVED_HEIGHT,
+        );
+
+        let parent = self
+            .app
+            .api()
+            .get_window_transient_for(id)
+            .and_then(|parent_id| {
+                self.active_workspace()
+                    .stack()
+                    .iter()
+                    .find(|client| client.id() == parent_id && !client.minimized())
+                    .cloned()
+            });
+
+        let containers = self
+            .active_workspace()
+            .stack()
+            .iter()
+            .filter(|client| !client.minimized())
+            .map(|client| {
+                (
+                    client.container_x(),
+                    client.container_y(),
+                    client.container_width(),
+                    client.container_height(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let (x, y) = match parent {
+            // Dialogs don't count as the previous placement, so cascading isn't thrown off by them
+            Some(parent) => center_over_parent(
+                container_width,
+                container_height,
+                area,
+                (
+                    parent.container_x(),
+                    parent.container_y(),
+                    parent.container_width(),
+                    parent.container_height(),
+                ),
+            ),
+            None => {
+                let pointer = if config::NEW_WINDOW_PLACEMENT == Placement::UnderPointer {
+                    self.app.api().query_pointer()
+                }
+                else {
+                    (0, 0)
+                };
+
+                let (x, y) = place_container(
+                    config::NEW_WINDOW_PLACEMENT,
+                    container_width,
+                    container_height,
+                    area,
+                    &containers,
+                    self.last_placement.get(),
+                    pointer,
+                );
+
+                self.last_placement.set(Some((x, y)));
+
+                (x, y)
+            }
+        };
+
+        (
+            x + client::BORDER_WIDTH as i16,
+            y + (client::BORDER_WIDTH + client::TITLEBAR_HEIGHT) as i16,
+        )
+    }
+
+    // Not counting dialogs
+    fn count_clients_by_class(&self, class: &str) -> usize {
+        self.workspaces
+            .iter()
+            .map(|workspace| {
+                workspace
+                    .stack()
+                    .iter()
+                    .filter(|client| {
+                        client.class().as_deref() == Some(class)
+                            && self
+                                .app
+                                .api()
+                                .get_window_transient_for(client.id())
+                                .is_none()
+                    })
+                    .count()
+            })
+            .sum()
+    }
+
+    // Switches to the workspace of the topmost client with the class and raises it
+    fn raise_client_by_class(&self, class: &str) {
+        let Some((workspace_index, client_id)) =
+            self.workspaces
+                .iter()
+                .enumerate()
+                .find_map(|(workspace_index, workspace)| {
+                    workspace
+                        .stack()
+                        .iter()
+                        .rev()
+                        .find(|client| client.class().as_deref() == Some(class))
+                        .map(|client| (workspace_index, client.id()))
+                })
+        else {
+            return;
+        };
+
+        self.change_active_workspace(workspace_index);
+
+        if let Some(stack_index) = self.active_workspace().stack_index_of(client_id) {
+            self.raise_client(stack_index);
+        }
+    }
+
     fn handle_unmap_notify(&self, event: &UnmapNotifyEvent) {
+        self.unmanaged_windows
+            .borrow_mut()
+            .retain(|(id, _)| *id != event.window);
+
+        self.unmanage_client(event.window);
+    }
+
+    fn handle_reparent_notify(&self, event: &ReparentNotifyEvent) {
+        let container_id = self.workspaces.iter().find_map(|workspace| {
+            workspace
+                .stack()
+                .iter()
+                .find(|client| client.id() == event.window)
+                .map(|client| client.container_id())
+        });
+
+        match container_id {
+            // Some toolkits reparent their windows themselves, after that there's nothing to frame
+            Some(container_id) => {
+                if event.parent != container_id {
+                    self.unmanage_client(event.window);
+                }
+            }
+            // A window reparented to the root while mapped never sends a MapRequest
+            None => {
+                if event.parent != self.app.api().root() {
+                    return;
+                }
+
+                let manageable = self
+                    .app
+                    .api()
+                    .get_window_attributes(event.window)
+                    .is_some_and(|attrs| {
+                        attrs.map_state != MapState::UNMAPPED && !attrs.override_redirect
+                    });
+
+                if manageable {
+                    self.manage_new_window(event.window);
+                }
+            }
+        }
+    }
+
+    fn unmanage_client(&self, id: u32) {
         let Some((workspace_index, client_stack_index)) = self
             .workspaces
             .iter()
@@ -336,21 +876,34 @@ impl Wm {
                     .stack
                     .borrow()
                     .iter()
-                    .position(|client| client.id() == event.window)
+                    .position(|client| client.id() == id)
                     .map(|client_index| (workspace_index, client_index))
             })
         else {
             return;
         };
 
+        if self.input_mode.get().client_id() == Some(id) {
+            self.leave_input_mode();
+        }
+
+        if self
+            .drag_state
+            .get()
+            .is_some_and(|state| state.client_id == id)
+        {
+            self.cancel_drag();
+        }
+
         let workspace = &self.workspaces[workspace_index];
         workspace.stack.borrow_mut().remove(client_stack_index);
+        workspace.floating_states.borrow_mut().remove(&id);
 
         let client_tasklist_index = workspace
             .tasklist
             .borrow()
             .iter()
-            .position(|client| client.id() == event.window)
+            .position(|client| client.id() == id)
             .unwrap();
 
         workspace
@@ -364,108 +917,645 @@ impl Wm {
             let stack = workspace.stack.borrow();
 
             if let Some(client) = stack.last() {
+                client.set_urgent(false);
                 client.notify();
             }
 
-            self.app
-                .api()
-                .set_focus(stack.last().map(|client| client.id()));
+            self.set_focus(&stack);
+
+            if let Some(client) = stack.last() {
+                self.warp_pointer_to_client(client);
+            }
 
             self.app.bottom_panel().notify();
         }
     }
 
     fn handle_key_press(&self, event: &KeyPressEvent) {
-        let Ok(keycode) = Keycode::try_from(event.detail)
-        else {
-            return;
-        };
-
-        let is_shift = event.state.contains(ModMask::SHIFT);
-
-        match keycode {
-            Keycode::Escape => {
-                let file = File::create(get_serialized_state_file_path()).unwrap();
-                serde_json::to_writer(BufWriter::new(file), &self.serialize()).unwrap();
-
-                let args = std::env::args()
-                    .map(|s| CString::new(s).unwrap())
-                    .collect::<Vec<_>>();
-
-                execvp(&args[0], &args).unwrap();
-            }
-            Keycode::K if is_shift => self.move_active_client_forward_in_tasklist(),
-            Keycode::J if is_shift => self.move_active_client_backward_in_tasklist(),
-            Keycode::K => self.raise_next_tasklist_client(),
-            Keycode::J => self.raise_previous_tasklist_client(),
-            Keycode::Number1 if is_shift => self.move_active_client_to_workspace(0),
-            Keycode::Number2 if is_shift => self.move_active_client_to_workspace(1),
-            Keycode::Number3 if is_shift => self.move_active_client_to_workspace(2),
-            Keycode::Number4 if is_shift => self.move_active_client_to_workspace(3),
-            Keycode::Number5 if is_shift => self.move_active_client_to_workspace(4),
-            Keycode::Number6 if is_shift => self.move_active_client_to_workspace(5),
-            Keycode::Number7 if is_shift => self.move_active_client_to_workspace(6),
-            Keycode::Number8 if is_shift => self.move_active_client_to_workspace(7),
-            Keycode::Number9 if is_shift => self.move_active_client_to_workspace(8),
-            Keycode::Number1 => self.change_active_workspace(0),
-            Keycode::Number2 => self.change_active_workspace(1),
-            Keycode::Number3 => self.change_active_workspace(2),
-            Keycode::Number4 => self.change_active_workspace(3),
-            Keycode::Number5 => self.change_active_workspace(4),
-            Keycode::Number6 => self.change_active_workspace(5),
-            Keycode::Number7 => self.change_active_workspace(6),
-            Keycode::Number8 => self.change_active_workspace(7),
-            Keycode::Number9 => self.change_active_workspace(8),
-            Keycode::Right => self.change_active_workspace(cycle_next(
+        // Keys pressed while the overview has grabbed the keyboard are meant for it
+        if event.event != self.app.api().root() {
+            return;
+        }
+
+        // The state is also checked on other keys, in case the release got lost to someone's grab
+        self.set_mod4_held(
+            self.is_super_key(event.detail) || event.state.contains(KeyButMask::MOD4),
+        );
+
+        match self.input_mode.get() {
+            InputMode::Normal => {}
+            InputMode::Resize { client_id } => {
+                self.handle_resize_mode_key_press(event, client_id);
+                return;
+            }
+            InputMode::Move { client_id } => {
+                self.handle_move_mode_key_press(event, client_id);
+                return;
+            }
+        }
+
+        let Some((key, modmask)) = self.app.keymap().get_key(event.detail, event.state)
+        else {
+            return;
+        };
+
+        let shift = ModMask::M4 | ModMask::SHIFT;
+        let shift_control = shift | ModMask::CONTROL;
+
+        match key {
+            Key::Escape => {
+                let app = self.app.clone();
+
+                match config::ESCAPE_ACTION {
+                    EscapeAction::Restart => self
+                        .app
+                        .dialog()
+                        .ask("Restart vaporwm?", move || app.wm().restart()),
+                    EscapeAction::Quit => self
+                        .app
+                        .dialog()
+                        .ask("Quit vaporwm?", move || app.wm().quit()),
+                    // The key isn't even grabbed
+                    EscapeAction::Disabled => {}
+                }
+            }
+            Key::K if modmask == shift => self.move_active_client_forward_in_tasklist(),
+            Key::J if modmask == shift => self.move_active_client_backward_in_tasklist(),
+            Key::K if config::DIRECTIONAL_FOCUS_JK => self.raise_client_in_direction(Direction::Up),
+            Key::J if config::DIRECTIONAL_FOCUS_JK => {
+                self.raise_client_in_direction(Direction::Down)
+            }
+            Key::K => self.raise_next_tasklist_client(),
+            Key::J => self.raise_previous_tasklist_client(),
+            Key::H => self.raise_client_in_direction(Direction::Left),
+            Key::L => self.raise_client_in_direction(Direction::Right),
+            Key::Number1 if modmask == shift => self.move_active_client_to_workspace(0),
+            Key::Number2 if modmask == shift => self.move_active_client_to_workspace(1),
+            Key::Number3 if modmask == shift => self.move_active_client_to_workspace(2),
+            Key::Number4 if modmask == shift => self.move_active_client_to_workspace(3),
+            Key::Number5 if modmask == shift => self.move_active_client_to_workspace(4),
+            Key::Number6 if modmask == shift => self.move_active_client_to_workspace(5),
+            Key::Number7 if modmask == shift => self.move_active_client_to_workspace(6),
+            Key::Number8 if modmask == shift => self.move_active_client_to_workspace(7),
+            Key::Number9 if modmask == shift => self.move_active_client_to_workspace(8),
+            Key::Number1 => self.change_active_workspace(0),
+            Key::Number2 => self.change_active_workspace(1),
+            Key::Number3 => self.change_active_workspace(2),
+            Key::Number4 => self.change_active_workspace(3),
+            Key::Number5 => self.change_active_workspace(4),
+            Key::Number6 => self.change_active_workspace(5),
+            Key::Number7 => self.change_active_workspace(6),
+            Key::Number8 => self.change_active_workspace(7),
+            Key::Number9 => self.change_active_workspace(8),
+            Key::Right
+                if modmask == shift_control
+                    || (modmask == shift && config::SHIFT_ARROWS_FOLLOW_WINDOW) =>
+            {
+                self.move_active_client_to_next_workspace()
+            }
+            Key::Left
+                if modmask == shift_control
+                    || (modmask == shift && config::SHIFT_ARROWS_FOLLOW_WINDOW) =>
+            {
+                self.move_active_client_to_previous_workspace()
+            }
+            Key::Right if modmask == shift => self.move_active_client_to_workspace(cycle_next(
+                &self.workspaces,
+                self.active_workspace_index(),
+            )),
+            Key::Left if modmask == shift => self.move_active_client_to_workspace(cycle_previous(
+                &self.workspaces,
+                self.active_workspace_index(),
+            )),
+            Key::Right if config::CYCLE_SKIP_EMPTY => self.change_active_workspace(
+                self.next_workspace_with_windows(self.active_workspace_index()),
+            ),
+            Key::Left if config::CYCLE_SKIP_EMPTY => self.change_active_workspace(
+                self.previous_workspace_with_windows(self.active_workspace_index()),
+            ),
+            Key::Right => self.change_active_workspace(cycle_next(
                 &self.workspaces,
                 self.active_workspace_index(),
             )),
-            Keycode::Left => self.change_active_workspace(cycle_previous(
+            Key::Left => self.change_active_workspace(cycle_previous(
                 &self.workspaces,
                 self.active_workspace_index(),
             )),
-            Keycode::X => {
+            Key::X => {
                 if let Some(client) = self.active_workspace().stack().last() {
                     self.app.api().ask_window_to_close(client.id())
                 }
             }
-            Keycode::M => {
+            Key::M if modmask == shift => self.toggle_monocle_layout(),
+            Key::M => {
+                let workspace = self.active_workspace();
+
+                if let Some(client) = workspace.stack().last() {
+                    // Every client is maximized in the monocle layout, except for floating ones
+                    if !workspace.arranges(client) {
+                        client.set_maximized(!client.maximized());
+                    }
+                }
+            }
+            Key::O => self.swap_active_client_with_previous(),
+            Key::U => {
                 if let Some(client) = self.active_workspace().stack().last() {
-                    client.set_maximized(!client.maximized());
+                    client.set_shaded(!client.shaded());
+                }
+            }
+            Key::S if modmask == shift => {
+                if let Some(client_id) = self.active_workspace().stack().last().map(|c| c.id()) {
+                    self.enter_input_mode(InputMode::Resize { client_id });
                 }
             }
+            Key::A if modmask == shift => {
+                if let Some(client) = self.active_workspace().stack().last() {
+                    client.set_aspect_locked(!client.aspect_locked());
+                }
+            }
+            Key::A => self.arrange_clients_in_grid(),
+            Key::F if modmask == shift => self.toggle_active_client_floating(),
+            Key::F1 => self.raise_tasklist_entry(0),
+            Key::F2 => self.raise_tasklist_entry(1),
+            Key::F3 => self.raise_tasklist_entry(2),
+            Key::F4 => self.raise_tasklist_entry(3),
+            Key::F5 => self.raise_tasklist_entry(4),
+            Key::F6 => self.raise_tasklist_entry(5),
+            Key::F7 => self.raise_tasklist_entry(6),
+            Key::F8 => self.raise_tasklist_entry(7),
+            Key::F9 => self.raise_tasklist_entry(8),
             _ => {}
         }
     }
 
-    fn move_active_client_to_workspace(&self, workspace_index: usize) {
-        let mut stack = self.active_workspace().stack.borrow_mut();
-        let mut tasklist = self.active_workspace().tasklist.borrow_mut();
+    fn handle_key_release(&self, event: &KeyReleaseEvent) {
+        if self.is_super_key(event.detail) {
+            self.set_mod4_held(false);
+        }
+    }
 
-        let Some(client) = stack.pop()
+    fn is_super_key(&self, keycode: u8) -> bool {
+        let keymap = self.app.keymap();
+
+        keymap.get_keycode(Key::SuperL) == Some(keycode)
+            || keymap.get_keycode(Key::SuperR) == Some(keycode)
+    }
+
+    fn set_mod4_held(&self, held: bool) {
+        if self.mod4_held.replace(held) != held {
+            self.app.bottom_panel().notify();
+        }
+    }
+
+    pub fn mod4_held(&self) -> bool {
+        self.mod4_held.get()
+    }
+
+    // Does nothing if there are fewer entries
+    fn raise_tasklist_entry(&self, tasklist_index: usize) {
+        let Some(client_id) = self
+            .active_workspace()
+            .tasklist()
+            .get(tasklist_index)
+            .map(|client| client.id())
         else {
             return;
         };
 
-        self.app.api().unmap_window(client.container_id());
-
-        if let Some(client) = stack.last() {
-            client.notify();
+        if let Some(stack_index) = self.active_workspace().stack_index_of(client_id) {
+            self.raise_client(stack_index);
         }
+    }
 
-        self.app.api().raise_window(client.container_id());
-        self.app.api().raise_window(self.app.top_panel().id());
-        self.app.api().raise_window(self.app.bottom_panel().id());
+    fn toggle_monocle_layout(&self) {
+        let layout = match self.active_workspace().layout() {
+            Layout::Floating => Layout::Monocle,
+            Layout::Monocle => Layout::Floating,
+        };
 
-        self.app
-            .api()
-            .set_focus(stack.last().map(|client| client.id()));
+        self.set_layout(self.active_workspace_index(), layout);
+    }
 
-        let client_tasklist_index = tasklist.iter().position(|c| c.id() == client.id()).unwrap();
+    fn set_layout(&self, workspace_index: usize, layout: Layout) {
+        let workspace = &self.workspaces[workspace_index];
 
-        tasklist.remove(client_tasklist_index);
+        if layout == workspace.layout() {
+            return;
+        }
+
+        let clients = workspace.stack().clone();
+
+        let floating_states = clients
+            .iter()
+            .map(|client| self.remove_client_from_layout(workspace, client))
+            .collect::<Vec<_>>();
+
+        workspace.layout.set(layout);
+
+        for (client, floating_state) in clients.iter().zip(floating_states) {
+            self.add_client_to_layout(workspace, client, floating_state);
+        }
+
+        // Always on top clients have been left under the active one, or have to go back over it
+        if workspace_index == self.active_workspace_index() {
+            if let Some(client) = clients.last() {
+                self.app.api().raise_window(client.container_id());
+            }
+
+            self.raise_above_clients(&clients);
+            self.raise_panels();
+        }
+
+        self.need_redraw.set(true);
+    }
+
+    // Takes the active client out of the workspace's layout or puts it back in
+    fn toggle_active_client_floating(&self) {
+        let workspace = self.active_workspace();
+
+        let Some(client) = workspace.stack().last().cloned()
+        else {
+            return;
+        };
+
+        let floating_state = self.remove_client_from_layout(workspace, &client);
+        client.set_floating(!client.floating());
+        self.add_client_to_layout(workspace, &client, floating_state);
+
+        self.raise_above_clients(&workspace.stack());
+        self.raise_panels();
+
+        self.need_redraw.set(true);
+    }
+
+    // Applies the workspace's layout to a client which joins it. The floating state is whether
+    // the client is maximized and shaded when it's not in the monocle layout
+    fn add_client_to_layout(
+        &self,
+        workspace: &Workspace,
+        client: &Client,
+        floating_state: (bool, bool),
+    ) {
+        if !workspace.arranges(client) {
+            let (maximized, shaded) = floating_state;
+            client.set_maximized(maximized);
+            client.set_shaded(shaded);
+            return;
+        }
+
+        // Only the monocle layout arranges clients so far
+        workspace
+            .floating_states
+            .borrow_mut()
+            .insert(client.id(), floating_state);
+
+        client.set_maximized(true);
+    }
+
+    // Returns the client's floating state, see add_client_to_layout()
+    fn remove_client_from_layout(&self, workspace: &Workspace, client: &Client) -> (bool, bool) {
+        if !workspace.arranges(client) {
+            return (client.maximized(), client.shaded());
+        }
+
+        workspace
+            .floating_states
+            .borrow_mut()
+            .remove(&client.id())
+            .unwrap_or_default()
+    }
+
+    // A one-off arrangement, the clients stay floating. Maximized and shaded ones are restored,
+    // minimized ones are left alone. Goes in tasklist order, row by row.
+    // Doesn't do anything in the monocle layout
+    fn arrange_clients_in_grid(&self) {
+        if self.active_workspace().layout() == Layout::Monocle {
+            return;
+        }
+
+        let clients = self
+            .active_workspace()
+            .tasklist()
+            .iter()
+            .filter(|client| !client.minimized())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if clients.is_empty() {
+            return;
+        }
+
+        let columns = (clients.len() as f64).sqrt().ceil() as u16;
+        let rows = (clients.len() as u16).div_ceil(columns);
+
+        let area_y = top_panel::RESERVED_HEIGHT;
+        let area_width = self.app.api().screen_width();
+        let area_height = self.app.api().screen_height()
+            - top_panel::RESERVED_HEIGHT
+            - bottom_panel::RESERVED_HEIGHT;
+
+        let cell_width = area_width / columns;
+        let cell_height = area_height / rows;
+
+        for (index, client) in clients.iter().enumerate() {
+            let column = index as u16 % columns;
+            let row = index as u16 / columns;
+
+            let (width, height) = apply_size_hints(
+                cell_width.saturating_sub(client::BORDER_WIDTH * 2),
+                cell_height.saturating_sub(client::BORDER_WIDTH * 2 + client::TITLEBAR_HEIGHT),
+                &client.size_hints(),
+                Dimension::Width,
+            );
+
+            client.set_shaded(false);
+            client.set_geometry(
+                (column * cell_width + client::BORDER_WIDTH) as _,
+                (area_y + row * cell_height + client::BORDER_WIDTH + client::TITLEBAR_HEIGHT) as _,
+                width,
+                height,
+                false,
+            );
+        }
+
+        self.need_redraw.set(true);
+    }
+
+    // Maximized clients can't be moved or resized and shaded ones can't be resized,
+    // so the mode isn't entered for them
+    fn enter_input_mode(&self, input_mode: InputMode) {
+        let Some(client) = input_mode.client_id().and_then(|client_id| {
+            self.active_workspace()
+                .stack()
+                .iter()
+                .find(|client| client.id() == client_id)
+                .cloned()
+        })
+        else {
+            return;
+        };
+
+        if client.maximized() || (client.shaded() && matches!(input_mode, InputMode::Resize { .. }))
+        {
+            return;
+        }
+
+        // Only a single client can be in a mode at once
+        self.leave_input_mode();
+        self.input_mode.set(input_mode);
+
+        client.set_resizing(true);
+
+        // Every key has to reach us, not only the bound ones, there'd be no way out otherwise
+        if !self.app.api().grab_keyboard(self.app.api().root()) {
+            self.leave_input_mode();
+            return;
+        }
+
+        self.app.top_panel().notify();
+    }
+
+    fn leave_input_mode(&self) {
+        let Some(client_id) = self.input_mode.replace(InputMode::Normal).client_id()
+        else {
+            return;
+        };
+
+        self.app.api().ungrab_keyboard();
+        self.app.top_panel().notify();
+
+        for workspace in &self.workspaces {
+            if let Some(client) = workspace.stack().iter().find(|c| c.id() == client_id) {
+                client.set_resizing(false);
+            }
+        }
+    }
+
+    pub fn in_input_mode(&self) -> bool {
+        self.input_mode.get() != InputMode::Normal
+    }
+
+    // Shown in the top panel while the mode is on
+    pub fn input_mode_label(&self) -> Option<&'static str> {
+        match self.input_mode.get() {
+            InputMode::Normal => None,
+            InputMode::Resize { .. } => Some("-- RESIZE --"),
+            InputMode::Move { .. } => Some("-- MOVE --"),
+        }
+    }
+
+    // Unit vector for arrows and H/J/K/L
+    fn get_key_direction(&self, keycode: u8) -> Option<(i32, i32)> {
+        let keymap = self.app.keymap();
+        let is_key = |key| keymap.get_keycode(key) == Some(keycode);
+
+        if is_key(Key::Right) || is_key(Key::L) {
+            Some((1, 0))
+        }
+        else if is_key(Key::Left) || is_key(Key::H) {
+            Some((-1, 0))
+        }
+        else if is_key(Key::Down) || is_key(Key::J) {
+            Some((0, 1))
+        }
+        else if is_key(Key::Up) || is_key(Key::K) {
+            Some((0, -1))
+        }
+        else {
+            None
+        }
+    }
+
+    // Arrows or H/J/K/L grow and shrink the client by RESIZE_STEP or its resize increment,
+    // with Shift by a single pixel. The bottom right corner moves, the top left one stays in place
+    fn handle_resize_mode_key_press(&self, event: &KeyPressEvent, client_id: u32) {
+        let keymap = self.app.keymap();
+        let is_key = |key| keymap.get_keycode(key) == Some(event.detail);
+
+        if is_key(Key::Return) || is_key(Key::Escape) {
+            self.leave_input_mode();
+            return;
+        }
+
+        // The client might have been maximized or moved away in the meantime, e.g. with the mouse
+        let Some(client) = self
+            .active_workspace()
+            .stack()
+            .iter()
+            .find(|client| client.id() == client_id)
+            .cloned()
+            .filter(|client| !client.maximized() && !client.shaded())
+        else {
+            self.leave_input_mode();
+            return;
+        };
+
+        let Some((dx, dy)) = self.get_key_direction(event.detail)
+        else {
+            return;
+        };
+
+        let size_hints = client.resize_hints();
+
+        let (step_x, step_y) = if event.state.contains(KeyButMask::SHIFT) {
+            (1, 1)
+        }
+        else {
+            size_hints.increment.unwrap_or((RESIZE_STEP, RESIZE_STEP))
+        };
+
+        let width = (client.width() as i32 + dx * step_x as i32).clamp(1, u16::MAX as _);
+        let height = (client.height() as i32 + dy * step_y as i32).clamp(1, u16::MAX as _);
+
+        let kept = if dx != 0 {
+            Dimension::Width
+        }
+        else {
+            Dimension::Height
+        };
+
+        let (width, height) = apply_size_hints(width as _, height as _, &size_hints, kept);
+        client.set_size(width, height);
+    }
+
+    // Arrows or H/J/K/L move the client by MOVE_STEP, with Shift by a single pixel.
+    // It's kept on screen the same way as when dragged
+    fn handle_move_mode_key_press(&self, event: &KeyPressEvent, client_id: u32) {
+        let keymap = self.app.keymap();
+        let is_key = |key| keymap.get_keycode(key) == Some(event.detail);
+
+        if is_key(Key::Return) || is_key(Key::Escape) {
+            self.leave_input_mode();
+            return;
+        }
+
+        // Same as for resize mode
+        let Some(client) = self
+            .active_workspace()
+            .stack()
+            .iter()
+            .find(|client| client.id() == client_id)
+            .cloned()
+            .filter(|client| !client.maximized())
+        else {
+            self.leave_input_mode();
+            return;
+        };
+
+        let Some((dx, dy)) = self.get_key_direction(event.detail)
+        else {
+            return;
+        };
+
+        let step = if event.state.contains(KeyButMask::SHIFT) {
+            1
+        }
+        else {
+            MOVE_STEP as i16
+        };
+
+        let (x, y) = self.clamp_client_position(
+            &client,
+            client.x() + dx as i16 * step,
+            client.y() + dy as i16 * step,
+        );
+
+        client.set_x(x);
+        client.set_y(y);
+    }
+
+    // Same as cycle_next() but skips workspaces without clients, stays at 'from' if all of them are
+    fn next_workspace_with_windows(&self, from: usize) -> usize {
+        let mut index = from;
+
+        for _ in 0..self.workspaces.len() {
+            index = cycle_next(&self.workspaces, index);
+
+            if !self.workspaces[index].stack().is_empty() {
+                return index;
+            }
+        }
+
+        from
+    }
+
+    fn previous_workspace_with_windows(&self, from: usize) -> usize {
+        let mut index = from;
+
+        for _ in 0..self.workspaces.len() {
+            index = cycle_previous(&self.workspaces, index);
+
+            if !self.workspaces[index].stack().is_empty() {
+                return index;
+            }
+        }
+
+        from
+    }
+
+    // Saves the state and re-executes itself, clients stay alive
+    fn restart(&self) -> ! {
+        self.save_state();
+
+        let args = std::env::args()
+            .map(|s| CString::new(s).unwrap())
+            .collect::<Vec<_>>();
+
+        let Err(error) = execvp(&args[0], &args);
+        panic!("Couldn't restart: {error}");
+    }
+
+    // The state is saved as for a restart, so the next start picks up where this one has left off
+    fn quit(&self) -> ! {
+        self.save_state();
+
+        // Dropping the clients puts their windows back on the root window
+        for workspace in &self.workspaces {
+            workspace.tasklist.borrow_mut().clear();
+            workspace.stack.borrow_mut().clear();
+        }
+
+        self.app.api().flush();
+        std::process::exit(0);
+    }
+
+    fn save_state(&self) {
+        let file = File::create(get_serialized_state_file_path()).unwrap();
+        serde_json::to_writer(BufWriter::new(file), &self.serialize()).unwrap();
+    }
+
+    // Doesn't do anything if it's the active workspace already
+    pub fn move_active_client_to_workspace(&self, workspace_index: usize) {
+        if workspace_index == self.active_workspace_index() {
+            return;
+        }
+
+        let mut stack = self.active_workspace().stack.borrow_mut();
+        let mut tasklist = self.active_workspace().tasklist.borrow_mut();
+
+        let Some(client) = stack.pop()
+        else {
+            return;
+        };
+
+        self.app.api().unmap_window(client.container_id());
+
+        if let Some(client) = stack.last() {
+            client.notify();
+        }
+
+        self.app.api().raise_window(client.container_id());
+        self.raise_panels();
+
+        self.set_focus(&stack);
+
+        let client_tasklist_index = tasklist.iter().position(|c| c.id() == client.id()).unwrap();
+
+        tasklist.remove(client_tasklist_index);
 
         let new_workspace = &self.workspaces[workspace_index];
+        let floating_state = self.remove_client_from_layout(self.active_workspace(), &client);
+        self.add_client_to_layout(new_workspace, &client, floating_state);
         new_workspace.stack.borrow_mut().push(client.clone());
         new_workspace.tasklist.borrow_mut().push(client);
 
@@ -473,6 +1563,64 @@ impl Wm {
         self.app.bottom_panel().notify();
     }
 
+    pub fn move_active_client_to_workspace_and_follow(&self, workspace_index: usize) {
+        self.move_active_client_to_workspace(workspace_index);
+        self.change_active_workspace(workspace_index);
+    }
+
+    // Both follow the client, wrapping around at the ends
+    fn move_active_client_to_next_workspace(&self) {
+        self.move_active_client_to_workspace_and_follow(cycle_next(
+            &self.workspaces,
+            self.active_workspace_index(),
+        ));
+    }
+
+    fn move_active_client_to_previous_workspace(&self) {
+        self.move_active_client_to_workspace_and_follow(cycle_previous(
+            &self.workspaces,
+            self.active_workspace_index(),
+        ));
+    }
+
+    fn swap_active_client_with_previous(&self) {
+        let stack = self.active_workspace().stack();
+
+        let Some((active_client, other_clients)) =
+            stack.split_last().filter(|(client, _)| !client.minimized())
+        else {
+            return;
+        };
+
+        // The stack is ordered by activation, so the previously active client is the closest one
+        // below. Minimized clients aren't visible, so there's nothing to swap with them
+        let Some(previous_client) = other_clients
+            .iter()
+            .rev()
+            .find(|client| !client.minimized())
+        else {
+            return;
+        };
+
+        let (x, y, width, height, maximized) = (
+            active_client.x(),
+            active_client.y(),
+            active_client.width(),
+            active_client.height(),
+            active_client.maximized(),
+        );
+
+        active_client.set_geometry(
+            previous_client.x(),
+            previous_client.y(),
+            previous_client.width(),
+            previous_client.height(),
+            previous_client.maximized(),
+        );
+
+        previous_client.set_geometry(x, y, width, height, maximized);
+    }
+
     fn move_active_client_forward_in_tasklist(&self) {
         let stack = self.active_workspace().stack();
 
@@ -575,22 +1723,64 @@ impl Wm {
         self.raise_client(previous_client_stack_index);
     }
 
-    fn handle_button_press(&self, event: &ButtonPressEvent) {
-        let clients = self.active_workspace().stack.borrow();
+    fn raise_client_in_direction(&self, direction: Direction) {
+        let client_stack_index = {
+            let stack = self.active_workspace().stack();
+
+            // Nothing visible to move from
+            let Some(active_client) = stack.last().filter(|client| !client.minimized())
+            else {
+                return;
+            };
+
+            let geometry = |client: &Client| {
+                (
+                    client.container_x(),
+                    client.container_y(),
+                    client.container_width(),
+                    client.container_height(),
+                )
+            };
+
+            // Minimized clients aren't visible, their geometry is only kept for restoring them
+            let (stack_indices, other_clients): (Vec<_>, Vec<_>) = stack[..stack.len() - 1]
+                .iter()
+                .enumerate()
+                .filter(|(_, client)| !client.minimized())
+                .map(|(stack_index, client)| (stack_index, geometry(client)))
+                .unzip();
 
-        let Some(client_index) = clients
+            let Some(index) =
+                find_closest_in_direction(geometry(active_client), &other_clients, direction)
+            else {
+                return;
+            };
+
+            stack_indices[index]
+        };
+
+        self.raise_client(client_stack_index);
+    }
+
+    fn handle_button_press(&self, event: &ButtonPressEvent) {
+        // A clone rather than a borrow of the stack, raise_client() needs to mutate it
+        let Some((client_index, client)) = self
+            .active_workspace()
+            .stack()
             .iter()
-            .position(|client| client.id() == event.event || client.container_id() == event.event)
+            .enumerate()
+            .find(|(_, client)| client.id() == event.event || client.container_id() == event.event)
+            .map(|(index, client)| (index, client.clone()))
         else {
             return;
         };
 
-        let on_container = clients[client_index].container_id() == event.event;
+        let on_container = client.container_id() == event.event;
         let button = ButtonIndex::from(event.detail);
         let is_mod4 = event.state.contains(KeyButMask::MOD4);
 
         if on_container {
-            if !(button == ButtonIndex::M1 || (button == ButtonIndex::M3 && is_mod4)) {
+            if !(button == ButtonIndex::M1 || button == ButtonIndex::M3) {
                 return;
             }
         }
@@ -598,13 +1788,16 @@ impl Wm {
             self.app.api().allow_pointer_events();
         }
 
-        // raise_client() needs exclusive access to clients so we have to explicitly unlock them
-        drop(clients);
         self.raise_client(client_index);
-        let clients = self.active_workspace().stack.borrow();
-        let client = clients.last().unwrap();
+
+        // raise_client() doesn't do anything if the client is already on top
+        client.set_last_activity(event.time);
 
         if client.maximized() {
+            if button == ButtonIndex::M1 && is_mod4 && !self.active_workspace().arranges(&client) {
+                self.tear_out_maximized_client(&client, event.root_x, event.root_y);
+            }
+
             return;
         }
 
@@ -613,41 +1806,278 @@ impl Wm {
             && (client::BORDER_WIDTH..=(client::BORDER_WIDTH + client::TITLEBAR_HEIGHT))
                 .contains(&(event.event_y as _));
 
+        let border_edges = on_container
+            .then(|| self.get_container_border_edges(&client, event.event_x, event.event_y))
+            .flatten();
+
         match button {
+            ButtonIndex::M1 if !is_mod4 && border_edges.is_some() => {
+                self.start_drag(
+                    &client,
+                    DragKind::Resize(border_edges.unwrap()),
+                    event.root_x as _,
+                    event.root_y as _,
+                    event.state.contains(KeyButMask::SHIFT),
+                );
+            }
+            // The classic window menu, anchored under the icon
+            ButtonIndex::M1
+                if !is_mod4
+                    && on_container
+                    && on_titlebar
+                    && client.icon_range().contains(&(event.event_x as _)) =>
+            {
+                self.app.menu().show_for_icon(
+                    client.id(),
+                    client.container_x() + *client.icon_range().start() as i16,
+                    client.container_y() + (client::BORDER_WIDTH + client::TITLEBAR_HEIGHT) as i16,
+                    event.time,
+                );
+            }
             ButtonIndex::M1 if is_mod4 || (on_container && on_titlebar) => {
-                self.drag_state.set(Some(DragState {
-                    kind: DragKind::Move,
-                    x: event.root_x as _,
-                    y: event.root_y as _,
-                }));
+                self.start_drag(
+                    &client,
+                    DragKind::Move,
+                    event.root_x as _,
+                    event.root_y as _,
+                    event.state.contains(KeyButMask::SHIFT),
+                );
             }
-            ButtonIndex::M3 if is_mod4 => {
+            // Mod4 makes it the resize gesture below, even on the titlebar
+            ButtonIndex::M3 if !is_mod4 && on_container && on_titlebar => {
+                self.app
+                    .menu()
+                    .show(client.id(), event.root_x, event.root_y, event.time);
+            }
+            ButtonIndex::M3 if is_mod4 && !client.shaded() => {
                 let x = (client.x() + client.width() as i16) as u16;
                 let y = (client.y() + client.height() as i16) as u16;
 
                 self.app.api().move_pointer(x, y);
-
-                self.drag_state.set(Some(DragState {
-                    kind: DragKind::Resize,
+                self.start_drag(
+                    &client,
+                    DragKind::Resize(ResizeEdges::BOTTOM_RIGHT),
                     x,
                     y,
-                }));
+                    event.state.contains(KeyButMask::SHIFT),
+                );
             }
             _ => {}
         }
     }
 
+    // The pointer is grabbed for the whole drag, even if it was started by a press on the container.
+    // This way the release reaches us wherever the pointer ends up, e.g. over a panel.
+    // Shift makes it an outlined drag, regardless of config::OUTLINE_RESIZE and OUTLINE_MOVE
+    fn start_drag(&self, client: &Client, kind: DragKind, x: u16, y: u16, shift: bool) {
+        let cursor = self.drag_cursor(kind);
+
+        let outlined = shift
+            || match kind {
+                DragKind::Move => config::OUTLINE_MOVE,
+                DragKind::Resize(_) => config::OUTLINE_RESIZE,
+            };
+
+        let deferred = outlined || config::DEFER_DRAG_GEOMETRY;
+
+        if config::CONFINE_POINTER_DURING_DRAG {
+            self.app
+                .api()
+                .confine_pointer_to_screen(client.container_id(), cursor);
+        }
+        else {
+            self.app.api().grab_pointer(client.container_id(), cursor);
+        }
+
+        self.drag_state.set(Some(DragState {
+            client_id: client.id(),
+            kind,
+            x,
+            y,
+            start_x: x,
+            start_y: y,
+            start_geometry: (client.x(), client.y(), client.width(), client.height()),
+            deferred,
+            outlined,
+            geometry: (client.x(), client.y(), client.width(), client.height()),
+        }));
+
+        if outlined {
+            self.show_drag_outline(
+                client,
+                (client.x(), client.y(), client.width(), client.height()),
+            );
+        }
+    }
+
+    // Restores the client's floating geometry under the pointer and starts moving it.
+    // The pointer keeps its relative horizontal position and ends up in the middle of the titlebar
+    fn tear_out_maximized_client(&self, client: &Client, pointer_x: i16, pointer_y: i16) {
+        let ratio = (pointer_x - client.container_x()) as f64 / client.container_width() as f64;
+        let container_width = client.width() + client::BORDER_WIDTH * 2;
+        let container_x = pointer_x - (ratio * container_width as f64).round() as i16;
+        let container_y = pointer_y - (client::BORDER_WIDTH + client::TITLEBAR_HEIGHT / 2) as i16;
+
+        let (x, y) = self.clamp_client_position(
+            client,
+            container_x + client::BORDER_WIDTH as i16,
+            container_y + (client::BORDER_WIDTH + client::TITLEBAR_HEIGHT) as i16,
+        );
+
+        client.set_geometry(x, y, client.width(), client.height(), false);
+
+        self.start_drag(
+            client,
+            DragKind::Move,
+            pointer_x as _,
+            pointer_y as _,
+            false,
+        );
+    }
+
+    fn stop_drag(&self) {
+        let Some(state) = self.drag_state.take()
+        else {
+            return;
+        };
+
+        self.app.api().ungrab_pointer();
+
+        if !state.deferred {
+            return;
+        }
+
+        if state.outlined {
+            self.outline.hide();
+        }
+
+        let stack = self.active_workspace().stack();
+
+        if let Some(client) = stack.iter().find(|client| client.id() == state.client_id) {
+            set_dragged_client_geometry(client, state.geometry);
+        }
+    }
+
+    // Unlike stop_drag(), a deferred drag is dropped without touching the client
+    fn cancel_drag(&self) {
+        if self.drag_state.take().is_some() {
+            self.app.api().ungrab_pointer();
+            self.outline.hide();
+        }
+    }
+
+    // The geometry is of the client, the outline goes around where its container would be
+    fn show_drag_outline(&self, client: &Client, (x, y, width, height): (i16, i16, u16, u16)) {
+        let container_height = if client.shaded() {
+            client.container_height()
+        }
+        else {
+            client.container_height() - client.height() + height
+        };
+
+        self.outline.show(
+            x + client.container_x() - client.x(),
+            y + client.container_y() - client.y(),
+            client.container_width() - client.width() + width,
+            container_height,
+        );
+    }
+
+    fn drag_cursor(&self, kind: DragKind) -> u32 {
+        let cursors = &self.app.api().cursors;
+
+        let DragKind::Resize(edges) = kind
+        else {
+            return cursors.fleur;
+        };
+
+        match (edges.left, edges.right, edges.top, edges.bottom) {
+            (true, _, true, _) => cursors.top_left_corner,
+            (_, true, true, _) => cursors.top_right_corner,
+            (true, _, _, true) => cursors.bottom_left_corner,
+            (_, true, _, true) => cursors.bottom_right_corner,
+            (true, _, _, _) => cursors.left_side,
+            (_, true, _, _) => cursors.right_side,
+            (_, _, true, _) => cursors.top_side,
+            _ => cursors.bottom_side,
+        }
+    }
+
+    // Edges of the container's border which 'x' and 'y' (relative to the container) are on
+    fn get_container_border_edges(&self, client: &Client, x: i16, y: i16) -> Option<ResizeEdges> {
+        if client.maximized() || client.shaded() {
+            return None;
+        }
+
+        let border_width = client::BORDER_WIDTH as i16;
+
+        let edges = ResizeEdges {
+            left: x < border_width,
+            right: x >= client.container_width() as i16 - border_width,
+            top: y < border_width,
+            bottom: y >= client.container_height() as i16 - border_width,
+        };
+
+        (edges.left || edges.right || edges.top || edges.bottom).then_some(edges)
+    }
+
+    fn update_container_cursor(&self, event: &MotionNotifyEvent) {
+        let clients = self.active_workspace().stack();
+
+        let Some(client) = clients
+            .iter()
+            .find(|client| client.container_id() == event.event)
+        else {
+            return;
+        };
+
+        let cursor = match self.get_container_border_edges(client, event.event_x, event.event_y) {
+            Some(edges) => self.drag_cursor(DragKind::Resize(edges)),
+            None => x11rb::NONE,
+        };
+
+        self.app
+            .api()
+            .set_window_cursor(client.container_id(), cursor);
+    }
+
+    // The grab of a drag ends by itself if its container becomes unviewable, without a release
+    fn handle_leave_notify(&self, event: &LeaveNotifyEvent) {
+        if event.mode == NotifyMode::UNGRAB && self.is_dragged_container(event.event) {
+            self.cancel_drag();
+        }
+    }
+
+    // Something else has taken the focus from the dragged client, e.g. a keyboard shortcut
+    fn handle_focus_out(&self, event: &FocusOutEvent) {
+        if event.detail != NotifyDetail::INFERIOR && self.is_dragged_container(event.event) {
+            self.cancel_drag();
+        }
+    }
+
+    fn is_dragged_container(&self, window: u32) -> bool {
+        let Some(state) = self.drag_state.get()
+        else {
+            return false;
+        };
+
+        self.active_workspace()
+            .stack()
+            .iter()
+            .any(|client| client.id() == state.client_id && client.container_id() == window)
+    }
+
     fn handle_motion_notify(&self, event: &MotionNotifyEvent) {
         let Some(state) = self.drag_state.get()
         else {
+            self.update_container_cursor(event);
             return;
         };
 
         let clients = self.active_workspace().stack.borrow();
 
-        let Some(client) = clients
-            .iter()
-            .find(|client| client.id() == event.event || client.container_id() == event.event)
+        // The events keep coming to the container the drag has started on, whatever is under the pointer
+        let Some(client) = clients.iter().find(|client| client.id() == state.client_id)
         else {
             return;
         };
@@ -655,28 +2085,173 @@ impl Wm {
         let dx = event.root_x - state.x as i16;
         let dy = event.root_y - state.y as i16;
 
-        match state.kind {
-            DragKind::Move => self.handle_drag_move(client, dx, dy),
-            DragKind::Resize => self.handle_drag_resize(client, dx, dy),
+        let geometry = match state.kind {
+            DragKind::Move => self.get_drag_move_geometry(client, &state, dx, dy),
+            DragKind::Resize(edges) => self.get_drag_resize_geometry(
+                client,
+                &state,
+                edges,
+                event.root_x,
+                event.root_y,
+                dx,
+                dy,
+            ),
+        };
+
+        if state.outlined {
+            self.show_drag_outline(client, geometry);
+        }
+
+        if !state.deferred {
+            set_dragged_client_geometry(client, geometry);
         }
 
         self.drag_state.set(Some(DragState {
-            kind: state.kind,
             x: event.root_x as _,
             y: event.root_y as _,
+            geometry,
+            ..state
         }));
     }
 
-    fn handle_drag_move(&self, client: &Client, dx: i16, dy: i16) {
-        client.set_x(client.x() + dx);
-        client.set_y(client.y() + dy);
-    }
+    fn get_drag_move_geometry(
+        &self,
+        client: &Client,
+        state: &DragState,
+        dx: i16,
+        dy: i16,
+    ) -> (i16, i16, u16, u16) {
+        let (x, y, width, height) = state.geometry;
+        let (x, y) = self.clamp_client_position(client, x + dx, y + dy);
+
+        (x, y, width, height)
+    }
+
+    // Keeps enough of the titlebar on screen to be able to drag the window back
+    fn clamp_client_position(&self, client: &Client, x: i16, y: i16) -> (i16, i16) {
+        let min_visible_width = config::MIN_VISIBLE_TITLEBAR_WIDTH as i16;
+
+        let x = x
+            .min(self.app.api().screen_width() as i16 - min_visible_width)
+            .max(min_visible_width - client.width() as i16);
+
+        // Top of the container stays below the top panel
+        // and bottom of the titlebar stays above the bottom panel
+        let y = y
+            .min(self.app.api().screen_height() as i16 - bottom_panel::RESERVED_HEIGHT as i16)
+            .max(
+                (top_panel::RESERVED_HEIGHT + client::BORDER_WIDTH + client::TITLEBAR_HEIGHT) as _,
+            );
+
+        (x, y)
+    }
+
+    fn get_drag_resize_geometry(
+        &self,
+        client: &Client,
+        state: &DragState,
+        edges: ResizeEdges,
+        x: i16,
+        y: i16,
+        dx: i16,
+        dy: i16,
+    ) -> (i16, i16, u16, u16) {
+        let (start_x, start_y, start_width, start_height) = state.start_geometry;
+        let total_dx = x - state.start_x as i16;
+        let total_dy = y - state.start_y as i16;
+
+        let width = if edges.right {
+            start_width as i16 + total_dx
+        }
+        else if edges.left {
+            start_width as i16 - total_dx
+        }
+        else {
+            start_width as i16
+        };
+
+        let height = if edges.bottom {
+            start_height as i16 + total_dy
+        }
+        else if edges.top {
+            start_height as i16 - total_dy
+        }
+        else {
+            start_height as i16
+        };
+
+        // Aspect ratio is kept by adjusting the dimension which isn't being dragged,
+        // or the one the pointer has moved along the least when dragging a corner
+        let horizontal = edges.left || edges.right;
+        let vertical = edges.top || edges.bottom;
+
+        let kept = if horizontal && (!vertical || dx.abs() >= dy.abs()) {
+            Dimension::Width
+        }
+        else {
+            Dimension::Height
+        };
+
+        let (width, height) = apply_size_hints(
+            width.max(1) as _,
+            height.max(1) as _,
+            &client.resize_hints(),
+            kept,
+        );
+
+        // Opposite edges stay in place
+        let x = if edges.left {
+            start_x + start_width as i16 - width as i16
+        }
+        else {
+            start_x
+        };
+
+        let y = if edges.top {
+            start_y + start_height as i16 - height as i16
+        }
+        else {
+            start_y
+        };
+
+        (x, y, width, height)
+    }
+
+    fn handle_client_message(&self, event: &ClientMessageEvent) {
+        if event.type_ != self.app.api().atoms._NET_WM_MOVERESIZE {
+            return;
+        }
+
+        let stack = self.active_workspace().stack();
+
+        let Some(client) = stack.iter().find(|client| client.id() == event.window)
+        else {
+            return;
+        };
+
+        // x_root, y_root, direction, button, source indication
+        let [_, _, direction, _, _] = event.data.as_data32();
 
-    fn handle_drag_resize(&self, client: &Client, dx: i16, dy: i16) {
-        let width = (client.width() as i16 + dx).max(1) as _;
-        let height = (client.height() as i16 + dy).max(1) as _;
+        let kind = match direction {
+            8 => DragKind::Move,
+            11 => {
+                self.cancel_drag();
+                return;
+            }
+            _ => match ResizeEdges::from_net_wm_moveresize_direction(direction) {
+                Some(edges) => DragKind::Resize(edges),
+                // Keyboard driven moving and resizing isn't supported
+                None => return,
+            },
+        };
 
-        client.set_size(width, height);
+        if client.maximized() {
+            return;
+        }
+
+        // Coordinates in the message are from the moment of the press, which might've been a while ago
+        let (x, y) = self.app.api().query_pointer();
+        self.start_drag(client, kind, x as _, y as _, false);
     }
 
     fn handle_property_notify(&self, event: &PropertyNotifyEvent) {
@@ -701,27 +2276,31 @@ impl Wm {
 
         if event.atom == u32::from(AtomEnum::WM_CLASS) {
             client.set_class(self.app.api().get_window_class(client.id()));
-            self.app.top_panel().notify();
         }
         else if event.atom == self.app.api().atoms._NET_WM_NAME {
             client.set_title(self.app.api().get_window_title(client.id()));
-
-            if workspace_index == self.active_workspace_index.get() {
-                self.app.bottom_panel().notify();
-            }
+        }
+        else if event.atom == u32::from(AtomEnum::WM_NORMAL_HINTS) {
+            client.set_size_hints(self.app.api().get_window_size_hints(client.id()));
         }
         else if event.atom == self.app.api().atoms._NET_WM_ICON {
             client.set_icon(self.app.api().get_window_icon(client.id()));
-
-            if workspace_index == self.active_workspace_index.get() {
-                self.app.bottom_panel().notify();
-            }
+        }
+        else if event.atom == u32::from(AtomEnum::WM_HINTS)
+            || event.atom == self.app.api().atoms.WM_PROTOCOLS
+        {
+            client.update_focus_model();
+        }
+        else if cfg!(debug_assertions) {
+            eprintln!(
+                "Ignored change of {:?} on {}",
+                self.app.api().atom_name(event.atom),
+                event.window
+            );
         }
     }
 
     pub fn handle_configure_request(&self, event: &ConfigureRequestEvent) {
-        dbg!(event);
-
         let Some((workspace, client_stack_index)) = self.workspaces.iter().find_map(|workspace| {
             workspace
                 .stack
@@ -743,7 +2322,20 @@ impl Wm {
 
         let stack = workspace.stack();
         let client = stack[client_stack_index].deref();
+
+        let old_size = (client.width(), client.height());
         client.set_size(event.width, event.height);
+
+        let (shift_x, shift_y) = gravity_shift(
+            client.size_hints().gravity,
+            old_size,
+            (client.width(), client.height()),
+        );
+
+        if (shift_x, shift_y) != (0, 0) {
+            client.set_x(client.x() + shift_x);
+            client.set_y(client.y() + shift_y);
+        }
     }
 
     pub fn change_active_workspace(&self, index: usize) {
@@ -751,50 +2343,342 @@ impl Wm {
             return;
         }
 
+        self.need_redraw.set(true);
+
+        // A new switch cancels the transition in progress
+        self.finish_workspace_transition();
+
         let workspace = &self.workspaces[index];
 
+        let direction = if index > self.active_workspace_index() {
+            1
+        }
+        else {
+            -1
+        };
+
         for client in workspace.stack.borrow().iter().rev() {
-            self.app.api().map_window(client.container_id());
+            // Incoming clients start right beyond the screen edge
+            if config::WORKSPACE_SWITCH_ANIMATION {
+                self.app.api().set_window_x(
+                    client.container_id(),
+                    client.container_x() + direction * self.app.api().screen_width() as i16,
+                );
+            }
+
+            if !client.minimized() {
+                self.app.api().map_window(client.container_id());
+            }
+
             client.notify();
         }
 
-        self.app
-            .api()
-            .set_focus(workspace.stack.borrow().last().map(|client| client.id()));
+        if config::WORKSPACE_SWITCH_ANIMATION {
+            self.workspace_transition.set(Some(WorkspaceTransition {
+                from: self.active_workspace_index(),
+                direction,
+                started_at: Instant::now(),
+            }));
 
-        for client in self.active_workspace().stack.borrow().iter() {
-            self.app.api().unmap_window(client.container_id());
+            // Clicks mustn't land on windows sliding under the pointer
+            self.app
+                .api()
+                .grab_pointer(self.app.api().root(), self.app.api().cursors.left_ptr);
+        }
+        else {
+            for client in self.active_workspace().stack.borrow().iter() {
+                self.app.api().unmap_window(client.container_id());
+            }
         }
 
         self.active_workspace_index.set(index);
+        self.wallpaper.show(index);
+
+        let last_focused = *workspace.last_focused.borrow();
+
+        let last_focused_stack_index = last_focused.and_then(|id| {
+            workspace
+                .stack()
+                .iter()
+                .position(|client| client.id() == id)
+        });
+
+        // The client might have been closed or moved to another workspace in the meantime
+        match last_focused_stack_index {
+            Some(stack_index) => {
+                self.raise_client(stack_index);
+                self.refocus();
+            }
+            None => self.set_focus(&workspace.stack()),
+        }
+
+        // raise_client() doesn't do anything if the client is on top already
+        if let Some(client) = workspace.stack().last() {
+            self.warp_pointer_to_client(client);
+        }
+
         self.app.top_panel().notify();
         self.app.bottom_panel().notify();
     }
 
+    fn advance_workspace_transition(&self) {
+        let Some(transition) = self.workspace_transition.get()
+        else {
+            return;
+        };
+
+        let progress = transition.started_at.elapsed().as_secs_f64()
+            / config::WORKSPACE_SWITCH_ANIMATION_DURATION.as_secs_f64();
+
+        if progress >= 1.0 {
+            self.finish_workspace_transition();
+            return;
+        }
+
+        let screen_width = self.app.api().screen_width() as i16;
+        let eased = 1.0 - (1.0 - progress).powi(3);
+        let offset = (eased * screen_width as f64).round() as i16 * transition.direction;
+
+        for client in self.workspaces[transition.from].stack().iter() {
+            self.app
+                .api()
+                .set_window_x(client.container_id(), client.container_x() - offset);
+        }
+
+        for client in self.active_workspace().stack().iter() {
+            self.app.api().set_window_x(
+                client.container_id(),
+                client.container_x() + transition.direction * screen_width - offset,
+            );
+        }
+    }
+
+    // Puts everything where it belongs immediately
+    fn finish_workspace_transition(&self) {
+        let Some(transition) = self.workspace_transition.take()
+        else {
+            return;
+        };
+
+        for client in self.workspaces[transition.from].stack().iter() {
+            self.app.api().unmap_window(client.container_id());
+
+            self.app
+                .api()
+                .set_window_x(client.container_id(), client.container_x());
+        }
+
+        for client in self.active_workspace().stack().iter() {
+            self.app
+                .api()
+                .set_window_x(client.container_id(), client.container_x());
+        }
+
+        self.app.api().ungrab_pointer();
+    }
+
     pub fn raise_client(&self, stack_index: usize) {
         let mut clients = self.active_workspace().stack.borrow_mut();
 
-        if stack_index == clients.len() - 1 {
+        if !can_raise(&clients, stack_index, |client| client.minimized()) {
             return;
         }
 
+        self.need_redraw.set(true);
+
         let client = clients.remove(stack_index);
 
         if let Some(client) = clients.last() {
             client.notify();
         }
 
+        if client.minimized() {
+            client.set_minimized(false);
+            self.app.api().map_window(client.container_id());
+        }
+
         self.app.api().raise_window(client.container_id());
-        self.app.api().raise_window(self.app.top_panel().id());
-        self.app.api().raise_window(self.app.bottom_panel().id());
-        self.app.api().set_focus(client.id());
+        self.raise_above_clients(&clients);
+        self.raise_panels();
 
+        client.set_urgent(false);
+        client.set_last_activity(self.last_input_time.get());
         client.notify();
+        self.warp_pointer_to_client(&client);
+
+        if config::RAISE_FLASH {
+            self.start_animation(&client, AnimationKind::RaiseFlash);
+        }
+
         clients.push(client);
+        self.set_focus(&clients);
+
+        self.app.bottom_panel().notify();
+    }
+
+    fn warp_pointer_to_client(&self, client: &Client) {
+        if !config::WARP_POINTER_ON_FOCUS || client.minimized() {
+            return;
+        }
+
+        if self.app.api().is_pointer_in_window(client.container_id()) {
+            return;
+        }
+
+        self.app.api().move_pointer(
+            (client.container_x() + client.container_width() as i16 / 2).max(0) as _,
+            (client.container_y() + client.container_height() as i16 / 2).max(0) as _,
+        );
+    }
+
+    // Puts everything which isn't a client back over the clients after one got raised: windows
+    // from config::UNMANAGED_CLASSES in their layer, the panels, and hot corners above it all
+    pub fn raise_panels(&self) {
+        self.raise_unmanaged_windows(UnmanagedLayer::BelowPanels);
+        self.app.top_panel().raise();
+        self.app.bottom_panel().raise();
+        self.raise_unmanaged_windows(UnmanagedLayer::AbovePanels);
+        self.app.hot_corners().raise();
+    }
+
+    fn raise_unmanaged_windows(&self, layer: UnmanagedLayer) {
+        for (id, _) in self
+            .unmanaged_windows
+            .borrow()
+            .iter()
+            .filter(|(_, window_layer)| *window_layer == layer)
+        {
+            self.app.api().raise_window(*id);
+        }
+    }
+
+    // Called after raising a container, so the ones marked as always on top stay over it
+    // In the monocle layout it's the floating ones instead, they'd be lost behind the others otherwise
+    fn raise_above_clients(&self, stack: &[Rc<Client>]) {
+        let layout = self.active_workspace().layout();
+
+        let keep_above = |client: &&Rc<Client>| match layout {
+            Layout::Floating => client.above(),
+            Layout::Monocle => client.floating(),
+        };
+
+        for client in stack.iter().filter(keep_above) {
+            self.app.api().raise_window(client.container_id());
+        }
+    }
+
+    // The client goes to the bottom of the stack, and the focus to the one which is on top now
+    fn minimize_client(&self, stack_index: usize) {
+        let mut stack = self.active_workspace().stack.borrow_mut();
+
+        if stack_index >= stack.len() || stack[stack_index].minimized() {
+            return;
+        }
+
+        self.need_redraw.set(true);
+
+        let client = stack.remove(stack_index);
+        client.set_minimized(true);
+        self.app.api().unmap_window(client.container_id());
+        stack.insert(0, client);
+
+        let active_client = stack.last().filter(|client| !client.minimized());
+
+        if let Some(client) = active_client {
+            client.notify();
+        }
 
+        self.set_focus(&stack);
         self.app.bottom_panel().notify();
     }
 
+    pub fn handle_menu_action(&self, client_id: u32, action: MenuAction) {
+        // The client might have been closed or moved away while the menu was shown
+        let Some(stack_index) = self.active_workspace().stack_index_of(client_id)
+        else {
+            return;
+        };
+
+        match action {
+            MenuAction::Close => self.app.api().ask_window_to_close(client_id),
+            MenuAction::ToggleMaximized => {
+                let client = self.active_workspace().stack()[stack_index].clone();
+
+                if !self.active_workspace().arranges(&client) {
+                    client.set_maximized(!client.maximized());
+                }
+
+                self.raise_client(stack_index);
+            }
+            MenuAction::ToggleShaded => {
+                let client = self.active_workspace().stack()[stack_index].clone();
+                client.set_shaded(!client.shaded());
+            }
+            MenuAction::Minimize => self.minimize_client(stack_index),
+            MenuAction::Move => {
+                self.raise_client(stack_index);
+                self.enter_input_mode(InputMode::Move { client_id });
+            }
+            MenuAction::Resize => {
+                self.raise_client(stack_index);
+                self.enter_input_mode(InputMode::Resize { client_id });
+            }
+            MenuAction::ToggleAbove => {
+                let client = self.active_workspace().stack()[stack_index].clone();
+                client.set_above(!client.above());
+
+                if client.above() {
+                    self.app.api().raise_window(client.container_id());
+                    self.raise_panels();
+                }
+            }
+            MenuAction::MoveToWorkspace(workspace_index) => {
+                if workspace_index != self.active_workspace_index() {
+                    self.raise_client(stack_index);
+                    self.move_active_client_to_workspace(workspace_index);
+                }
+            }
+        }
+    }
+
+    // The focus policy, every change of the focus goes through here. Focuses the topmost client
+    // of the stack which wants the focus, skipping minimized ones and ones which don't accept
+    // input at all, like some docks and OSDs. Without such a client the focus goes to a window of
+    // ours, never to the root. Follows the ICCCM input models: clients which set the focus
+    // themselves get WM_TAKE_FOCUS, with or without us setting it too
+    fn set_focus(&self, stack: &[Rc<Client>]) {
+        let client = stack.iter().rev().find(|client| {
+            can_focus(
+                client.minimized(),
+                client.accepts_input(),
+                client.takes_focus(),
+            )
+        });
+
+        match client {
+            Some(client) => {
+                if client.accepts_input() {
+                    self.app.api().set_focus(client.id());
+                }
+
+                if client.takes_focus() {
+                    self.app
+                        .api()
+                        .ask_window_to_take_focus(client.id(), self.last_input_time.get());
+                }
+            }
+            None => self.app.api().set_focus(None),
+        }
+
+        *self.active_workspace().last_focused.borrow_mut() = client.map(|client| client.id());
+    }
+
+    // Gives the focus back to the client on top, e.g. once a popup which has taken it is gone.
+    // Callers which hold the stack borrowed use set_focus() with it instead
+    pub fn refocus(&self) {
+        self.set_focus(&self.active_workspace().stack());
+    }
+
     pub fn active_workspace_index(&self) -> usize {
         self.active_workspace_index.get()
     }
@@ -807,21 +2691,109 @@ impl Wm {
         &self.workspaces[self.active_workspace_index.get()]
     }
 
+    // Clients redraw their frames themselves, but only if they get walked over
+    pub fn handle_client_change(&self, _change: &ClientChange) {
+        self.need_redraw.set(true);
+    }
+
     pub fn handle_event(&self, event: &Event) {
+        // The rest only deal with drags and the pointer, or don't concern us at all.
+        // Geometry and state changes get here through handle_client_change() anyway
+        if matches!(
+            event,
+            Event::MapRequest(_)
+                | Event::UnmapNotify(_)
+                | Event::ReparentNotify(_)
+                | Event::KeyPress(_)
+                | Event::ButtonPress(_)
+                | Event::ButtonRelease(_)
+                | Event::PropertyNotify(_)
+                | Event::ConfigureRequest(_)
+                | Event::ClientMessage(_)
+        ) {
+            self.need_redraw.set(true);
+        }
+
         match event {
             Event::MapRequest(event) => self.handle_map_request(event),
             Event::UnmapNotify(event) => self.handle_unmap_notify(event),
-            Event::KeyPress(event) => self.handle_key_press(event),
-            Event::ButtonPress(event) => self.handle_button_press(event),
+            Event::ReparentNotify(event) => self.handle_reparent_notify(event),
+            Event::KeyPress(event) => {
+                self.last_input_time.set(event.time);
+                self.handle_key_press(event);
+            }
+            Event::KeyRelease(event) => self.handle_key_release(event),
+            Event::ButtonPress(event) => {
+                self.last_input_time.set(event.time);
+                self.handle_button_press(event);
+            }
             Event::MotionNotify(event) => self.handle_motion_notify(event),
-            Event::ButtonRelease(_) => self.drag_state.set(None),
+            // Whatever window it's reported on, it's the end of the drag
+            Event::ButtonRelease(_) => self.stop_drag(),
+            Event::LeaveNotify(event) => self.handle_leave_notify(event),
+            Event::FocusOut(event) => self.handle_focus_out(event),
             Event::PropertyNotify(event) => self.handle_property_notify(event),
             Event::ConfigureRequest(event) => self.handle_configure_request(event),
+            Event::ClientMessage(event) => self.handle_client_message(event),
             _ => {}
         }
     }
 
+    // How long we may wait for events before the next animation frame
+    pub fn timeout(&self) -> Option<Duration> {
+        let animating =
+            !self.animations.borrow().is_empty() || self.workspace_transition.get().is_some();
+
+        animating.then_some(animation::FRAME_INTERVAL)
+    }
+
+    // Replaces an animation of the same kind which is still playing on the client
+    fn start_animation(&self, client: &Client, kind: AnimationKind) {
+        let mut animations = self.animations.borrow_mut();
+
+        animations
+            .retain(|animation| animation.client_id() != client.id() || animation.kind() != kind);
+
+        animations.push(Animation::new(self.app.api(), client, kind));
+    }
+
+    fn advance_animations(&self) {
+        let mut animations = self.animations.borrow_mut();
+
+        if animations.is_empty() {
+            return;
+        }
+
+        // Flashing frames have to be redrawn
+        self.need_redraw.set(true);
+
+        animations.retain(|animation| {
+            let client = self.workspaces.iter().find_map(|workspace| {
+                workspace
+                    .stack()
+                    .iter()
+                    .find(|client| client.id() == animation.client_id())
+                    .cloned()
+            });
+
+            // The window might have been closed already
+            let Some(client) = client
+            else {
+                return false;
+            };
+
+            animation.advance(self.app.api(), &client)
+        });
+    }
+
     pub fn request_redraw(&self) {
+        self.advance_animations();
+        self.advance_workspace_transition();
+
+        if !self.need_redraw.take() {
+            return;
+        }
+
         let clients = self.active_workspace().stack.borrow();
 
         for (index, client) in clients.iter().enumerate() {
@@ -829,8 +2801,32 @@ impl Wm {
         }
     }
 
+    // Same shape as the state saved for restarts, as JSON
+    pub fn dump_state(&self) -> String {
+        serde_json::to_string(&self.serialize()).unwrap()
+    }
+
+    // Clients from all workspaces as a JSON array, the most recently active first if 'recent'
+    pub fn dump_clients(&self, recent: bool) -> String {
+        let now = self.last_input_time.get();
+
+        let mut clients = self
+            .serialize()
+            .workspaces
+            .into_iter()
+            .flat_map(|workspace| workspace.stack)
+            .collect::<Vec<_>>();
+
+        if recent {
+            clients.sort_by_key(|client| milliseconds_since(now, client.last_activity));
+        }
+
+        serde_json::to_string(&clients).unwrap()
+    }
+
     fn serialize(&self) -> SerializedState {
         SerializedState {
+            version: SERIALIZED_STATE_VERSION,
             active_workspace_index: self.active_workspace_index(),
             workspaces: self
                 .workspaces
@@ -839,13 +2835,18 @@ impl Wm {
                     stack: workspace
                         .stack()
                         .iter()
-                        .map(|client| SerializedClient {
-                            id: client.id(),
-                            x: client.x(),
-                            y: client.y(),
-                            width: client.width(),
-                            height: client.height(),
-                            maximized: client.maximized(),
+                        .map(|client| {
+                            let mut serialized_client = SerializedClient::from(client.deref());
+
+                            // The layout gets applied again when restoring
+                            if let Some((maximized, shaded)) =
+                                workspace.floating_states.borrow().get(&client.id())
+                            {
+                                serialized_client.maximized = *maximized;
+                                serialized_client.shaded = *shaded;
+                            }
+
+                            serialized_client
                         })
                         .collect(),
                     tasklist: workspace
@@ -853,6 +2854,8 @@ impl Wm {
                         .iter()
                         .map(|client| client.id())
                         .collect(),
+                    last_focused: *workspace.last_focused.borrow(),
+                    layout: Some(workspace.layout()),
                 })
                 .collect::<Vec<_>>()
                 .try_into()
@@ -861,6 +2864,167 @@ impl Wm {
     }
 }
 
+fn get_unmanaged_layer(class: Option<&str>) -> Option<UnmanagedLayer> {
+    config::UNMANAGED_CLASSES
+        .iter()
+        .find(|(unmanaged_class, _)| class == Some(*unmanaged_class))
+        .map(|(_, layer)| *layer)
+}
+
+// Windows which were recreated while we were restarting have new ids. Saved clients whose windows
+// are gone are matched with new windows by class and the beginning of the title, in the order
+// they were saved, and take over their ids. This way they keep their workspace, geometry
+// and position in the tasklist
+fn match_recreated_windows(
+    serialized_workspaces: &mut [SerializedWorkspace; 9],
+    snapshots: &HashMap<u32, WindowSnapshot>,
+) {
+    let serialized_ids = serialized_workspaces
+        .iter()
+        .flat_map(|workspace| workspace.stack.iter().map(|client| client.id))
+        .collect::<HashSet<_>>();
+
+    let mut new_ids = snapshots
+        .iter()
+        .filter(|(id, snapshot)| {
+            !serialized_ids.contains(id)
+                && snapshot.attributes.map_state != MapState::UNMAPPED
+                && !snapshot.attributes.override_redirect
+        })
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+
+    // Ids grow as windows get created, so older windows take over older clients
+    new_ids.sort();
+
+    let title_prefix = |title: &Option<String>| {
+        title.as_deref().map(|title| {
+            title
+                .chars()
+                .take(RESTORED_TITLE_PREFIX_LENGTH)
+                .collect::<String>()
+        })
+    };
+
+    for workspace in serialized_workspaces {
+        for client in &mut workspace.stack {
+            if snapshots.contains_key(&client.id) || client.class.is_none() {
+                continue;
+            }
+
+            let Some(new_id_index) = new_ids.iter().position(|id| {
+                let snapshot = &snapshots[id];
+                snapshot.class == client.class
+                    && title_prefix(&snapshot.title) == title_prefix(&client.title)
+            })
+            else {
+                continue;
+            };
+
+            let old_id = client.id;
+            client.id = new_ids.remove(new_id_index);
+
+            for id in &mut workspace.tasklist {
+                if *id == old_id {
+                    *id = client.id;
+                }
+            }
+
+            if workspace.last_focused == Some(old_id) {
+                workspace.last_focused = Some(client.id);
+            }
+        }
+    }
+}
+
+// Either we set the focus or the client takes it itself when asked to, see WM_TAKE_FOCUS.
+// Clients which do neither, e.g. some panels and docks, are skipped
+fn can_focus(minimized: bool, accepts_input: bool, takes_focus: bool) -> bool {
+    !minimized && (accepts_input || takes_focus)
+}
+
+// The index might come from a panel layout captured before the stack has changed.
+// A minimized client can be on top if all of them are, it still has to be restored
+fn can_raise<T>(stack: &[T], stack_index: usize, minimized: impl Fn(&T) -> bool) -> bool {
+    match stack.get(stack_index) {
+        Some(client) => stack_index + 1 < stack.len() || minimized(client),
+        None => false,
+    }
+}
+
+// Only what has changed, every change is a request to the server and makes the client redraw
+fn set_dragged_client_geometry(client: &Client, (x, y, width, height): (i16, i16, u16, u16)) {
+    if x != client.x() {
+        client.set_x(x);
+    }
+
+    if y != client.y() {
+        client.set_y(y);
+    }
+
+    if (width, height) != (client.width(), client.height()) {
+        client.set_size(width, height);
+    }
+}
+
 fn get_serialized_state_file_path() -> String {
     format!("/tmp/vaporwm{}.json", std::env::var("DISPLAY").unwrap())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_raise_below_top() {
+        let stack = [false, false, false];
+
+        assert!(can_raise(&stack, 0, |minimized| *minimized));
+        assert!(can_raise(&stack, 1, |minimized| *minimized));
+    }
+
+    #[test]
+    fn can_raise_top_only_if_minimized() {
+        assert!(!can_raise(&[false, false], 1, |minimized| *minimized));
+        assert!(can_raise(&[true, true], 1, |minimized| *minimized));
+    }
+
+    #[test]
+    fn can_raise_out_of_range_index() {
+        assert!(!can_raise(&[false, false], 2, |minimized| *minimized));
+        assert!(!can_raise(&[true], 5, |minimized| *minimized));
+        assert!(!can_raise(&[] as &[bool], 0, |minimized| *minimized));
+    }
+
+    #[test]
+    fn can_focus_needs_a_way_to_get_focus() {
+        assert!(can_focus(false, true, false));
+        assert!(can_focus(false, false, true));
+        assert!(can_focus(false, true, true));
+        assert!(!can_focus(false, false, false));
+    }
+
+    #[test]
+    fn can_focus_skips_minimized() {
+        assert!(!can_focus(true, true, false));
+        assert!(!can_focus(true, false, true));
+    }
+
+    #[test]
+    fn focus_skips_no_input_client() {
+        // (minimized, accepts input, takes focus) from the bottom of the stack
+        let stack = [
+            (false, true, false),
+            (false, false, true),
+            (false, false, false),
+        ];
+
+        let focused = stack
+            .iter()
+            .rposition(|(minimized, accepts_input, takes_focus)| {
+                can_focus(*minimized, *accepts_input, *takes_focus)
+            });
+
+        assert_eq!(focused, Some(1));
+    }
+}
@@ -0,0 +1,531 @@
+// Runs vaporwm on a nested Xephyr display, creates windows with x11rb and checks the state it
+// reports over the IPC socket. Needs Xephyr, so it's ignored by default:
+// cargo test --test x11 -- --ignored --test-threads=1
+
+use serde_json::Value;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::AtomEnum;
+use x11rb::protocol::xproto::ButtonPressEvent;
+use x11rb::protocol::xproto::ButtonReleaseEvent;
+use x11rb::protocol::xproto::ConnectionExt;
+use x11rb::protocol::xproto::CreateWindowAux;
+use x11rb::protocol::xproto::EventMask;
+use x11rb::protocol::xproto::GrabMode;
+use x11rb::protocol::xproto::GrabStatus;
+use x11rb::protocol::xproto::KeyButMask;
+use x11rb::protocol::xproto::Motion;
+use x11rb::protocol::xproto::MotionNotifyEvent;
+use x11rb::protocol::xproto::PropMode;
+use x11rb::protocol::xproto::WindowClass;
+use x11rb::protocol::xproto::BUTTON_PRESS_EVENT;
+use x11rb::protocol::xproto::BUTTON_RELEASE_EVENT;
+use x11rb::protocol::xproto::MOTION_NOTIFY_EVENT;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+// Far enough from the usual ones not to clash with a running session
+const DISPLAY: &str = ":19";
+
+// How long to wait for Xephyr, vaporwm or a state change
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Xephyr and vaporwm running on it, both are killed when it's dropped
+struct Session {
+    xephyr: Child,
+    wm: Child,
+
+    // vaporwm's $XDG_RUNTIME_DIR, where it puts the IPC socket
+    runtime_dir: PathBuf,
+
+    connection: RustConnection,
+    screen_num: usize,
+}
+
+impl Session {
+    fn start() -> Self {
+        // Otherwise the state saved by a previous run would be restored
+        let _ = std::fs::remove_file(format!("/tmp/vaporwm{DISPLAY}.json"));
+
+        let xephyr = Command::new("Xephyr")
+            .args(["-br", "-ac", "-noreset", "-screen", "1280x720", DISPLAY])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Couldn't start Xephyr");
+
+        let (connection, screen_num) = wait_for(|| x11rb::connect(Some(DISPLAY)).ok())
+            .expect("Xephyr didn't start accepting connections");
+
+        let runtime_dir =
+            std::env::temp_dir().join(format!("vaporwm-x11-test-{}", std::process::id()));
+        std::fs::create_dir_all(&runtime_dir).unwrap();
+
+        let wm = Command::new(env!("CARGO_BIN_EXE_vaporwm"))
+            .env("DISPLAY", DISPLAY)
+            .env("XDG_RUNTIME_DIR", &runtime_dir)
+            .spawn()
+            .expect("Couldn't start vaporwm");
+
+        let session = Self {
+            xephyr,
+            wm,
+            runtime_dir,
+            connection,
+            screen_num,
+        };
+
+        session.settle(|_| true);
+        session
+    }
+
+    fn dump_state(&self) -> Option<Value> {
+        let mut stream =
+            UnixStream::connect(self.runtime_dir.join(format!("vaporwm{DISPLAY}.sock"))).ok()?;
+        writeln!(stream, "dump-state").ok()?;
+
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response).ok()?;
+
+        serde_json::from_str(&response).ok()
+    }
+
+    // Waits until the reported state satisfies 'condition' and returns it
+    fn settle(&self, condition: impl Fn(&Value) -> bool) -> Value {
+        wait_for(|| self.dump_state().filter(&condition)).unwrap_or_else(|| {
+            panic!(
+                "State didn't settle, last one: {}",
+                self.dump_state().unwrap_or_default()
+            )
+        })
+    }
+
+    fn create_window(&self, class: &str) -> u32 {
+        let screen = &self.connection.setup().roots[self.screen_num];
+        let id = self.connection.generate_id().unwrap();
+
+        self.connection
+            .create_window(
+                screen.root_depth,
+                id,
+                screen.root,
+                0,
+                0,
+                400,
+                300,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                screen.root_visual,
+                &CreateWindowAux::new().background_pixel(screen.white_pixel),
+            )
+            .unwrap();
+
+        // Instance and class, each terminated by a null byte
+        self.connection
+            .change_property8(
+                PropMode::REPLACE,
+                id,
+                AtomEnum::WM_CLASS,
+                AtomEnum::STRING,
+                format!("{class}\0{class}\0").as_bytes(),
+            )
+            .unwrap();
+
+        self.connection.map_window(id).unwrap();
+        self.connection.flush().unwrap();
+
+        id
+    }
+
+    // The bottom panel spans the whole width of the bottom edge
+    fn find_bottom_panel(&self) -> u32 {
+        let screen = &self.connection.setup().roots[self.screen_num];
+        let tree = self
+            .connection
+            .query_tree(screen.root)
+            .unwrap()
+            .reply()
+            .unwrap();
+
+        tree.children
+            .into_iter()
+            .find(|id| {
+                self.connection
+                    .get_geometry(*id)
+                    .unwrap()
+                    .reply()
+                    .is_ok_and(|geometry| {
+                        geometry.width == screen.width_in_pixels
+                            && geometry.y as u16 + geometry.height == screen.height_in_pixels
+                    })
+            })
+            .expect("There's no bottom panel")
+    }
+
+    // A left click as if it was made by the user, 'x' is relative to the window
+    fn click(&self, window: u32, x: i16) {
+        let screen = &self.connection.setup().roots[self.screen_num];
+
+        let event = ButtonPressEvent {
+            response_type: BUTTON_PRESS_EVENT,
+            detail: 1,
+            sequence: 0,
+            time: x11rb::CURRENT_TIME,
+            root: screen.root,
+            event: window,
+            child: x11rb::NONE,
+            root_x: x,
+            root_y: 0,
+            event_x: x,
+            event_y: 5,
+            state: Default::default(),
+            same_screen: true,
+        };
+
+        self.connection
+            .send_event(false, window, EventMask::BUTTON_PRESS, event)
+            .unwrap();
+
+        self.connection.flush().unwrap();
+    }
+
+    // The frame vaporwm has reparented the window into
+    fn find_container(&self, window: u32) -> u32 {
+        self.connection
+            .query_tree(window)
+            .unwrap()
+            .reply()
+            .unwrap()
+            .parent
+    }
+
+    // Synthetic events with an empty mask go to whoever created the window, vaporwm for a container.
+    // Pointer positions are relative to the root, vaporwm only looks at those while dragging
+    fn start_drag(&self, container: u32, x: i16, y: i16) {
+        let event = ButtonPressEvent {
+            response_type: BUTTON_PRESS_EVENT,
+            detail: 1,
+            sequence: 0,
+            time: x11rb::CURRENT_TIME,
+            root: self.root(),
+            event: container,
+            child: x11rb::NONE,
+            root_x: x,
+            root_y: y,
+            event_x: 0,
+            event_y: 0,
+            state: KeyButMask::MOD4,
+            same_screen: true,
+        };
+
+        self.connection
+            .send_event(false, container, EventMask::NO_EVENT, event)
+            .unwrap();
+
+        self.connection.flush().unwrap();
+    }
+
+    fn drag_to(&self, container: u32, x: i16, y: i16) {
+        let event = MotionNotifyEvent {
+            response_type: MOTION_NOTIFY_EVENT,
+            detail: Motion::NORMAL,
+            sequence: 0,
+            time: x11rb::CURRENT_TIME,
+            root: self.root(),
+            event: container,
+            child: x11rb::NONE,
+            root_x: x,
+            root_y: y,
+            event_x: 0,
+            event_y: 0,
+            state: KeyButMask::MOD4 | KeyButMask::BUTTON1,
+            same_screen: true,
+        };
+
+        self.connection
+            .send_event(false, container, EventMask::NO_EVENT, event)
+            .unwrap();
+
+        self.connection.flush().unwrap();
+    }
+
+    fn stop_drag(&self, container: u32, x: i16, y: i16) {
+        let event = ButtonReleaseEvent {
+            response_type: BUTTON_RELEASE_EVENT,
+            detail: 1,
+            sequence: 0,
+            time: x11rb::CURRENT_TIME,
+            root: self.root(),
+            event: container,
+            child: x11rb::NONE,
+            root_x: x,
+            root_y: y,
+            event_x: 0,
+            event_y: 0,
+            state: KeyButMask::MOD4 | KeyButMask::BUTTON1,
+            same_screen: true,
+        };
+
+        self.connection
+            .send_event(false, container, EventMask::NO_EVENT, event)
+            .unwrap();
+
+        self.connection.flush().unwrap();
+    }
+
+    fn root(&self) -> u32 {
+        self.connection.setup().roots[self.screen_num].root
+    }
+
+    // An unmapped window of the test's own to reparent clients into
+    fn create_holder_window(&self) -> u32 {
+        let screen = &self.connection.setup().roots[self.screen_num];
+        let id = self.connection.generate_id().unwrap();
+
+        self.connection
+            .create_window(
+                screen.root_depth,
+                id,
+                screen.root,
+                0,
+                0,
+                400,
+                300,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                screen.root_visual,
+                &CreateWindowAux::new(),
+            )
+            .unwrap();
+
+        self.connection.flush().unwrap();
+        id
+    }
+
+    fn reparent(&self, window: u32, parent: u32) {
+        self.connection
+            .reparent_window(window, parent, 0, 0)
+            .unwrap();
+        self.connection.flush().unwrap();
+    }
+
+    fn destroy_window(&self, id: u32) {
+        self.connection.destroy_window(id).unwrap();
+        self.connection.flush().unwrap();
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.wm.kill();
+        let _ = self.wm.wait();
+        let _ = self.xephyr.kill();
+        let _ = self.xephyr.wait();
+        let _ = std::fs::remove_dir_all(&self.runtime_dir);
+    }
+}
+
+fn wait_for<T>(mut attempt: impl FnMut() -> Option<T>) -> Option<T> {
+    let start = Instant::now();
+
+    while start.elapsed() < SETTLE_TIMEOUT {
+        if let Some(result) = attempt() {
+            return Some(result);
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    None
+}
+
+fn clients(state: &Value) -> Vec<&Value> {
+    state["workspaces"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|workspace| workspace["stack"].as_array().unwrap())
+        .collect()
+}
+
+fn sorted_ids<'a>(values: impl Iterator<Item = &'a Value>) -> Vec<u64> {
+    let mut ids = values
+        .map(|value| value.as_u64().unwrap())
+        .collect::<Vec<_>>();
+
+    ids.sort();
+    ids
+}
+
+#[test]
+#[ignore = "needs Xephyr"]
+fn maps_windows_on_the_active_workspace() {
+    let session = Session::start();
+
+    session.create_window("HarnessA");
+    session.create_window("HarnessB");
+
+    let state = session.settle(|state| clients(state).len() == 2);
+    let workspace = &state["workspaces"][0];
+
+    let mut classes = clients(&state)
+        .iter()
+        .map(|client| client["class"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+
+    classes.sort();
+
+    assert_eq!(state["active_workspace_index"], 0);
+    assert_eq!(classes, ["HarnessA", "HarnessB"]);
+
+    assert_eq!(
+        sorted_ids(workspace["tasklist"].as_array().unwrap().iter()),
+        sorted_ids(clients(&state).iter().map(|client| &client["id"]))
+    );
+    assert!(clients(&state).iter().all(|client| {
+        client["width"].as_u64().unwrap() > 0 && client["height"].as_u64().unwrap() > 0
+    }));
+}
+
+#[test]
+#[ignore = "needs Xephyr"]
+fn forgets_destroyed_windows() {
+    let session = Session::start();
+
+    let window = session.create_window("HarnessA");
+    session.create_window("HarnessB");
+    session.settle(|state| clients(state).len() == 2);
+
+    session.destroy_window(window);
+
+    let state = session.settle(|state| clients(state).len() == 1);
+    let workspace = &state["workspaces"][0];
+
+    assert_eq!(clients(&state)[0]["class"], "HarnessB");
+    assert_eq!(workspace["tasklist"].as_array().unwrap().len(), 1);
+}
+
+fn find_client(state: &Value, id: u32) -> Option<&Value> {
+    clients(state).into_iter().find(|client| client["id"] == id)
+}
+
+fn top_client_id(state: &Value) -> Option<u64> {
+    state["workspaces"][0]["stack"].as_array()?.last()?["id"].as_u64()
+}
+
+// Raising from the panel used to happen while the stack was still borrowed for the lookup
+#[test]
+#[ignore = "needs Xephyr"]
+fn raises_from_bottom_panel() {
+    let session = Session::start();
+
+    let first = session.create_window("HarnessA");
+    let second = session.create_window("HarnessB");
+
+    session.settle(|state| top_client_id(state) == Some(second as u64));
+
+    // The first entry is the first window, both by tasklist order and by class
+    session.click(session.find_bottom_panel(), 5);
+
+    let state = session.settle(|state| top_client_id(state) == Some(first as u64));
+    assert_eq!(clients(&state).len(), 2);
+}
+
+// Some toolkits move their windows out of the frame and back to the root themselves
+#[test]
+#[ignore = "needs Xephyr"]
+fn follows_self_reparenting_windows() {
+    let session = Session::start();
+
+    let window = session.create_window("HarnessA");
+    session.settle(|state| clients(state).len() == 1);
+
+    let holder = session.create_holder_window();
+    session.reparent(window, holder);
+    session.settle(|state| clients(state).is_empty());
+
+    // Still mapped, so it's managed again without a MapRequest
+    session.reparent(window, session.root());
+
+    let state = session.settle(|state| clients(state).len() == 1);
+    assert_eq!(clients(&state)[0]["id"], window);
+}
+
+// The drag keeps following the client it has started on, whatever gets mapped on top of it
+#[test]
+#[ignore = "needs Xephyr"]
+fn keeps_dragging_while_a_window_maps() {
+    let session = Session::start();
+
+    let window = session.create_window("HarnessA");
+    let state = session.settle(|state| clients(state).len() == 1);
+
+    let client = find_client(&state, window).unwrap();
+    let x = client["x"].as_i64().unwrap();
+    let y = client["y"].as_i64().unwrap();
+
+    let container = session.find_container(window);
+    session.start_drag(container, 600, 300);
+    session.drag_to(container, 610, 310);
+
+    let other = session.create_window("HarnessB");
+    session.settle(|state| find_client(state, other).is_some());
+
+    session.drag_to(container, 650, 340);
+    session.stop_drag(container, 650, 340);
+
+    let state = session
+        .settle(|state| find_client(state, window).is_some_and(|client| client["x"] == x + 50));
+
+    assert_eq!(find_client(&state, window).unwrap()["y"], y + 40);
+    assert_eq!(clients(&state).len(), 2);
+}
+
+// Destroying the dragged window cancels the drag, the release never comes
+#[test]
+#[ignore = "needs Xephyr"]
+fn cancels_the_drag_when_the_dragged_window_closes() {
+    let session = Session::start();
+
+    let window = session.create_window("HarnessA");
+    session.settle(|state| clients(state).len() == 1);
+
+    let container = session.find_container(window);
+    session.start_drag(container, 600, 300);
+    session.drag_to(container, 610, 310);
+
+    session.destroy_window(window);
+    session.settle(|state| clients(state).is_empty());
+
+    // Still managing windows, and without a pointer grab left behind
+    let other = session.create_window("HarnessB");
+    session.settle(|state| find_client(state, other).is_some());
+
+    let grab = session
+        .connection
+        .grab_pointer(
+            false,
+            session.root(),
+            EventMask::BUTTON_PRESS,
+            GrabMode::ASYNC,
+            GrabMode::ASYNC,
+            x11rb::NONE,
+            x11rb::NONE,
+            x11rb::CURRENT_TIME,
+        )
+        .unwrap()
+        .reply()
+        .unwrap();
+
+    assert_eq!(grab.status, GrabStatus::SUCCESS);
+}